@@ -0,0 +1,163 @@
+//! Tamper-evident audit log of the executed tasks.
+//!
+//! Log is a JSON Lines file ttr only appends to. Every entry contains the hash of the
+//! previous one and its own hash (SHA-256 of the entry serialized with an empty `hash`), so
+//! removing or editing an entry breaks the chain, which is detected by `ttr audit verify`.
+use crate::Result;
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Hash of the previous entry used for the first entry of the log
+const GENESIS: &str = "";
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Entry {
+    /// Number of the entry starting from 0
+    pub seq: u64,
+    /// Unix timestamp of the task start
+    pub started_at: u64,
+    pub user: String,
+    pub project: PathBuf,
+    /// Key path of the task from the root group
+    pub path: String,
+    pub name: String,
+    /// Command as it was run (verbose variant if the task was rerun with extra verbosity)
+    pub cmd: String,
+    pub working_dir: PathBuf,
+    pub duration_ms: u64,
+    /// `None` if the task was killed by a signal or launched on an external target
+    pub exit_code: Option<i32>,
+    /// Hash of the previous entry
+    pub prev: String,
+    pub hash: String,
+}
+
+impl Entry {
+    fn compute_hash(&self) -> Result<String> {
+        let entry = Entry {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let json = serde_json::to_vec(&entry)?;
+        Ok(format!("{:x}", Sha256::digest(json)))
+    }
+}
+
+/// Name of the user running ttr
+pub fn user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+/// Fails if the log can't be written, so the tasks are not run without being audited
+pub fn check(log: &Path) -> Result<()> {
+    let context = || format!("Unable to write audit log: {}", log.display());
+    if let Some(dir) = log.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(context)?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log)
+        .with_context(context)?;
+    Ok(())
+}
+
+/// Chains the entry to the last entry of the log and appends it
+pub fn append(log: &Path, mut entry: Entry) -> Result<()> {
+    let context = || format!("Unable to write audit log: {}", log.display());
+    if let Some(dir) = log.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(context)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(log)
+        .with_context(context)?;
+    // Other ttr instances can't append between reading the last entry and writing a new one
+    file.lock().with_context(context)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content).with_context(context)?;
+    let last = parse(&content)?.pop();
+    entry.seq = last.as_ref().map(|e| e.seq + 1).unwrap_or(0);
+    entry.prev = last.map(|e| e.hash).unwrap_or(GENESIS.to_string());
+    entry.hash = entry.compute_hash()?;
+
+    let mut line = serde_json::to_string(&entry)?;
+    line.push('\n');
+    file.write_all(line.as_bytes()).with_context(context)?;
+    Ok(())
+}
+
+/// Checks the hash chain of the log. Returns the number of entries verified
+pub fn verify(log: &Path) -> Result<usize> {
+    let entries = read(log)?;
+    let mut prev = GENESIS.to_string();
+    for (i, entry) in entries.iter().enumerate() {
+        let line = i + 1;
+        if entry.seq != i as u64 {
+            bail!("Line {}: expected entry #{}, found #{}", line, i, entry.seq);
+        }
+        if entry.prev != prev {
+            bail!("Line {}: previous entry is missing or modified", line);
+        }
+        if entry.compute_hash()? != entry.hash {
+            bail!("Line {}: entry is modified", line);
+        }
+        prev = entry.hash.clone();
+    }
+    Ok(entries.len())
+}
+
+fn read(log: &Path) -> Result<Vec<Entry>> {
+    let content = fs::read_to_string(log)
+        .with_context(|| format!("Unable to read audit log: {}", log.display()))?;
+    parse(&content)
+}
+
+fn parse(content: &str) -> Result<Vec<Entry>> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line).with_context(|| format!("Line {}: invalid entry", i + 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_hash_chain() {
+        let log = crate::temp::path("audit").unwrap().with_extension("jsonl");
+        let entry = |name: &str| Entry {
+            name: name.to_string(),
+            ..Entry::default()
+        };
+        append(&log, entry("build")).unwrap();
+        append(&log, entry("test")).unwrap();
+        append(&log, entry("deploy")).unwrap();
+        assert_eq!(3, verify(&log).unwrap());
+
+        let content = fs::read_to_string(&log).unwrap();
+        fs::write(&log, content.replace("\"test\"", "\"lint\"")).unwrap();
+        let error = verify(&log).unwrap_err().to_string();
+        assert_eq!("Line 2: entry is modified", error);
+
+        let lines = content.lines().collect::<Vec<_>>();
+        fs::write(&log, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert!(verify(&log).is_err());
+        fs::remove_file(&log).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+//! Time windows when the tasks of a group can be run (`available: {days: [mon-fri], hours:
+//! "09:00-18:00"}`). Outside of the window tasks are only run with `--override`.
+use crate::Result;
+use anyhow::{bail, Context};
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use serde::Deserialize;
+use std::fmt;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Availability {
+    /// days of the week or ranges of them (`mon-fri`, `sat`). Any day if empty
+    #[serde(default)]
+    days: Vec<String>,
+    /// range of the local time (`09:00-18:00`). Any time if not set
+    hours: Option<String>,
+}
+
+impl Availability {
+    /// Checks that the days and hours are valid
+    pub fn validate(&self) -> Result<()> {
+        for days in &self.days {
+            parse_days(days)?;
+        }
+        if let Some(hours) = &self.hours {
+            parse_hours(hours)?;
+        }
+        Ok(())
+    }
+
+    /// Returns true if the given local time is within the window
+    pub fn contains(&self, now: NaiveDateTime) -> Result<bool> {
+        let weekday = now.weekday().num_days_from_monday();
+        let mut day_matches = self.days.is_empty();
+        for days in &self.days {
+            let (from, to) = parse_days(days)?;
+            day_matches |= match from <= to {
+                true => (from..=to).contains(&weekday),
+                // Range over the weekend (`fri-mon`)
+                false => weekday >= from || weekday <= to,
+            };
+        }
+        let time_matches = match &self.hours {
+            Some(hours) => {
+                let (from, to) = parse_hours(hours)?;
+                let time = now.time();
+                match from <= to {
+                    true => from <= time && time < to,
+                    // Range over midnight (`22:00-06:00`)
+                    false => time >= from || time < to,
+                }
+            }
+            None => true,
+        };
+        Ok(day_matches && time_matches)
+    }
+}
+
+impl fmt::Display for Availability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = self.days.clone();
+        parts.extend(self.hours.clone());
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// Parses the day or the range of days into numbers of days from Monday
+fn parse_days(days: &str) -> Result<(u32, u32)> {
+    let day = |day: &str| -> Result<u32> {
+        let weekday = day
+            .trim()
+            .parse::<Weekday>()
+            .map_err(|_| anyhow::anyhow!("Invalid day of the week: {}", day))?;
+        Ok(weekday.num_days_from_monday())
+    };
+    match days.split_once('-') {
+        Some((from, to)) => Ok((day(from)?, day(to)?)),
+        None => Ok((day(days)?, day(days)?)),
+    }
+}
+
+fn parse_hours(hours: &str) -> Result<(NaiveTime, NaiveTime)> {
+    let time = |time: &str| {
+        NaiveTime::parse_from_str(time.trim(), "%H:%M")
+            .with_context(|| format!("Invalid time: {}", time))
+    };
+    let Some((from, to)) = hours.split_once('-') else {
+        bail!("Invalid hours (expected range like 09:00-18:00): {}", hours);
+    };
+    Ok((time(from)?, time(to)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, time: &str) -> NaiveDateTime {
+        // 2024-01-01 is Monday
+        let date = NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        date.and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+    }
+
+    fn window(days: &[&str], hours: Option<&str>) -> Availability {
+        Availability {
+            days: days.iter().map(|d| d.to_string()).collect(),
+            hours: hours.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn check_contains() {
+        let office = window(&["mon-fri"], Some("09:00-18:00"));
+        assert!(office.contains(at(1, "09:00")).unwrap());
+        assert!(!office.contains(at(1, "18:00")).unwrap());
+        assert!(!office.contains(at(6, "12:00")).unwrap());
+
+        let nights = window(&["fri-mon", "wed"], Some("22:00-06:00"));
+        assert!(nights.contains(at(7, "23:00")).unwrap());
+        assert!(nights.contains(at(3, "05:59")).unwrap());
+        assert!(!nights.contains(at(2, "23:00")).unwrap());
+
+        assert!(window(&[], None).contains(at(6, "12:00")).unwrap());
+    }
+
+    #[test]
+    fn check_validate() {
+        assert!(window(&["mon-fri"], Some("09:00-18:00")).validate().is_ok());
+        assert!(window(&["monday-fry"], None).validate().is_err());
+        assert!(window(&[], Some("9-18")).validate().is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! Integration with `git bisect`. The task is used as a test command: ttr drives
+//! `git bisect run` showing the progress and reports the first bad commit.
+use crate::{theme, Invocation, Result};
+use anyhow::{bail, Context};
+use crossterm::style::Stylize;
+use std::{
+    io::{BufRead, BufReader},
+    path::Path,
+    process::{Command, Stdio},
+};
+use tracing::info;
+
+/// Finds the first bad commit between `good` and `bad` revisions using the invocation as a test
+pub fn run(invocation: &Invocation, good: &str, bad: &str) -> Result<()> {
+    let dir = &invocation.working_dir;
+    git(dir, &["bisect", "start", bad, good])?;
+    let result = bisect(invocation);
+    // Returning the repository to the original state even if bisect failed
+    let reset = git(dir, &["bisect", "reset"]);
+    let culprit = result?;
+    reset?;
+
+    let summary = git(
+        dir,
+        &["show", "-s", "--format=%h %s (%an, %ar)", culprit.as_str()],
+    )?;
+    println!();
+    let title = "First bad commit:".with(theme::current().error()).bold();
+    println!("{}", theme::plain(format!("   {} {}", title, summary)));
+    Ok(())
+}
+
+/// Runs `git bisect run` and returns the hash of the first bad commit
+fn bisect(invocation: &Invocation) -> Result<String> {
+    info!(cmd = ?invocation.args, "running git bisect");
+    let mut child = Command::new("git")
+        .args(["bisect", "run"])
+        .args(invocation.command_line())
+        .envs(invocation.secrets.iter().map(|(k, v)| (k, v)))
+        .current_dir(&invocation.working_dir)
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Unable to run git bisect")?;
+
+    let mut culprit = None;
+    let mut step = 0;
+    let stdout = child.stdout.take().context("No stdout")?;
+    for line in BufReader::new(stdout).lines() {
+        let line = line?;
+        match parse_line(&line) {
+            Some(Progress::Step(left)) => {
+                step += 1;
+                let progress = format!("step {}: {}", step, left).stylize().yellow();
+                println!("   {} {}", "bisect".stylize().bold(), progress);
+            }
+            Some(Progress::Found(hash)) => culprit = Some(hash.to_string()),
+            None => println!("{}", line),
+        }
+    }
+    let status = child.wait()?;
+    match culprit {
+        Some(culprit) if status.success() => Ok(culprit),
+        _ => bail!("git bisect failed ({})", status),
+    }
+}
+
+enum Progress<'a> {
+    /// Bisect step started. Contains description of the revisions left
+    Step(&'a str),
+    /// First bad commit is found
+    Found(&'a str),
+}
+
+fn parse_line(line: &str) -> Option<Progress<'_>> {
+    if let Some(left) = line.strip_prefix("Bisecting: ") {
+        return Some(Progress::Step(left));
+    }
+    line.strip_suffix(" is the first bad commit")
+        .map(Progress::Found)
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stderr(Stdio::inherit())
+        .output()
+        .context("Unable to run git")?;
+    if !output.status.success() {
+        bail!("git {} failed ({})", args.join(" "), output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_parse_line() {
+        let line = "Bisecting: 3 revisions left to test after this (roughly 2 steps)";
+        let Some(Progress::Step(left)) = parse_line(line) else {
+            panic!("Step expected");
+        };
+        assert_eq!(
+            "3 revisions left to test after this (roughly 2 steps)",
+            left
+        );
+
+        let line = "1a2b3c4d is the first bad commit";
+        assert!(matches!(
+            parse_line(line),
+            Some(Progress::Found("1a2b3c4d"))
+        ));
+        assert!(parse_line("running 'sh' '-c' 'exec make test'").is_none());
+    }
+}
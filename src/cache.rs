@@ -0,0 +1,144 @@
+//! Skipping of up to date tasks.
+//!
+//! Task can declare `inputs` and `outputs` globs. After a successful run the hash of the inputs
+//! is stored in the state. Next time the task is skipped if the inputs hash is the same and all
+//! the outputs exist.
+use crate::{Result, Task};
+use anyhow::Context;
+use std::{
+    collections::HashMap,
+    fs,
+    hash::Hasher,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// File produced by the task
+pub struct Artifact {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Expands globs relative to `dir`. Returns sorted list of matching files
+pub fn expand(patterns: &[String], dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for pattern in patterns {
+        let pattern = dir.join(pattern);
+        let pattern = pattern.to_string_lossy();
+        let paths = glob::glob(&pattern).with_context(|| format!("Invalid glob: {}", pattern))?;
+        for path in paths {
+            let path = path?;
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Hash of the task command and the content of its inputs. `None` if task has no inputs
+pub fn inputs_hash(task: &Task, dir: &Path) -> Result<Option<String>> {
+    if task.inputs.is_empty() {
+        return Ok(None);
+    }
+    let mut hasher = Fnv64::default();
+    hasher.write(task.command().as_bytes());
+    for file in expand(&task.inputs, dir)? {
+        hasher.write(file.to_string_lossy().as_bytes());
+        hasher.write_u8(0);
+        hasher.write(&fs::read(&file)?);
+        hasher.write_u8(0);
+    }
+    Ok(Some(format!("{:016x}", hasher.finish())))
+}
+
+/// Returns true if every output glob matches at least one file
+pub fn outputs_exist(task: &Task, dir: &Path) -> Result<bool> {
+    for pattern in &task.outputs {
+        if expand(std::slice::from_ref(pattern), dir)?.is_empty() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Modification times of the task outputs
+pub fn outputs_snapshot(task: &Task, dir: &Path) -> Result<HashMap<PathBuf, SystemTime>> {
+    let mut snapshot = HashMap::new();
+    for path in expand(&task.outputs, dir)? {
+        let modified = fs::metadata(&path)?.modified()?;
+        snapshot.insert(path, modified);
+    }
+    Ok(snapshot)
+}
+
+/// Returns outputs of the task created or modified since the `snapshot` was taken.
+///
+/// Modification times are compared instead of checking against the task start time, because
+/// file system timestamps are coarser than the system clock
+pub fn updated_outputs(
+    task: &Task,
+    dir: &Path,
+    snapshot: &HashMap<PathBuf, SystemTime>,
+) -> Result<Vec<Artifact>> {
+    let mut artifacts = vec![];
+    for path in expand(&task.outputs, dir)? {
+        let metadata = fs::metadata(&path)?;
+        if snapshot.get(&path) != Some(&metadata.modified()?) {
+            let size = metadata.len();
+            artifacts.push(Artifact { path, size });
+        }
+    }
+    Ok(artifacts)
+}
+
+/// FNV-1a hash. Unlike [`std::collections::hash_map::DefaultHasher`] it is stable between
+/// Rust releases, so it can be persisted
+struct Fnv64(u64);
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv64 {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_fnv() {
+        let mut hasher = Fnv64::default();
+        hasher.write(b"a");
+        assert_eq!(0xaf63dc4c8601ec8c, hasher.finish());
+    }
+
+    #[test]
+    fn check_expand() {
+        let dir = std::env::temp_dir().join(format!("ttr-cache-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/a.rs"), "a").unwrap();
+        fs::write(dir.join("src/b.rs"), "b").unwrap();
+        fs::write(dir.join("README"), "").unwrap();
+
+        let patterns = vec!["src/*.rs".to_string(), "src/a.rs".to_string()];
+        let files = expand(&patterns, &dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(vec![dir.join("src/a.rs"), dir.join("src/b.rs")], files);
+    }
+}
@@ -0,0 +1,306 @@
+//! Capturing of task output.
+//!
+//! Output of a task is streamed to the terminal and simultaneously stored in an [`OutputBuffer`].
+//! The buffer keeps only the tail of the output in memory. Once the output grows over the memory
+//! limit, the whole output is spilled to a temporary file, so a task printing gigabytes doesn't
+//! exhaust memory.
+//!
+//! Output can also be appended to a log file (`log:` of the task or `--log-dir`), so it stays
+//! available for postmortems after ttr exits.
+use crate::{temp, Result};
+use anyhow::{anyhow, bail, Context};
+use chrono::{
+    format::{Item, StrftimeItems},
+    NaiveDateTime,
+};
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process::Child,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+/// Default memory limit of the output buffer
+pub const DEFAULT_LIMIT: usize = 1024 * 1024;
+
+pub struct OutputBuffer {
+    limit: usize,
+    /// Tail of the output no longer than `limit` bytes
+    tail: VecDeque<u8>,
+    /// Total number of bytes written
+    len: usize,
+    spill: Option<Spill>,
+    /// Spill file is left in place when the buffer is dropped
+    kept: bool,
+}
+
+struct Spill {
+    path: PathBuf,
+    file: BufWriter<File>,
+}
+
+impl OutputBuffer {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            tail: VecDeque::new(),
+            len: 0,
+            spill: None,
+            kept: false,
+        }
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.len += data.len();
+        if self.spill.is_none() && self.len > self.limit {
+            let path = temp::path("output")?;
+            let mut file = BufWriter::new(temp::create(&path)?);
+            let (a, b) = self.tail.as_slices();
+            file.write_all(a)?;
+            file.write_all(b)?;
+            self.spill = Some(Spill { path, file });
+        }
+        if let Some(spill) = &mut self.spill {
+            spill.file.write_all(data)?;
+        }
+
+        let data = &data[data.len().saturating_sub(self.limit)..];
+        let excess = (self.tail.len() + data.len()).saturating_sub(self.limit);
+        self.tail.drain(..excess);
+        self.tail.extend(data);
+        Ok(())
+    }
+
+    /// Total size of the output in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Writes the whole output to `out`
+    pub fn replay(&mut self, out: &mut impl Write) -> Result<()> {
+        match &mut self.spill {
+            Some(spill) => {
+                spill.file.flush()?;
+                io::copy(&mut File::open(&spill.path)?, out)?;
+            }
+            None => {
+                let (a, b) = self.tail.as_slices();
+                out.write_all(a)?;
+                out.write_all(b)?;
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Returns a path to the file with the whole output.
+    ///
+    /// If output was not spilled to disk, it is written to a new temporary file
+    pub fn persist(&mut self) -> Result<&Path> {
+        if self.spill.is_none() {
+            let path = temp::path("output")?;
+            let (a, b) = self.tail.as_slices();
+            temp::create(&path)?.write_all(&[a, b].concat())?;
+            self.spill = Some(Spill {
+                file: BufWriter::new(File::options().append(true).open(&path)?),
+                path,
+            });
+        }
+        let spill = self.spill.as_mut().unwrap();
+        spill.file.flush()?;
+        Ok(&spill.path)
+    }
+
+    /// Persists the output to the file which is not removed when the buffer is dropped
+    pub fn keep(&mut self) -> Result<&Path> {
+        self.kept = true;
+        self.persist()
+    }
+}
+
+impl Drop for OutputBuffer {
+    fn drop(&mut self) {
+        if let Some(spill) = self.spill.as_ref().filter(|_| !self.kept) {
+            let _ = fs::remove_file(&spill.path);
+        }
+    }
+}
+
+/// Output capture of a running process
+pub struct Capture {
+    buffer: Arc<Mutex<OutputBuffer>>,
+    threads: Vec<JoinHandle<io::Result<()>>>,
+}
+
+impl Capture {
+    /// Starts capturing piped stdout and stderr of the child process. If `silent` is set the
+    /// output is not streamed to the terminal. Both streams are appended to the `log` if given
+    pub fn start(child: &mut Child, limit: usize, silent: bool, log: Option<File>) -> Self {
+        let buffer = Arc::new(Mutex::new(OutputBuffer::new(limit)));
+        let log = log.map(|file| Arc::new(Mutex::new(file)));
+        let mut threads = vec![];
+        let terminal = |stream: Box<dyn Write + Send>| match silent {
+            true => Box::new(io::sink()),
+            false => stream,
+        };
+        if let Some(stdout) = child.stdout.take() {
+            let output = terminal(Box::new(io::stdout()));
+            threads.push(tee(stdout, output, Arc::clone(&buffer), log.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            let output = terminal(Box::new(io::stderr()));
+            threads.push(tee(stderr, output, Arc::clone(&buffer), log));
+        }
+        Self { buffer, threads }
+    }
+
+    /// Waits for the output streams to be closed and returns captured output
+    pub fn finish(self) -> Result<OutputBuffer> {
+        for thread in self.threads {
+            thread
+                .join()
+                .map_err(|_| anyhow!("Output capture thread panicked"))??;
+        }
+        let buffer =
+            Arc::try_unwrap(self.buffer).map_err(|_| anyhow!("Output buffer is still in use"))?;
+        Ok(buffer.into_inner().unwrap())
+    }
+}
+
+fn tee(
+    mut input: impl Read + Send + 'static,
+    mut output: impl Write + Send + 'static,
+    buffer: Arc<Mutex<OutputBuffer>>,
+    log: Option<Arc<Mutex<File>>>,
+) -> JoinHandle<io::Result<()>> {
+    thread::spawn(move || {
+        let mut chunk = [0; 8192];
+        loop {
+            let size = input.read(&mut chunk)?;
+            if size == 0 {
+                break Ok(());
+            }
+            output.write_all(&chunk[..size])?;
+            output.flush()?;
+            buffer.lock().unwrap().write(&chunk[..size])?;
+            if let Some(log) = &log {
+                log.lock().unwrap().write_all(&chunk[..size])?;
+            }
+        }
+    })
+}
+
+/// Path of the log file with strftime-style specifiers (`build-%Y%m%d.log`) replaced with the
+/// start time of the run
+pub fn log_path(template: &str, started_at: NaiveDateTime) -> Result<PathBuf> {
+    let items = StrftimeItems::new(template).collect::<Vec<_>>();
+    if items.contains(&Item::Error) {
+        bail!("Invalid log file name: {}", template);
+    }
+    Ok(started_at
+        .format_with_items(items.iter())
+        .to_string()
+        .into())
+}
+
+/// Opens the log file for appending, creating the missing directories
+pub fn open_log(path: &Path) -> Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    File::options()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Unable to open log file {}", path.display()))
+}
+
+/// Parses size with an optional `K`, `M` or `G` suffix (eg. `512K`)
+pub fn parse_size(size: &str) -> Result<usize> {
+    let size = size.trim();
+    let (number, multiplier) = match size.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&size[..i], 1024),
+        Some((i, 'M' | 'm')) => (&size[..i], 1024 * 1024),
+        Some((i, 'G' | 'g')) => (&size[..i], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    Ok(number.trim().parse::<usize>()? * multiplier)
+}
+
+/// Formats size in bytes using the largest suffix fitting it (eg. `1.5K`)
+pub fn format_size(size: u64) -> String {
+    let mut size = size as f64;
+    for suffix in ["B", "K", "M"] {
+        if size < 1024.0 {
+            return match suffix {
+                "B" => format!("{}{}", size, suffix),
+                _ => format!("{:.1}{}", size, suffix),
+            };
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1}G", size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_output_buffer_spill() {
+        let mut buffer = OutputBuffer::new(8);
+        buffer.write(b"one\ntwo\n").unwrap();
+        assert!(buffer.spill.is_none());
+        buffer.write(b"three\n").unwrap();
+        assert_eq!(b"o\nthree\n", &buffer.tail.make_contiguous()[..]);
+        assert_eq!(14, buffer.len());
+
+        let mut replayed = vec![];
+        buffer.replay(&mut replayed).unwrap();
+        assert_eq!(b"one\ntwo\nthree\n", &replayed[..]);
+
+        let path = buffer.persist().unwrap().to_path_buf();
+        assert_eq!("one\ntwo\nthree\n", fs::read_to_string(&path).unwrap());
+        drop(buffer);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn check_keep() {
+        let mut buffer = OutputBuffer::new(1024);
+        buffer.write(b"output\n").unwrap();
+        let path = buffer.keep().unwrap().to_path_buf();
+        drop(buffer);
+        assert_eq!("output\n", fs::read_to_string(&path).unwrap());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn check_log_path() {
+        let started_at = chrono::NaiveDate::from_ymd_opt(2024, 3, 7)
+            .unwrap()
+            .and_hms_opt(9, 5, 0)
+            .unwrap();
+        let path = log_path("logs/build-%Y%m%d-%H%M%S.log", started_at).unwrap();
+        assert_eq!(PathBuf::from("logs/build-20240307-090500.log"), path);
+        assert!(log_path("build-%Q.log", started_at).is_err());
+    }
+
+    #[test]
+    fn check_parse_size() {
+        assert_eq!(512, parse_size("512").unwrap());
+        assert_eq!(2 * 1024 * 1024, parse_size("2M").unwrap());
+        assert!(parse_size("M").is_err());
+    }
+
+    #[test]
+    fn check_format_size() {
+        assert_eq!("512B", format_size(512));
+        assert_eq!("1.5K", format_size(1536));
+        assert_eq!("2.0M", format_size(2 * 1024 * 1024));
+        assert_eq!("3.0G", format_size(3 * 1024 * 1024 * 1024));
+    }
+}
@@ -0,0 +1,149 @@
+//! Tracking of file system side effects of a task.
+//!
+//! Snapshot of the working directory and the dotfiles in the home directory is taken before
+//! the task is run and compared with the one taken after it exited. Useful to see what an
+//! unfamiliar setup script did.
+use crate::Result;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tracing::warn;
+
+/// Maximum number of files in the working directory tracked
+const MAX_FILES: usize = 10_000;
+
+/// Directories not descended into
+const IGNORED_DIRS: [&str; 1] = [".git"];
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Created(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+}
+
+/// Modification times of the tracked files
+pub struct Snapshot {
+    files: HashMap<PathBuf, SystemTime>,
+}
+
+impl Snapshot {
+    pub fn take(working_dir: &Path) -> Result<Self> {
+        let Self { mut files } = Self::take_dir(working_dir)?;
+        // Home directory may be missing or unreadable (eg. in a container)
+        if let Some(entries) = dirs::home_dir().and_then(|home| fs::read_dir(home).ok()) {
+            for entry in entries.flatten() {
+                if entry.file_name().to_string_lossy().starts_with('.') {
+                    insert(&mut files, &entry);
+                }
+            }
+        }
+        Ok(Self { files })
+    }
+
+    /// Snapshot of the directory only (without the dotfiles in the home directory)
+    pub fn take_dir(dir: &Path) -> Result<Self> {
+        let mut files = HashMap::new();
+        walk(dir, &mut files)?;
+        if files.len() >= MAX_FILES {
+            warn!(dir = %dir.display(), "too many files, tracking is incomplete");
+        }
+        Ok(Self { files })
+    }
+
+    /// Returns changes made between `self` and the `after` snapshot, sorted by path
+    pub fn diff(&self, after: &Snapshot) -> Vec<Change> {
+        let mut changes = vec![];
+        for (path, modified) in &after.files {
+            match self.files.get(path) {
+                None => changes.push(Change::Created(path.clone())),
+                Some(before) if before != modified => changes.push(Change::Modified(path.clone())),
+                Some(_) => {}
+            }
+        }
+        for path in self.files.keys() {
+            if !after.files.contains_key(path) {
+                changes.push(Change::Removed(path.clone()));
+            }
+        }
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        changes
+    }
+}
+
+impl Change {
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Created(path) | Change::Modified(path) | Change::Removed(path) => path,
+        }
+    }
+
+    /// Single character marker of the change kind (`+`, `~` or `-`)
+    pub fn marker(&self) -> char {
+        match self {
+            Change::Created(_) => '+',
+            Change::Modified(_) => '~',
+            Change::Removed(_) => '-',
+        }
+    }
+}
+
+/// Unreadable subdirectories (eg. permission denied) are skipped, so tracking is incomplete
+fn walk(dir: &Path, files: &mut HashMap<PathBuf, SystemTime>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        if files.len() >= MAX_FILES {
+            break;
+        }
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if IGNORED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref()) {
+                continue;
+            }
+            if let Err(e) = walk(&entry.path(), files) {
+                warn!(dir = %entry.path().display(), "unable to track changes: {}", e);
+            }
+        } else {
+            insert(files, &entry);
+        }
+    }
+    Ok(())
+}
+
+/// Files removed while the snapshot is taken are skipped
+fn insert(files: &mut HashMap<PathBuf, SystemTime>, entry: &fs::DirEntry) {
+    if let Ok(modified) = entry.metadata().and_then(|m| m.modified()) {
+        files.insert(entry.path(), modified);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn check_diff() {
+        let time = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let snapshot = |files: &[(&str, u64)]| Snapshot {
+            files: files.iter().map(|(p, t)| (p.into(), time(*t))).collect(),
+        };
+        let before = snapshot(&[("a", 1), ("b", 1), ("c", 1)]);
+        let after = snapshot(&[("a", 1), ("b", 2), ("d", 2)]);
+        assert_eq!(
+            vec![
+                Change::Modified("b".into()),
+                Change::Removed("c".into()),
+                Change::Created("d".into())
+            ],
+            before.diff(&after)
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Printable reference of all the tasks (`ttr cheatsheet` and `ttr help-tasks`).
+use crate::{Group, Result};
+use clap::ValueEnum;
+use std::fmt::Write;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Markdown
+    Md,
+    Html,
+}
+
+/// Renders the task tree of the group. Every group is a section with a table of its tasks
+pub fn render(root: &Group, format: Format) -> Result<String> {
+    let mut out = String::new();
+    if format == Format::Html {
+        writeln!(out, "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Tasks</title></head>\n<body>")?;
+    }
+    render_group(&mut out, root, format, "", 1)?;
+    if format == Format::Html {
+        writeln!(out, "</body>\n</html>")?;
+    }
+    Ok(out)
+}
+
+fn render_group(
+    out: &mut String,
+    group: &Group,
+    format: Format,
+    path: &str,
+    level: usize,
+) -> Result<()> {
+    let title = match path {
+        "" => "Tasks".to_string(),
+        _ => format!("{} ({})", group.name, path),
+    };
+    let level = level.min(6);
+    match format {
+        Format::Md => writeln!(out, "{} {}\n", "#".repeat(level), md(&title))?,
+        Format::Html => writeln!(out, "<h{level}>{}</h{level}>", html(&title), level = level)?,
+    }
+    if let Some(description) = &group.description {
+        match format {
+            Format::Md => writeln!(out, "{}\n", description.trim_end())?,
+            Format::Html => writeln!(out, "<p>{}</p>", html(description))?,
+        }
+    }
+    if !group.tasks.is_empty() {
+        match format {
+            Format::Md => {
+                writeln!(out, "| Keys | Task | Description |")?;
+                writeln!(out, "|------|------|-------------|")?;
+                for task in &group.tasks {
+                    let description = task.description.as_deref().unwrap_or_default();
+                    writeln!(
+                        out,
+                        "| `{}` | {} | {} |",
+                        task.path,
+                        md(&task.name),
+                        md(description)
+                    )?;
+                }
+                writeln!(out)?;
+            }
+            Format::Html => {
+                writeln!(
+                    out,
+                    "<table>\n<tr><th>Keys</th><th>Task</th><th>Description</th></tr>"
+                )?;
+                for task in &group.tasks {
+                    let description = task.description.as_deref().unwrap_or_default();
+                    writeln!(
+                        out,
+                        "<tr><td><kbd>{}</kbd></td><td>{}</td><td>{}</td></tr>",
+                        html(&task.path),
+                        html(&task.name),
+                        html(description)
+                    )?;
+                }
+                writeln!(out, "</table>")?;
+            }
+        }
+    }
+    for child in &group.groups {
+        let path = format!("{}{}", path, child.key);
+        render_group(out, child, format, &path, level + 1)?;
+    }
+    Ok(())
+}
+
+/// Renders the task tree as plain text for the terminal. Each line is the key path, the name
+/// of the task or group (indented by the depth) and the description
+pub fn tree(root: &Group) -> Result<String> {
+    let mut lines = vec![];
+    collect_tree(&mut lines, root, "", 0);
+    let path_width = lines.iter().map(|(p, _, _)| p.len()).max().unwrap_or(0);
+    let name_width = lines
+        .iter()
+        .map(|(_, n, _)| n.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for (path, name, description) in lines {
+        let line = format!(
+            "  {:path_width$}  {:name_width$}  {}",
+            path,
+            name,
+            description,
+            path_width = path_width,
+            name_width = name_width
+        );
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(out)
+}
+
+fn collect_tree(
+    lines: &mut Vec<(String, String, String)>,
+    group: &Group,
+    path: &str,
+    depth: usize,
+) {
+    let indent = "  ".repeat(depth);
+    for task in &group.tasks {
+        let description = task.description.as_deref().unwrap_or_default();
+        let description = description.lines().next().unwrap_or_default();
+        lines.push((
+            task.path.clone(),
+            format!("{}{}", indent, task.name),
+            description.to_string(),
+        ));
+    }
+    for child in &group.groups {
+        let path = format!("{}{}", path, child.key);
+        let description = child.description.as_deref().unwrap_or_default();
+        let description = description.lines().next().unwrap_or_default();
+        lines.push((
+            path.clone(),
+            format!("{}{}/", indent, child.name),
+            description.to_string(),
+        ));
+        collect_tree(lines, child, &path, depth + 1);
+    }
+}
+
+/// Escapes characters breaking Markdown table cells
+fn md(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_markdown() {
+        let yaml = "
+            name: ROOT
+            key: _
+            tasks:
+            - name: build | test
+              key: b
+              cmd: make
+              description: Builds everything
+            groups:
+            - name: git
+              key: g
+              description: Version control
+              tasks:
+              - name: status
+                key: s
+                cmd: git status
+        ";
+        let mut root: Group = serde_yaml::from_str(yaml).unwrap();
+        root.assign_paths("");
+        let expected = "# Tasks
+
+| Keys | Task | Description |
+|------|------|-------------|
+| `b` | build \\| test | Builds everything |
+
+## git (g)
+
+Version control
+
+| Keys | Task | Description |
+|------|------|-------------|
+| `gs` | status |  |
+
+";
+        assert_eq!(expected, render(&root, Format::Md).unwrap());
+    }
+
+    #[test]
+    fn check_tree() {
+        let yaml = "
+            name: ROOT
+            key: _
+            tasks:
+            - name: build
+              key: b
+              cmd: make
+              description: Builds everything
+            groups:
+            - name: git
+              key: g
+              tasks:
+              - name: status
+                key: s
+                cmd: git status
+        ";
+        let mut root: Group = serde_yaml::from_str(yaml).unwrap();
+        root.assign_paths("");
+        let expected = "  b   build     Builds everything\n  g   git/\n  gs    status\n";
+        assert_eq!(expected, tree(&root).unwrap());
+    }
+
+    #[test]
+    fn check_html_escaping() {
+        assert_eq!("a &lt;b&gt; &amp; &quot;c&quot;", html("a <b> & \"c\""));
+    }
+}
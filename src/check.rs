@@ -0,0 +1,438 @@
+//! Problems of the config reported by `ttr check`.
+//!
+//! Configs are checked one by one before merging (duplicate and reserved keys, empty commands)
+//! and then as they are merged, since the tasks shadowed by other configs are dropped silently.
+use crate::{
+    config::{Document, Location},
+    version, Group, Task,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Keys handled by the selector itself, so tasks and groups using them can't be selected
+const RESERVED_KEYS: [char; 3] = ['q', ' ', '!'];
+
+/// Minimum number of words for the commands to be compared for similarity. Shorter commands
+/// (`ls`, `make test`) are too often similar by accident
+const MIN_SIMILAR_WORDS: usize = 3;
+
+#[derive(Debug, PartialEq)]
+enum Similarity {
+    /// Commands are the same up to whitespace
+    Identical,
+    /// Commands differ in a single word
+    Similar,
+}
+
+#[derive(Debug, PartialEq)]
+struct Duplicate {
+    similarity: Similarity,
+    /// Key paths of the tasks
+    tasks: Vec<String>,
+}
+
+/// Config file as it was read before merging
+struct Source<'a> {
+    file: &'a Path,
+    root: &'a Group,
+    /// Lines of the tasks and groups, empty if the file can't be parsed to find them
+    locations: Vec<Location>,
+}
+
+impl<'a> Source<'a> {
+    fn new(file: &'a Path, root: &'a Group, text: Option<&str>) -> Self {
+        let locations = text
+            .and_then(|text| Document::parse(text).ok())
+            .map(|document| document.locations())
+            .unwrap_or_default();
+        Self {
+            file,
+            root,
+            locations,
+        }
+    }
+
+    /// `file:line` of the `nth` task or group with the given key path
+    fn at(&self, path: &str, is_group: bool, nth: usize) -> String {
+        let line = self
+            .locations
+            .iter()
+            .filter(|l| l.path == path && l.is_group == is_group)
+            .nth(nth);
+        match line {
+            Some(location) => format!("{}:{}", self.file.display(), location.line),
+            None => self.file.display().to_string(),
+        }
+    }
+}
+
+/// Returns the report of the problems of the configs (in the order they are merged), empty if
+/// there are none
+pub fn configs(configs: &[(PathBuf, Group)]) -> String {
+    let texts = configs
+        .iter()
+        .map(|(file, _)| fs::read_to_string(file).ok())
+        .collect::<Vec<_>>();
+    let sources = configs
+        .iter()
+        .zip(&texts)
+        .map(|((file, root), text)| Source::new(file, root, text.as_deref()))
+        .collect::<Vec<_>>();
+    problems(&sources)
+        .into_iter()
+        .map(|problem| problem + "\n")
+        .collect()
+}
+
+fn problems(sources: &[Source]) -> Vec<String> {
+    let mut problems = vec![];
+    for source in sources {
+        check_group(source, source.root, "", &mut problems);
+    }
+    let roots = sources
+        .iter()
+        .map(|source| (source, source.root))
+        .collect::<Vec<_>>();
+    check_merge(&roots, "", &mut problems);
+    problems
+}
+
+/// Problems of the group within a single config
+fn check_group(source: &Source, group: &Group, prefix: &str, problems: &mut Vec<String>) {
+    // Tasks are looked up before the groups in the selector
+    let items = group
+        .tasks
+        .iter()
+        .map(|t| (t.key, &t.name, false))
+        .chain(group.groups.iter().map(|g| (g.key, &g.name, true)));
+    let mut seen: Vec<(char, String)> = vec![];
+    let mut occurrences: HashMap<(char, bool), usize> = HashMap::new();
+    for (key, name, is_group) in items {
+        let nth = occurrences.entry((key, is_group)).or_default();
+        let at = source.at(&format!("{}{}", prefix, key), is_group, *nth);
+        *nth += 1;
+        let (what, title) = if is_group {
+            ("group", "Group")
+        } else {
+            ("task", "Task")
+        };
+        if RESERVED_KEYS.contains(&key) {
+            problems.push(format!(
+                "{}: {} {} uses reserved key '{}'",
+                at, title, name, key
+            ));
+        }
+        if let Some((_, other)) = seen.iter().find(|(k, _)| *k == key) {
+            problems.push(format!(
+                "{}: {} {} is unreachable, key '{}' is already used by {}",
+                at, title, name, key, other
+            ));
+        }
+        seen.push((key, format!("{} {}", what, name)));
+    }
+
+    let mut occurrences: HashMap<char, usize> = HashMap::new();
+    for task in &group.tasks {
+        let nth = occurrences.entry(task.key).or_default();
+        let at = source.at(&format!("{}{}", prefix, task.key), false, *nth);
+        *nth += 1;
+        // Tasks requiring a newer ttr are stripped to the name and key
+        let supported = version::require(task.requires_ttr.as_deref()).is_ok();
+        if supported && !task.is_composite() && task.command().trim().is_empty() {
+            problems.push(format!("{}: Task {} has an empty command", at, task.name));
+        }
+    }
+
+    for child in &group.groups {
+        check_group(source, child, &format!("{}{}", prefix, child.key), problems);
+    }
+}
+
+/// Problems of merging the groups with the same key path from different configs. Mirrors the
+/// rules of `merge_groups`: groups named differently than the first one are ignored and the
+/// first task or group bound to a key shadows the ones from the next configs
+fn check_merge(groups: &[(&Source, &Group)], path: &str, problems: &mut Vec<String>) {
+    let [(first_source, first), ..] = groups else {
+        return;
+    };
+    if groups.len() < 2 {
+        return;
+    }
+    let mut children: Vec<(char, Vec<(&Source, &Group)>)> = vec![];
+    let mut tasks: HashMap<char, (&Source, &Task)> = HashMap::new();
+    for (source, group) in groups {
+        if group.name != first.name || group.key != first.key {
+            problems.push(format!(
+                "{}: Group {} is ignored, its key path is used by group {} from {}",
+                source.at(path, true, 0),
+                group.name,
+                first.name,
+                first_source.file.display()
+            ));
+            continue;
+        }
+        for child in &group.groups {
+            match children.iter_mut().find(|(key, _)| *key == child.key) {
+                Some((_, similar)) => similar.push((source, child)),
+                None => children.push((child.key, vec![(source, child)])),
+            }
+        }
+        for task in &group.tasks {
+            let at = || source.at(&format!("{}{}", path, task.key), false, 0);
+            let shadowing_group = children
+                .iter()
+                .find(|(key, _)| *key == task.key)
+                .and_then(|(_, similar)| similar.iter().find(|(s, _)| s.file != source.file));
+            if let Some((other, group)) = shadowing_group {
+                problems.push(format!(
+                    "{}: Task {} is shadowed by group {} from {}",
+                    at(),
+                    task.name,
+                    group.name,
+                    other.at(&format!("{}{}", path, group.key), true, 0)
+                ));
+                continue;
+            }
+            match tasks.get(&task.key) {
+                Some((other, existing)) if other.file != source.file => {
+                    problems.push(format!(
+                        "{}: Task {} is shadowed by task {} from {}",
+                        at(),
+                        task.name,
+                        existing.name,
+                        other.at(&format!("{}{}", path, existing.key), false, 0)
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    tasks.insert(task.key, (source, task));
+                }
+            }
+        }
+    }
+
+    for (key, similar) in &children {
+        let child_path = format!("{}{}", path, key);
+        if let Some((other, task)) = tasks.get(key) {
+            for (source, group) in similar {
+                problems.push(format!(
+                    "{}: Group {} is unreachable, its key is used by task {} from {}",
+                    source.at(&child_path, true, 0),
+                    group.name,
+                    task.name,
+                    other.at(&child_path, false, 0)
+                ));
+            }
+        }
+        check_merge(similar, &child_path, problems);
+    }
+}
+
+/// Returns the report of the problems of the merged config, empty if there are none
+pub fn run(root: &Group) -> String {
+    let tasks = root.all_tasks();
+    let mut out = String::new();
+    for duplicate in duplicates(&tasks) {
+        let names = duplicate
+            .tasks
+            .iter()
+            .map(|path| {
+                let task = tasks.iter().find(|t| &t.path == path).unwrap();
+                format!("{} ({})", path, task.name)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let _ = match duplicate.similarity {
+            Similarity::Identical => writeln!(
+                out,
+                "Identical commands: {}. Consider keeping one task in a shared group",
+                names
+            ),
+            Similarity::Similar => writeln!(
+                out,
+                "Similar commands: {}. Consider one task with {{{{args}}}} instead",
+                names
+            ),
+        };
+    }
+    out
+}
+
+fn duplicates(tasks: &[&Task]) -> Vec<Duplicate> {
+    // Tasks with the same command and working directory
+    let mut identical: BTreeMap<(Option<&PathBuf>, Vec<&str>), Vec<String>> = BTreeMap::new();
+    for task in tasks {
+        let words = task.command().split_whitespace().collect::<Vec<_>>();
+        identical
+            .entry((task.working_dir.as_ref(), words))
+            .or_default()
+            .push(task.path.clone());
+    }
+    let commands = identical.into_iter().collect::<Vec<_>>();
+
+    let mut duplicates = vec![];
+    for ((_, _), paths) in &commands {
+        if paths.len() > 1 {
+            duplicates.push(Duplicate {
+                similarity: Similarity::Identical,
+                tasks: paths.clone(),
+            });
+        }
+    }
+
+    // Clusters of the commands differing in one word from some other command of the cluster
+    let mut cluster = (0..commands.len()).collect::<Vec<_>>();
+    fn root(cluster: &mut [usize], mut i: usize) -> usize {
+        while cluster[i] != i {
+            i = cluster[i];
+        }
+        i
+    }
+    for i in 0..commands.len() {
+        for j in i + 1..commands.len() {
+            let ((a_dir, a), _) = &commands[i];
+            let ((b_dir, b), _) = &commands[j];
+            let long_enough = a.len().min(b.len()) >= MIN_SIMILAR_WORDS;
+            if a_dir == b_dir && long_enough && distance(a, b) == 1 {
+                let (a, b) = (root(&mut cluster, i), root(&mut cluster, j));
+                cluster[b] = a;
+            }
+        }
+    }
+    let mut similar: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for (i, (_, paths)) in commands.iter().enumerate() {
+        similar
+            .entry(root(&mut cluster, i))
+            .or_default()
+            .extend(paths.iter().cloned());
+    }
+    for (i, paths) in similar {
+        if paths.len() > commands[i].1.len() {
+            duplicates.push(Duplicate {
+                similarity: Similarity::Similar,
+                tasks: paths,
+            });
+        }
+    }
+    duplicates
+}
+
+/// Edit distance between the commands in words
+fn distance(a: &[&str], b: &[&str]) -> usize {
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a_word) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_word) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_word != b_word);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_distance() {
+        let words = |s: &'static str| s.split_whitespace().collect::<Vec<_>>();
+        assert_eq!(0, distance(&words("a b c"), &words("a b c")));
+        assert_eq!(1, distance(&words("a b c"), &words("a x c")));
+        assert_eq!(1, distance(&words("a b c"), &words("a b c d")));
+        assert_eq!(2, distance(&words("a b c"), &words("b c d")));
+    }
+
+    #[test]
+    fn check_problems() {
+        let project = "
+tasks:
+  - {name: build, key: b, cmd: make}
+  - name: bench
+    key: b
+    cmd: make bench
+  - name: quit
+    key: q
+    cmd: '  '
+groups:
+  - name: git
+    key: g
+    tasks:
+      - {name: status, key: s, cmd: git status}
+";
+        let home = "
+tasks:
+  - {name: browse, key: b, cmd: open .}
+groups:
+  - name: github
+    key: g
+    tasks: []
+";
+        let configs = [("p.yaml", project), ("h.yaml", home)].map(|(file, yaml)| {
+            (
+                PathBuf::from(file),
+                yaml,
+                serde_yaml::from_str::<Group>(&format!("name: ROOT\nkey: _\n{}", yaml)).unwrap(),
+            )
+        });
+        let sources = configs
+            .iter()
+            .map(|(file, yaml, root)| Source::new(file, root, Some(yaml)))
+            .collect::<Vec<_>>();
+        let expected = vec![
+            "p.yaml:4: Task bench is unreachable, key 'b' is already used by task build",
+            "p.yaml:7: Task quit uses reserved key 'q'",
+            "p.yaml:7: Task quit has an empty command",
+            "h.yaml:3: Task browse is shadowed by task build from p.yaml:3",
+            "h.yaml:5: Group github is ignored, its key path is used by group git from p.yaml",
+        ];
+        assert_eq!(expected, problems(&sources));
+    }
+
+    #[test]
+    fn check_duplicates() {
+        let yaml = r#"
+            name: root
+            key: _
+            tasks:
+            - name: up
+              key: u
+              cmd: docker compose  up -d
+            - name: test
+              key: t
+              cmd: cargo test
+            groups:
+            - name: docker
+              key: d
+              tasks:
+              - name: up
+                key: u
+                cmd: docker compose up -d
+              - name: down
+                key: d
+                cmd: docker compose down -d
+              - name: run
+                key: r
+                cmd: cargo run
+        "#;
+        let mut root: Group = serde_yaml::from_str(yaml).unwrap();
+        root.assign_paths("");
+        let duplicates = duplicates(&root.all_tasks());
+        let expected = vec![
+            Duplicate {
+                similarity: Similarity::Identical,
+                tasks: vec!["u".to_string(), "du".to_string()],
+            },
+            Duplicate {
+                similarity: Similarity::Similar,
+                tasks: vec!["dd".to_string(), "u".to_string(), "du".to_string()],
+            },
+        ];
+        assert_eq!(expected, duplicates);
+    }
+}
@@ -0,0 +1,73 @@
+//! Conditions on the environment ttr runs in (`when: {devcontainer: true}`).
+//!
+//! Tasks whose conditions don't hold are removed from the config before the configs are merged,
+//! so the tasks meant for different environments can share the same key.
+use serde::Deserialize;
+use std::{env, sync::OnceLock};
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct When {
+    /// ttr is running inside a devcontainer or a Codespace (or not, if false)
+    pub devcontainer: Option<bool>,
+}
+
+impl When {
+    /// Returns true if all the conditions hold
+    pub fn holds(&self) -> bool {
+        self.holds_in(Environment::current())
+    }
+
+    fn holds_in(&self, environment: &Environment) -> bool {
+        self.devcontainer
+            .is_none_or(|devcontainer| devcontainer == environment.devcontainer)
+    }
+}
+
+/// Environment ttr is running in, detected once
+pub struct Environment {
+    pub devcontainer: bool,
+}
+
+impl Environment {
+    pub fn current() -> &'static Self {
+        static ENVIRONMENT: OnceLock<Environment> = OnceLock::new();
+        ENVIRONMENT.get_or_init(|| Environment {
+            devcontainer: is_devcontainer(|name| env::var(name).ok()),
+        })
+    }
+}
+
+/// Variables set by VS Code Dev Containers, GitHub Codespaces and devcontainer images
+const DEVCONTAINER_VARIABLES: [&str; 3] = ["REMOTE_CONTAINERS", "CODESPACES", "DEVCONTAINER"];
+
+fn is_devcontainer(var: impl Fn(&str) -> Option<String>) -> bool {
+    DEVCONTAINER_VARIABLES
+        .iter()
+        .any(|name| var(name).is_some_and(|value| value == "true"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_devcontainer() {
+        assert!(is_devcontainer(
+            |name| (name == "CODESPACES").then(|| "true".to_string())
+        ));
+        assert!(!is_devcontainer(|_| Some("false".to_string())));
+        assert!(!is_devcontainer(|_| None));
+
+        let inside = Environment { devcontainer: true };
+        let outside = Environment {
+            devcontainer: false,
+        };
+        let when = When {
+            devcontainer: Some(true),
+        };
+        assert!(when.holds_in(&inside));
+        assert!(!when.holds_in(&outside));
+        assert!(When::default().holds_in(&outside));
+    }
+}
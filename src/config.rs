@@ -0,0 +1,667 @@
+//! Editing of config files preserving comments (`ttr config fmt` and `ttr config mv`).
+//!
+//! Config is parsed into a tree of blocks by indentation, so comments and formatting of the
+//! values are kept as they are. Only block style YAML is restructured, values written in flow
+//! style (`[a, b]`) or as block scalars (`|`) are treated as opaque. Every edit is checked by
+//! comparing the parsed YAML before and after.
+use crate::Result;
+use anyhow::{bail, Context};
+use serde_yaml::Value;
+
+/// Order of the task fields after formatting. Fields not listed keep their relative order
+/// after the listed ones
+const TASK_ORDER: [&str; 5] = ["name", "key", "description", "cmd", "script"];
+
+/// Order of the group fields after formatting
+const GROUP_ORDER: [&str; 4] = ["name", "key", "tasks", "groups"];
+
+#[derive(Debug, Clone)]
+struct Node {
+    /// Comments and blank lines preceding the node
+    leading: Vec<String>,
+    /// Line of the node in the parsed file starting from 1, 0 for the nodes added by edits
+    line: usize,
+    indent: usize,
+    kind: Kind,
+    /// Lines of the value not parsed into nodes (block scalars, multi-line plain scalars)
+    raw: Vec<String>,
+    children: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    /// `key: value` entry of a mapping
+    Entry(String),
+    /// `- ` item of a sequence. Mapping items have their entries as children, scalar items
+    /// have the value here
+    Item(Option<String>),
+}
+
+struct Line {
+    indent: usize,
+    content: String,
+}
+
+impl Line {
+    fn is_trivia(&self) -> bool {
+        self.content.is_empty() || self.content.starts_with('#')
+    }
+}
+
+/// Config file parsed into the tree of blocks
+pub struct Document {
+    nodes: Vec<Node>,
+    /// Comments and blank lines at the end of the file
+    trailing: Vec<String>,
+}
+
+impl Document {
+    pub fn parse(text: &str) -> Result<Self> {
+        if text.lines().any(|l| l.starts_with('\t')) {
+            bail!("Tabs are not allowed for indentation");
+        }
+        let mut parser = Parser {
+            lines: text
+                .lines()
+                .map(|l| Line {
+                    indent: l.len() - l.trim_start().len(),
+                    content: l.trim().to_string(),
+                })
+                .collect(),
+            pos: 0,
+        };
+        let nodes = parser.parse_nodes(0, false)?;
+        let trailing = parser.take_trivia();
+        if parser.pos < parser.lines.len() {
+            bail!("Unexpected indentation at line {}", parser.pos + 1);
+        }
+        Ok(Self { nodes, trailing })
+    }
+
+    pub fn render(&self) -> String {
+        let mut lines = vec![];
+        for node in &self.nodes {
+            node.render(&mut lines);
+        }
+        lines.extend(self.trailing.iter().cloned());
+        let mut text = lines
+            .iter()
+            .map(|l| l.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n");
+        text.push('\n');
+        text
+    }
+
+    /// Lines the tasks and groups are defined at, in the order of the file
+    pub fn locations(&self) -> Vec<Location> {
+        let mut locations = vec![];
+        collect_locations(&self.nodes, "", &mut locations);
+        locations
+    }
+
+    /// Orders the fields of all tasks and groups in the canonical order
+    pub fn format(&mut self) {
+        format_entries(&mut self.nodes);
+    }
+
+    /// Moves the task with key path `task` to the group with key path `group` (empty for the
+    /// root group)
+    pub fn move_task(&mut self, task: &str, group: &str) -> Result<()> {
+        let Some((group_path, key)) = task.char_indices().last().map(|(i, key)| (&task[..i], key))
+        else {
+            bail!("Task key path is empty");
+        };
+        let source = find_group(&mut self.nodes, group_path)?;
+        let Some(tasks) = source.iter_mut().find(|n| n.key() == Some("tasks")) else {
+            bail!("No task for key path: {}", task);
+        };
+        let Some(index) = tasks
+            .children
+            .iter()
+            .position(|t| t.item_key() == Some(key))
+        else {
+            bail!("No task for key path: {}", task);
+        };
+        let mut node = tasks.children.remove(index);
+        if tasks.children.is_empty() {
+            tasks.kind = Kind::Entry("tasks: []".to_string());
+        }
+
+        let target = find_group(&mut self.nodes, group)?;
+        let target_indent = target.first().map(|n| n.indent).unwrap_or(0);
+        let tasks = match target.iter().position(|n| n.key() == Some("tasks")) {
+            Some(i) => &mut target[i],
+            None => {
+                target.push(Node {
+                    leading: vec![],
+                    line: 0,
+                    indent: target_indent,
+                    kind: Kind::Entry("tasks:".to_string()),
+                    raw: vec![],
+                    children: vec![],
+                });
+                target.last_mut().unwrap()
+            }
+        };
+        if tasks.value() == Some("[]") {
+            tasks.kind = Kind::Entry("tasks:".to_string());
+        }
+        if !tasks.raw.is_empty() || tasks.value().is_some_and(|v| !v.is_empty()) {
+            bail!("Tasks of the target group are not a block sequence");
+        }
+        if tasks.children.iter().any(|t| t.item_key() == Some(key)) {
+            bail!("Target group already has a task with key {}", key);
+        }
+        let indent = tasks
+            .children
+            .first()
+            .map(|n| n.indent)
+            .unwrap_or(tasks.indent);
+        node.shift(indent as isize - node.indent as isize);
+        tasks.children.push(node);
+        Ok(())
+    }
+}
+
+/// Line of a task or a group in the config file
+#[derive(Debug, PartialEq)]
+pub struct Location {
+    /// Key path of the task or the group relative to the root of the file
+    pub path: String,
+    pub is_group: bool,
+    pub line: usize,
+}
+
+fn collect_locations(entries: &[Node], prefix: &str, locations: &mut Vec<Location>) {
+    for entry in entries {
+        let is_group = match entry.key() {
+            Some("tasks") => false,
+            Some("groups") => true,
+            _ => continue,
+        };
+        for item in &entry.children {
+            let Some(key) = item.any_item_key() else {
+                continue;
+            };
+            let path = format!("{}{}", prefix, key);
+            locations.push(Location {
+                path: path.clone(),
+                is_group,
+                line: item.line,
+            });
+            if is_group {
+                collect_locations(&item.children, &path, locations);
+            }
+        }
+    }
+}
+
+/// Checks that the edited config has the same content as the original one
+pub fn ensure_equivalent(original: &str, edited: &str) -> Result<()> {
+    let original: Value = serde_yaml::from_str(original)?;
+    let edited: Value = serde_yaml::from_str(edited).context("Edited config is not valid")?;
+    if original != edited {
+        bail!("Edited config differs from the original one");
+    }
+    Ok(())
+}
+
+/// Checks that the edited config differs from the original one only by the task moved from
+/// `task` key path to the `group`
+pub fn ensure_moved(original: &str, edited: &str, task: &str, group: &str) -> Result<()> {
+    let mut expected: Value = serde_yaml::from_str(original)?;
+    let edited: Value = serde_yaml::from_str(edited).context("Edited config is not valid")?;
+    let (group_path, key) =
+        task.split_at(task.len() - task.chars().last().map_or(0, char::len_utf8));
+    let tasks = value_tasks(&mut expected, group_path)?;
+    let index = tasks
+        .iter()
+        .position(|t| value_key(t) == key)
+        .context("Task not found")?;
+    let moved = tasks.remove(index);
+    value_tasks(&mut expected, group)?.push(moved);
+    if expected != edited {
+        bail!("Edited config differs from the expected one");
+    }
+    Ok(())
+}
+
+fn value_tasks<'a>(root: &'a mut Value, path: &str) -> Result<&'a mut Vec<Value>> {
+    let mut group = root;
+    for key in path.chars() {
+        let groups = group
+            .get_mut("groups")
+            .and_then(Value::as_sequence_mut)
+            .context("Group not found")?;
+        group = groups
+            .iter_mut()
+            .find(|g| value_key(g) == key.to_string())
+            .context("Group not found")?;
+    }
+    let mapping = group.as_mapping_mut().context("Group is not a mapping")?;
+    let tasks = mapping
+        .entry(Value::String("tasks".to_string()))
+        .or_insert(Value::Sequence(vec![]));
+    tasks.as_sequence_mut().context("Tasks are not a sequence")
+}
+
+fn value_key(value: &Value) -> String {
+    match value.get("key") {
+        Some(Value::String(key)) => key.clone(),
+        Some(key) => serde_yaml::to_string(key)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        None => String::new(),
+    }
+}
+
+/// Entries of the group with the given key path starting from the root entries
+fn find_group<'a>(root: &'a mut Vec<Node>, path: &str) -> Result<&'a mut Vec<Node>> {
+    let mut entries = root;
+    for key in path.chars() {
+        let group = entries
+            .iter_mut()
+            .find(|n| n.key() == Some("groups"))
+            .and_then(|groups| {
+                groups
+                    .children
+                    .iter_mut()
+                    .find(|g| g.item_key() == Some(key))
+            });
+        let Some(group) = group else {
+            bail!("No group for key path: {}", path);
+        };
+        entries = &mut group.children;
+    }
+    Ok(entries)
+}
+
+fn format_entries(entries: &mut [Node]) {
+    for entry in entries.iter_mut() {
+        match entry.key() {
+            Some("tasks") => {
+                for task in &mut entry.children {
+                    task.order(&TASK_ORDER);
+                }
+            }
+            Some("groups") => {
+                for group in &mut entry.children {
+                    group.order(&GROUP_ORDER);
+                    format_entries(&mut group.children);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Node {
+    /// Key of the mapping entry
+    fn key(&self) -> Option<&str> {
+        match &self.kind {
+            Kind::Entry(text) => text.split(':').next().map(str::trim),
+            Kind::Item(_) => None,
+        }
+    }
+
+    /// Inline value of the mapping entry
+    fn value(&self) -> Option<&str> {
+        match &self.kind {
+            Kind::Entry(text) => text.split_once(':').map(|(_, v)| v.trim()),
+            Kind::Item(_) => None,
+        }
+    }
+
+    /// Value of the `key` field of the mapping item
+    fn item_key(&self) -> Option<char> {
+        let entry = self.children.iter().find(|c| c.key() == Some("key"))?;
+        let value = entry.value()?.split(" #").next()?.trim();
+        let value = value.trim_matches(|c| c == '\'' || c == '"');
+        let mut chars = value.chars();
+        let key = chars.next()?;
+        chars.next().is_none().then_some(key)
+    }
+
+    /// Value of the `key` field of the mapping item written in either block or flow style
+    fn any_item_key(&self) -> Option<char> {
+        let Kind::Item(Some(value)) = &self.kind else {
+            return self.item_key();
+        };
+        let text = std::iter::once(value.as_str())
+            .chain(self.raw.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let value: Value = serde_yaml::from_str(&text).ok()?;
+        let key = value_key(&value);
+        let mut chars = key.chars();
+        let key = chars.next()?;
+        chars.next().is_none().then_some(key)
+    }
+
+    /// Reorders entries of the mapping item
+    fn order(&mut self, order: &[&str]) {
+        let rank = |node: &Node| {
+            let key = node.key().unwrap_or_default();
+            order.iter().position(|k| *k == key).unwrap_or(order.len())
+        };
+        // Leading comments of the item stay before the item itself
+        self.children.sort_by_key(rank);
+    }
+
+    fn shift(&mut self, delta: isize) {
+        let shift_line = |line: &mut String| {
+            if line.trim().is_empty() {
+                return;
+            }
+            if delta > 0 {
+                line.insert_str(0, &" ".repeat(delta as usize));
+            } else {
+                let spaces = line.len() - line.trim_start().len();
+                line.drain(..spaces.min(-delta as usize));
+            }
+        };
+        self.leading.iter_mut().for_each(shift_line);
+        self.raw.iter_mut().for_each(shift_line);
+        self.indent = (self.indent as isize + delta).max(0) as usize;
+        for child in &mut self.children {
+            child.shift(delta);
+        }
+    }
+
+    fn render(&self, lines: &mut Vec<String>) {
+        lines.extend(self.leading.iter().cloned());
+        let indent = " ".repeat(self.indent);
+        match &self.kind {
+            Kind::Entry(text) => {
+                lines.push(format!("{}{}", indent, text));
+                lines.extend(self.raw.iter().cloned());
+                for child in &self.children {
+                    child.render(lines);
+                }
+            }
+            Kind::Item(Some(value)) => {
+                lines.push(format!("{}- {}", indent, value));
+                lines.extend(self.raw.iter().cloned());
+            }
+            Kind::Item(None) => {
+                let mut children = self.children.iter();
+                if let Some(first) = children.next() {
+                    // The first entry is written on the line of the dash
+                    let mut first_lines = vec![];
+                    first.render(&mut first_lines);
+                    let dash_line = first.leading.len();
+                    let mut first_lines = first_lines.into_iter();
+                    lines.extend(first_lines.by_ref().take(dash_line));
+                    if let Some(line) = first_lines.next() {
+                        let offset = first.indent.saturating_sub(self.indent + 2);
+                        let content = &line[first.indent..];
+                        lines.push(format!("{}- {}{}", indent, " ".repeat(offset), content));
+                    }
+                    lines.extend(first_lines);
+                }
+                for child in children {
+                    child.render(lines);
+                }
+            }
+        }
+    }
+}
+
+struct Parser {
+    lines: Vec<Line>,
+    pos: usize,
+}
+
+impl Parser {
+    /// Index of the next line which is not a comment or blank
+    fn next_significant(&self) -> Option<usize> {
+        (self.pos..self.lines.len()).find(|i| !self.lines[*i].is_trivia())
+    }
+
+    fn take_trivia(&mut self) -> Vec<String> {
+        let end = self.next_significant().unwrap_or(self.lines.len());
+        let trivia = self.lines[self.pos..end]
+            .iter()
+            .map(|l| format!("{}{}", " ".repeat(l.indent), l.content))
+            .collect();
+        self.pos = end;
+        trivia
+    }
+
+    /// Parses sibling nodes at the given indent. If `items_only` is set only sequence items
+    /// are parsed (sequence as a value of the mapping entry at the same indent)
+    fn parse_nodes(&mut self, indent: usize, items_only: bool) -> Result<Vec<Node>> {
+        let mut nodes = vec![];
+        while let Some(next) = self.next_significant() {
+            let line = &self.lines[next];
+            let is_item = line.content == "-" || line.content.starts_with("- ");
+            if line.indent != indent || (items_only && !is_item) {
+                break;
+            }
+            let leading = self.take_trivia();
+            let node = if is_item {
+                self.parse_item(leading)?
+            } else {
+                self.parse_entry(leading)?
+            };
+            nodes.push(node);
+        }
+        Ok(nodes)
+    }
+
+    fn parse_item(&mut self, leading: Vec<String>) -> Result<Node> {
+        let number = self.pos + 1;
+        let line = &mut self.lines[self.pos];
+        let indent = line.indent;
+        let rest = line.content[1..].trim_start();
+        let offset = line.content.len() - rest.len();
+        if rest.is_empty() || !is_entry(rest) {
+            let value = rest.to_string();
+            self.pos += 1;
+            let raw = self.take_deeper(indent);
+            let kind = Kind::Item((!value.is_empty()).then_some(value));
+            if matches!(kind, Kind::Item(None)) && !raw.is_empty() {
+                bail!("Unsupported sequence item at line {}", self.pos);
+            }
+            return Ok(Node {
+                leading,
+                line: number,
+                indent,
+                kind,
+                raw,
+                children: vec![],
+            });
+        }
+        // The first entry of the mapping is on the line of the dash, so the line is
+        // rewritten as if the entry was on its own line
+        line.content = rest.to_string();
+        line.indent = indent + offset;
+        let children = self.parse_nodes(indent + offset, false)?;
+        Ok(Node {
+            leading,
+            line: number,
+            indent,
+            kind: Kind::Item(None),
+            raw: vec![],
+            children,
+        })
+    }
+
+    fn parse_entry(&mut self, leading: Vec<String>) -> Result<Node> {
+        let number = self.pos + 1;
+        let line = &self.lines[self.pos];
+        let indent = line.indent;
+        let text = line.content.clone();
+        if !is_entry(&text) {
+            bail!("Unsupported YAML at line {}: {}", self.pos + 1, text);
+        }
+        self.pos += 1;
+        let value = text
+            .split_once(':')
+            .map(|(_, v)| v.trim())
+            .unwrap_or_default();
+        let value = value.split(" #").next().unwrap_or_default().trim();
+        let mut node = Node {
+            leading,
+            line: number,
+            indent,
+            kind: Kind::Entry(text.clone()),
+            raw: vec![],
+            children: vec![],
+        };
+        if !value.is_empty() {
+            // Block scalar or a plain scalar continued on the next lines
+            node.raw = self.take_deeper(indent);
+            return Ok(node);
+        }
+        let Some(next) = self.next_significant() else {
+            return Ok(node);
+        };
+        let next = &self.lines[next];
+        let next_indent = next.indent;
+        if next_indent > indent {
+            node.children = self.parse_nodes(next_indent, false)?;
+        } else if next_indent == indent && (next.content == "-" || next.content.starts_with("- ")) {
+            node.children = self.parse_nodes(indent, true)?;
+        }
+        Ok(node)
+    }
+
+    /// Takes lines indented deeper than `indent` as is. Blank lines and comments followed by
+    /// less indented lines are left for the next node
+    fn take_deeper(&mut self, indent: usize) -> Vec<String> {
+        let mut raw = vec![];
+        let mut end = self.pos;
+        for (i, line) in self.lines.iter().enumerate().skip(self.pos) {
+            if line.content.is_empty() {
+                continue;
+            }
+            if line.indent <= indent {
+                break;
+            }
+            end = i + 1;
+        }
+        for line in &self.lines[self.pos..end] {
+            raw.push(format!("{}{}", " ".repeat(line.indent), line.content));
+        }
+        self.pos = end;
+        raw
+    }
+}
+
+/// Returns true if the line looks like `key: value` mapping entry
+fn is_entry(text: &str) -> bool {
+    if text.starts_with(['"', '\'', '[', '{', '&', '*', '!', '?', '|', '>']) {
+        return false;
+    }
+    match text.split_once(':') {
+        Some((key, value)) => {
+            !key.is_empty() && !key.contains(' ') && (value.is_empty() || value.starts_with(' '))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "# Project tasks
+groups:
+- key: g # git
+  name: git
+  tasks:
+  - cmd: git status
+    key: s
+    name: status
+tasks:
+  # Build
+  - key: b
+    name: build
+    script: |
+      cargo build
+
+      cargo test
+";
+
+    #[test]
+    fn check_round_trip() {
+        let document = Document::parse(CONFIG).unwrap();
+        assert_eq!(CONFIG, document.render());
+    }
+
+    #[test]
+    fn check_format() {
+        let mut document = Document::parse(CONFIG).unwrap();
+        document.format();
+        let expected = "# Project tasks
+groups:
+- name: git
+  key: g # git
+  tasks:
+  - name: status
+    key: s
+    cmd: git status
+tasks:
+  # Build
+  - name: build
+    key: b
+    script: |
+      cargo build
+
+      cargo test
+";
+        let formatted = document.render();
+        assert_eq!(expected, formatted);
+        ensure_equivalent(CONFIG, &formatted).unwrap();
+    }
+
+    #[test]
+    fn check_move() {
+        let mut document = Document::parse(CONFIG).unwrap();
+        document.move_task("b", "g").unwrap();
+        let expected = "# Project tasks
+groups:
+- key: g # git
+  name: git
+  tasks:
+  - cmd: git status
+    key: s
+    name: status
+  # Build
+  - key: b
+    name: build
+    script: |
+      cargo build
+
+      cargo test
+tasks: []
+";
+        let moved = document.render();
+        assert_eq!(expected, moved);
+        ensure_moved(CONFIG, &moved, "b", "g").unwrap();
+
+        assert!(document.move_task("gx", "").is_err());
+        assert!(document.move_task("gb", "x").is_err());
+    }
+
+    #[test]
+    fn check_locations() {
+        let config = format!("{}  - {{name: test, key: t, cmd: cargo test}}\n", CONFIG);
+        let document = Document::parse(&config).unwrap();
+        let location = |path: &str, is_group, line| Location {
+            path: path.to_string(),
+            is_group,
+            line,
+        };
+        let expected = vec![
+            location("g", true, 3),
+            location("gs", false, 6),
+            location("b", false, 11),
+            location("t", false, 17),
+        ];
+        assert_eq!(expected, document.locations());
+    }
+}
@@ -0,0 +1,218 @@
+//! Running the task inside an ephemeral container (`image: node:20`) or an already running one
+//! (`container: dev`, `compose_service: web`).
+//!
+//! The working directory is mounted into the ephemeral container at the same path, so the paths
+//! in the command and its output stay the same. Commands in a running container are run with
+//! `exec` in its own working directory unless `remote_dir` is given, Compose looks up the
+//! project in the working directory of the task. Docker is used if installed, Podman otherwise.
+use crate::{find_in_path, sandbox::Network, Result};
+use anyhow::bail;
+use std::{
+    env,
+    io::{stdin, stdout, IsTerminal},
+    path::{Path, PathBuf},
+};
+
+/// Container engines in the order of preference
+const ENGINES: [&str; 2] = ["docker", "podman"];
+
+/// Container the task is run in
+pub struct Container<'a> {
+    pub image: &'a str,
+    pub working_dir: &'a Path,
+    /// Environment variables set in the container
+    pub env: &'a [(String, String)],
+    /// Secrets passed by name only, the engine takes their values from its own environment
+    pub secrets: &'a [(String, String)],
+    /// Files mounted read-only at the same path (eg. the script of the task)
+    pub files: Vec<PathBuf>,
+    pub network: Network,
+}
+
+impl Container<'_> {
+    /// Wraps the program, so it is run in the container
+    pub fn wrap(&self, program: String, args: Vec<String>) -> Result<(String, Vec<String>)> {
+        let engine = engine()?;
+        let tty = stdin().is_terminal() && stdout().is_terminal();
+        let args = self.run_args(&engine, tty, program, args);
+        Ok((engine.to_string_lossy().to_string(), args))
+    }
+
+    /// Arguments of the engine running the program in the container
+    fn run_args(
+        &self,
+        engine: &Path,
+        tty: bool,
+        program: String,
+        args: Vec<String>,
+    ) -> Vec<String> {
+        let working_dir = self.working_dir.to_string_lossy();
+        let mut run = vec!["run".to_string(), "--rm".to_string(), "-i".to_string()];
+        if tty {
+            run.push("-t".to_string());
+        }
+        run.push("-v".to_string());
+        run.push(format!("{}:{}", working_dir, working_dir));
+        for file in &self.files {
+            let file = file.to_string_lossy();
+            run.push("-v".to_string());
+            run.push(format!("{}:{}:ro", file, file));
+        }
+        run.push("-w".to_string());
+        run.push(working_dir.to_string());
+        for (name, value) in self.env {
+            run.push("-e".to_string());
+            run.push(format!("{}={}", name, value));
+        }
+        for (name, _) in self.secrets {
+            run.push("-e".to_string());
+            run.push(name.clone());
+        }
+        if self.network == Network::None {
+            run.push("--network=none".to_string());
+        }
+        // Rootless Podman maps the user itself, Docker runs as root unless the user is given,
+        // so the files created in the working directory would be owned by root
+        #[cfg(unix)]
+        if engine_name(engine) == "docker" {
+            // SAFETY: getuid and getgid are always successful
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            run.push(format!("--user={}:{}", uid, gid));
+        }
+        run.push(self.image.to_string());
+        run.push(program);
+        run.extend(args);
+        run
+    }
+}
+
+/// Running container the task is executed in
+pub struct Exec<'a> {
+    /// Name of the container, or of the service if `compose` is set
+    pub name: &'a str,
+    pub compose: bool,
+    /// Directory of the command in the container, its working directory if not given
+    pub dir: Option<&'a str>,
+    /// Environment variables set for the command
+    pub env: &'a [(String, String)],
+    /// Secrets passed by name only, the engine takes their values from its own environment
+    pub secrets: &'a [(String, String)],
+}
+
+impl Exec<'_> {
+    /// Wraps the program, so it is run in the container
+    pub fn wrap(&self, program: String, args: Vec<String>) -> Result<(String, Vec<String>)> {
+        let engine = engine()?;
+        let tty = stdin().is_terminal() && stdout().is_terminal();
+        let args = self.exec_args(tty, program, args);
+        Ok((engine.to_string_lossy().to_string(), args))
+    }
+
+    fn exec_args(&self, tty: bool, program: String, args: Vec<String>) -> Vec<String> {
+        let exec: &[&str] = match (self.compose, tty) {
+            // Compose allocates a terminal unless it is disabled
+            (true, true) => &["compose", "exec"],
+            (true, false) => &["compose", "exec", "-T"],
+            (false, true) => &["exec", "-i", "-t"],
+            (false, false) => &["exec", "-i"],
+        };
+        let mut exec = exec.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        if let Some(dir) = self.dir {
+            exec.push("-w".to_string());
+            exec.push(dir.to_string());
+        }
+        for (name, value) in self.env {
+            exec.push("-e".to_string());
+            exec.push(format!("{}={}", name, value));
+        }
+        for (name, _) in self.secrets {
+            exec.push("-e".to_string());
+            exec.push(name.clone());
+        }
+        exec.push(self.name.to_string());
+        exec.push(program);
+        exec.extend(args);
+        exec
+    }
+}
+
+/// Path of the container engine. `TTR_CONTAINER_ENGINE` overrides the detected one
+fn engine() -> Result<PathBuf> {
+    if let Some(engine) = env::var_os("TTR_CONTAINER_ENGINE").filter(|e| !e.is_empty()) {
+        return Ok(PathBuf::from(engine));
+    }
+    let path = env::var("PATH").unwrap_or_default();
+    let engine = ENGINES
+        .iter()
+        .find_map(|engine| find_in_path(&format!("{}{}", engine, env::consts::EXE_SUFFIX), &path));
+    match engine {
+        Some(engine) => Ok(engine),
+        None => bail!("Neither docker nor podman is found to run the task in a container"),
+    }
+}
+
+fn engine_name(engine: &Path) -> String {
+    engine
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_run_args() {
+        let env = [("TZ".to_string(), "UTC".to_string())];
+        let secrets = [("NPM_TOKEN".to_string(), "s3cret".to_string())];
+        let container = Container {
+            image: "node:20",
+            working_dir: Path::new("/project"),
+            env: &env,
+            secrets: &secrets,
+            files: vec![PathBuf::from("/tmp/script.sh")],
+            network: Network::None,
+        };
+        let args = vec!["-c".to_string(), "exec npm test".to_string()];
+        let engine = Path::new("/usr/bin/podman");
+        let args = container.run_args(engine, false, "sh".to_string(), args);
+        let expected = "run --rm -i -v /project:/project -v /tmp/script.sh:/tmp/script.sh:ro \
+            -w /project -e TZ=UTC -e NPM_TOKEN --network=none node:20 sh -c";
+        assert_eq!(expected.split_whitespace().collect::<Vec<_>>(), args[..17]);
+        assert_eq!("exec npm test", args[17]);
+    }
+
+    #[test]
+    fn check_exec_args() {
+        let env = [("RUST_LOG".to_string(), "debug".to_string())];
+        let exec = Exec {
+            name: "web",
+            compose: true,
+            dir: Some("/app"),
+            env: &env,
+            secrets: &[],
+        };
+        let args = vec!["-c".to_string(), "cargo test".to_string()];
+        let expected = "compose exec -T -w /app -e RUST_LOG=debug web sh -c";
+        let compose_args = exec.exec_args(false, "sh".to_string(), args.clone());
+        assert_eq!(
+            expected.split_whitespace().collect::<Vec<_>>(),
+            compose_args[..10]
+        );
+        assert_eq!("cargo test", compose_args[10]);
+
+        let exec = Exec {
+            name: "dev",
+            compose: false,
+            dir: None,
+            env: &[],
+            secrets: &[],
+        };
+        let args = exec.exec_args(true, "sh".to_string(), args);
+        assert_eq!(
+            ["exec", "-i", "-t", "dev", "sh", "-c", "cargo test"],
+            args[..]
+        );
+    }
+}
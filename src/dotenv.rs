@@ -0,0 +1,154 @@
+//! Loading environment variables of the tasks from dotenv files (`env_file: .env.production`).
+//!
+//! Files are read right before the task is run, relative to its working directory, so the
+//! changes are picked up without reloading the config. Each line is `KEY=VALUE` optionally
+//! prefixed with `export`. Values in single quotes are taken literally, in double quotes `\n`,
+//! `\"` and `\\` are unescaped. `${VAR}` in unquoted and double quoted values is expanded using
+//! the variables defined earlier in the file and the environment of ttr.
+use crate::{envvars, Result};
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Dotenv files of the task as written in the config: a single path or a list
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(from = "EnvFilesSpec")]
+pub struct EnvFiles(Vec<PathBuf>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EnvFilesSpec {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+impl From<EnvFilesSpec> for EnvFiles {
+    fn from(spec: EnvFilesSpec) -> Self {
+        match spec {
+            EnvFilesSpec::One(path) => Self(vec![path]),
+            EnvFilesSpec::Many(paths) => Self(paths),
+        }
+    }
+}
+
+impl EnvFiles {
+    /// Puts the files of the group before the own ones, so the latter take precedence
+    pub fn inherit(&mut self, parent: &EnvFiles) {
+        self.0.splice(0..0, parent.0.iter().cloned());
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.0
+    }
+
+    /// Reads the variables from all the files. Variables of the later files take precedence
+    pub fn load(&self, dir: &Path) -> Result<Vec<(String, String)>> {
+        let mut vars = vec![];
+        for path in &self.0 {
+            let path = dir.join(path);
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("Unable to read env file {}", path.display()))?;
+            vars.extend(
+                parse(&text).with_context(|| format!("Invalid env file {}", path.display()))?,
+            );
+        }
+        Ok(vars)
+    }
+}
+
+fn parse(text: &str) -> Result<Vec<(String, String)>> {
+    let mut vars: Vec<(String, String)> = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((name, value)) = line.split_once('=') else {
+            bail!("Line {}: KEY=VALUE expected", i + 1);
+        };
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            bail!("Line {}: invalid variable name: {}", i + 1, name);
+        }
+        let var = |name: &str| {
+            let defined = vars.iter().rev().find(|(n, _)| n == name);
+            defined
+                .map(|(_, v)| v.clone())
+                .or_else(|| env::var(name).ok())
+        };
+        let value = value.trim();
+        let value = if let Some(value) = quoted(value, '\'') {
+            value.to_string()
+        } else if let Some(value) = quoted(value, '"') {
+            envvars::expand(&unescape(value), var)
+        } else {
+            // Comment after an unquoted value has to be separated by whitespace
+            let value = value.split(" #").next().unwrap_or_default().trim_end();
+            envvars::expand(value, var)
+        };
+        vars.push((name.to_string(), value));
+    }
+    Ok(vars)
+}
+
+/// Value between the quotes. Text after the closing quote (eg. a comment) is ignored
+fn quoted(value: &str, quote: char) -> Option<&str> {
+    let value = value.strip_prefix(quote)?;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            '\\' if quote == '"' => escaped = !escaped,
+            c if c == quote && !escaped => return Some(&value[..i]),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => result.push('\n'),
+                Some(c) => result.push(c),
+                None => result.push('\\'),
+            },
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_parse() {
+        let text = r#"
+            # database
+            export DB_HOST=localhost
+            DB_URL=postgres://${DB_HOST}/app # comment
+            DB_PORT=${TTR_TEST_UNSET_PORT:-5432}
+            GREETING="hello\n\"world\"" # comment
+            LITERAL='${DB_HOST} # not a comment'
+            EMPTY=
+        "#;
+        let vars = parse(text).unwrap();
+        let value = |name: &str| vars.iter().find(|(n, _)| n == name).unwrap().1.as_str();
+        assert_eq!("localhost", value("DB_HOST"));
+        assert_eq!("postgres://localhost/app", value("DB_URL"));
+        assert_eq!("5432", value("DB_PORT"));
+        assert_eq!("hello\n\"world\"", value("GREETING"));
+        assert_eq!("${DB_HOST} # not a comment", value("LITERAL"));
+        assert_eq!("", value("EMPTY"));
+        assert!(parse("NO_VALUE").is_err());
+        assert!(parse("A B=1").is_err());
+    }
+}
@@ -0,0 +1,104 @@
+//! Expansion of environment variables in config values (`working_dir: ${HOME}/src/app`).
+//!
+//! `${VAR}` is replaced with the value of the variable when the config is loaded and
+//! `${VAR:-default}` falls back to the default if the variable is unset or empty. Commands are
+//! run by a shell, so there [`expand_cmd`] leaves the variables which are not set as is even if
+//! they have a default, and the shell variables (`for f in *.log; do gzip ${f:-none}; done`) keep
+//! working. `$${VAR}` is an escape leaving `${VAR}` to the shell even if the variable is set (eg.
+//! `$${PWD}`, which depends on the working directory).
+
+/// Expands the variables in a value which is not interpreted by a shell (`working_dir`, `env`)
+/// using `var` to look up their values
+pub fn expand(text: &str, var: impl Fn(&str) -> Option<String>) -> String {
+    expand_with(text, var, true)
+}
+
+/// Expands the variables in a command, leaving the ones which are not set to the shell
+pub fn expand_cmd(text: &str, var: impl Fn(&str) -> Option<String>) -> String {
+    expand_with(text, var, false)
+}
+
+fn expand_with(text: &str, var: impl Fn(&str) -> Option<String>, defaults: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            result.push_str(&rest[..start - 1]);
+            result.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        result.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let Some(end) = placeholder.find('}') else {
+            result.push_str(placeholder);
+            return result;
+        };
+        let expression = &placeholder[2..end];
+        let (name, default) = match expression.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expression, None),
+        };
+        let value = is_name(name).then(|| var(name)).flatten();
+        match (value, default) {
+            (Some(value), None) => result.push_str(&value),
+            (Some(value), Some(default)) => match value.is_empty() {
+                true => result.push_str(default),
+                false => result.push_str(&value),
+            },
+            (None, Some(default)) if defaults && is_name(name) => result.push_str(default),
+            (None, _) => result.push_str(&placeholder[..end + 1]),
+        }
+        rest = &placeholder[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Name of the variable as accepted by POSIX shells. Other expressions (`${f%.log}`) are left
+/// for the shell
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_expand() {
+        let var = |name: &str| match name {
+            "HOME" => Some("/home/user".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        assert_eq!("/home/user/src", expand("${HOME}/src", var));
+        assert_eq!("cd /home/user", expand("cd ${HOME:-/tmp}", var));
+        assert_eq!("cd /tmp", expand("cd ${MISSING:-/tmp}", var));
+        assert_eq!("cd /tmp", expand("cd ${EMPTY:-/tmp}", var));
+        assert_eq!("cd ", expand("cd ${EMPTY}", var));
+        assert_eq!("gzip ${f}", expand("gzip ${f}", var));
+        assert_eq!("echo ${HOME}", expand("echo $${HOME}", var));
+        assert_eq!("echo ${f%.log}", expand("echo ${f%.log}", var));
+        assert_eq!("echo $HOME ${HOME", expand("echo $HOME ${HOME", var));
+    }
+
+    #[test]
+    fn check_expand_cmd() {
+        let var = |name: &str| match name {
+            "HOME" => Some("/home/user".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        assert_eq!("cd /home/user", expand_cmd("cd ${HOME:-/tmp}", var));
+        assert_eq!("cd /tmp", expand_cmd("cd ${EMPTY:-/tmp}", var));
+        assert_eq!(
+            "cd ${MISSING:-/tmp}",
+            expand_cmd("cd ${MISSING:-/tmp}", var)
+        );
+        let cmd = "for f in *; do echo ${f:-none}; done";
+        assert_eq!(cmd, expand_cmd(cmd, var));
+        assert_eq!("echo ${HOME}", expand_cmd("echo $${HOME}", var));
+    }
+}
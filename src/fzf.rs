@@ -0,0 +1,82 @@
+//! External fuzzy finder used instead of the built-in selector (`--selector fzf`).
+//!
+//! Tasks of all the groups are piped to the finder one per line as the key path, the label
+//! with the group names and the description separated by tabs. The finder draws its UI on the
+//! terminal itself, so it also works when ttr can't take over the screen.
+use crate::{search, Group, Result, Task};
+use anyhow::{bail, Context};
+use clap::ValueEnum;
+use std::{
+    io::{self, BufRead, Write},
+    process::{Command, Stdio},
+};
+
+#[derive(Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum Selector {
+    /// Selector of ttr navigating the groups with single keys
+    #[default]
+    Builtin,
+    /// fzf
+    Fzf,
+    /// skim
+    Sk,
+}
+
+/// Lets the user choose the task with the finder. Returns `None` if the finder was closed
+/// without choosing a task
+pub fn select(selector: Selector, root: &Group) -> Result<Option<&Task>> {
+    let program = match selector {
+        Selector::Fzf => "fzf",
+        Selector::Sk => "sk",
+        Selector::Builtin => unreachable!("built-in selector doesn't use a finder"),
+    };
+    let mut child = Command::new(program)
+        .args([
+            "--delimiter",
+            "\t",
+            "--with-nth",
+            "1..",
+            "--prompt",
+            "ttr> ",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Unable to run {}", program))?;
+    let mut stdin = child.stdin.take().unwrap();
+    for candidate in search::candidates(root) {
+        let description = candidate.task.description.as_deref().unwrap_or_default();
+        let line = format!(
+            "{}\t{}\t{}",
+            candidate.task.path, candidate.label, description
+        );
+        // Finder exits without reading the rest once the task is chosen
+        if writeln!(stdin, "{}", line.trim_end()).is_err() {
+            break;
+        }
+    }
+    drop(stdin);
+    let output = child.wait_with_output()?;
+    // 1 means no match and 130 means the finder was interrupted (Esc or Ctrl-C)
+    if matches!(output.status.code(), Some(1 | 130)) {
+        return Ok(None);
+    }
+    if !output.status.success() {
+        bail!("{} failed ({})", program, output.status);
+    }
+    let selection = String::from_utf8_lossy(&output.stdout);
+    let path = selection.split('\t').next().unwrap_or_default().trim();
+    match root.find_task(path) {
+        Some(task) => Ok(Some(task)),
+        None => bail!("No task for key path: {}", path),
+    }
+}
+
+/// Asks the question on the terminal. Returns true if the answer is yes
+pub fn ask(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
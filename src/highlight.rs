@@ -0,0 +1,205 @@
+//! Syntax highlighting of shell commands shown in the task preview.
+//!
+//! Highlighting is deliberately approximate: commands are split into words, quoted strings,
+//! variables, operators and comments without trying to fully parse the shell grammar.
+use crossterm::style::{StyledContent, Stylize};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    /// First word of a command
+    Command,
+    Text,
+    String,
+    Variable,
+    /// `{{ }}` template placeholder
+    Placeholder,
+    Operator,
+    Comment,
+}
+
+/// Characters terminating a word
+const SPECIAL: &str = "'\"$&|;()<>#";
+
+/// Highlights the command. Every line of the command is split into rows of at most `width`
+/// characters
+pub fn highlight(cmd: &str, width: usize) -> Vec<Vec<String>> {
+    let width = width.max(8);
+    let mut lines = vec![];
+    let mut rows = vec![];
+    let mut row = String::new();
+    let mut column = 0;
+    for (kind, token) in tokenize(cmd) {
+        if token == "\n" {
+            rows.push(std::mem::take(&mut row));
+            lines.push(std::mem::take(&mut rows));
+            column = 0;
+            continue;
+        }
+        let mut rest = token;
+        while !rest.is_empty() {
+            if column >= width {
+                rows.push(std::mem::take(&mut row));
+                column = 0;
+            }
+            let split = rest
+                .char_indices()
+                .nth(width - column)
+                .map(|(i, _)| i)
+                .unwrap_or(rest.len());
+            let (piece, tail) = rest.split_at(split);
+            row.push_str(&style(kind, piece).to_string());
+            column += piece.chars().count();
+            rest = tail;
+        }
+    }
+    rows.push(row);
+    lines.push(rows);
+    lines
+}
+
+fn style(kind: Kind, text: &str) -> StyledContent<&str> {
+    match kind {
+        Kind::Command => text.bold(),
+        Kind::Text => text.stylize(),
+        Kind::String => text.green(),
+        Kind::Variable => text.cyan(),
+        Kind::Placeholder => text.magenta(),
+        Kind::Operator => text.yellow(),
+        Kind::Comment => text.grey(),
+    }
+}
+
+fn tokenize(cmd: &str) -> Vec<(Kind, &str)> {
+    let mut tokens = vec![];
+    let mut expect_command = true;
+    let mut rest = cmd;
+    while let Some(ch) = rest.chars().next() {
+        let (kind, len) = if ch == '\n' {
+            expect_command = true;
+            (Kind::Text, 1)
+        } else if ch.is_whitespace() {
+            let len = rest.find(|c: char| !c.is_whitespace() || c == '\n');
+            (Kind::Text, len.unwrap_or(rest.len()))
+        } else if ch == '#' && ends_word(tokens.last()) {
+            (Kind::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else if ch == '\'' {
+            (Kind::String, closing(rest, '\'', false))
+        } else if ch == '"' {
+            (Kind::String, closing(rest, '"', true))
+        } else if rest.starts_with("{{") {
+            let len = rest.find("}}").map(|i| i + 2).unwrap_or(rest.len());
+            (Kind::Placeholder, len)
+        } else if rest.starts_with("$(") {
+            expect_command = true;
+            (Kind::Operator, 2)
+        } else if rest.starts_with("${") {
+            (Kind::Variable, closing(rest, '}', false))
+        } else if ch == '$' {
+            let name = rest[1..].find(|c: char| !c.is_ascii_alphanumeric() && c != '_');
+            (
+                Kind::Variable,
+                name.map(|i| (i + 1).max(2)).unwrap_or(rest.len()),
+            )
+        } else if "&|;()<>".contains(ch) {
+            let len = rest.find(|c| !"&|;()<>".contains(c)).unwrap_or(rest.len());
+            let operator = &rest[..len];
+            if !operator.starts_with(['<', '>']) && operator != ")" {
+                expect_command = true;
+            }
+            (Kind::Operator, len)
+        } else {
+            let len = word_len(rest);
+            let is_assignment = rest[..len].contains('=');
+            if expect_command && !is_assignment {
+                expect_command = false;
+                (Kind::Command, len)
+            } else {
+                (Kind::Text, len)
+            }
+        };
+        tokens.push((kind, &rest[..len]));
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+/// Returns true if the next token starts a new word
+fn ends_word(token: Option<&(Kind, &str)>) -> bool {
+    match token {
+        None => true,
+        Some((Kind::Text, text)) => text.ends_with(char::is_whitespace),
+        Some((Kind::Operator, _)) => true,
+        _ => false,
+    }
+}
+
+/// Length of the quoted string starting at the beginning of `s` including the quotes
+fn closing(s: &str, quote: char, escapes: bool) -> usize {
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, ch)) = chars.next() {
+        if escapes && ch == '\\' {
+            chars.next();
+        } else if ch == quote {
+            return i + ch.len_utf8();
+        }
+    }
+    s.len()
+}
+
+fn word_len(s: &str) -> usize {
+    let mut chars = s.char_indices();
+    while let Some((i, ch)) = chars.next() {
+        if ch == '\\' {
+            chars.next();
+        } else if ch.is_whitespace() || SPECIAL.contains(ch) || s[i..].starts_with("{{") {
+            return i;
+        }
+    }
+    s.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::strip_ansi;
+    use Kind::*;
+
+    #[test]
+    fn check_tokenize() {
+        let tokens = tokenize("FOO=1 cargo test \"$X\" && echo ${HOME}/{{cwd}} | less # done");
+        let expected = vec![
+            (Text, "FOO=1"),
+            (Text, " "),
+            (Command, "cargo"),
+            (Text, " "),
+            (Text, "test"),
+            (Text, " "),
+            (String, "\"$X\""),
+            (Text, " "),
+            (Operator, "&&"),
+            (Text, " "),
+            (Command, "echo"),
+            (Text, " "),
+            (Variable, "${HOME}"),
+            (Text, "/"),
+            (Placeholder, "{{cwd}}"),
+            (Text, " "),
+            (Operator, "|"),
+            (Text, " "),
+            (Command, "less"),
+            (Text, " "),
+            (Comment, "# done"),
+        ];
+        assert_eq!(expected, tokens);
+    }
+
+    #[test]
+    fn check_wrapping() {
+        let lines = highlight("echo 0123456789\nls", 10);
+        let lines = lines
+            .iter()
+            .map(|rows| rows.iter().map(|r| strip_ansi(r)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![vec!["echo 01234", "56789"], vec!["ls"]], lines);
+    }
+}
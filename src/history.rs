@@ -0,0 +1,167 @@
+//! Quick access to the recently run tasks: `.` in the selector repeats the last task and `h`
+//! shows the most recent ones.
+use crate::{
+    insights::format_duration,
+    state::{self, Outcome, ProjectState, RunRecord},
+    ui::Ui,
+    Group, Result, Task,
+};
+use crossterm::{
+    event::{KeyCode, KeyModifiers},
+    style::{style, Stylize},
+};
+use std::{collections::HashSet, fmt::Write};
+
+/// Maximum number of tasks shown in the history (one for each digit key)
+const MAX_RECENT: usize = 9;
+
+/// Distinct tasks run most recently, the latest first. Tasks removed from the config or moved to
+/// another key path are skipped
+pub fn recent<'a, 's>(
+    state: &'s ProjectState,
+    root: &'a Group,
+    limit: usize,
+) -> Vec<(&'a Task, &'s RunRecord)> {
+    let mut seen = HashSet::new();
+    state
+        .history
+        .iter()
+        .rev()
+        .filter_map(|record| {
+            let task = root.find_task(&record.path)?;
+            (task.name == record.name).then_some((task, record))
+        })
+        .filter(|(task, _)| seen.insert(task.path.as_str()))
+        .take(limit)
+        .collect()
+}
+
+/// Shows the recently run tasks. Returns the chosen task or `None` if the user returned to the
+/// selector
+pub fn select<'a>(ui: &mut Ui, state: &ProjectState, root: &'a Group) -> Result<Option<&'a Task>> {
+    let recent = recent(state, root, MAX_RECENT);
+    let now = state::now();
+    let mut frame = String::new();
+    writeln!(frame)?;
+    writeln!(frame, "  {}", "RECENT TASKS".stylize().grey())?;
+    writeln!(frame)?;
+    let width = recent
+        .iter()
+        .map(|(task, _)| task.name.chars().count())
+        .max()
+        .unwrap_or_default();
+    for (i, (task, record)) in recent.iter().enumerate() {
+        let marker = match record.outcome() {
+            Outcome::Success => "✓".stylize().green(),
+            Outcome::Warning => "!".stylize().yellow(),
+            Outcome::Failure => "✗".stylize().red(),
+        };
+        let details = format!(
+            "{:6} {:>8} ago, took {}",
+            task.path,
+            format_age(now.saturating_sub(record.started_at)),
+            format_duration(record.duration_ms)
+        );
+        writeln!(
+            frame,
+            "  {} → {} {:width$}  {}",
+            style(i + 1).green(),
+            marker,
+            task.name,
+            details.stylize().grey(),
+            width = width
+        )?;
+    }
+    if recent.is_empty() {
+        writeln!(frame, "    {}", "No tasks were run yet".stylize().grey())?;
+    }
+    writeln!(frame)?;
+    writeln!(
+        frame,
+        "  {} → run, {} → back",
+        "1-9".stylize().red(),
+        "<Esc>".stylize().red()
+    )?;
+    ui.draw(&frame)?;
+
+    loop {
+        let event = ui.next_key_event()?;
+        match event.code {
+            KeyCode::Esc | KeyCode::Char('h') => return Ok(None),
+            KeyCode::Char('c') if event.modifiers == KeyModifiers::CONTROL => return Ok(None),
+            KeyCode::Char(ch) if ch.is_ascii_digit() => {
+                let i = ch.to_digit(10).unwrap() as usize;
+                if let Some((task, _)) = i.checked_sub(1).and_then(|i| recent.get(i)) {
+                    return Ok(Some(task));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Age of the run in the largest whole units (eg. `5m`)
+pub fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(path: &str, name: &str) -> RunRecord {
+        RunRecord {
+            path: path.to_string(),
+            name: name.to_string(),
+            started_at: 0,
+            duration_ms: 1000,
+            exit_code: Some(0),
+            outcome: None,
+            usage: None,
+            timed_out: false,
+        }
+    }
+
+    #[test]
+    fn check_recent() {
+        let yaml = "
+            name: ROOT
+            key: _
+            tasks:
+            - {name: build, key: b, cmd: make}
+            - {name: test, key: t, cmd: make test}
+        ";
+        let mut root: Group = serde_yaml::from_str(yaml).unwrap();
+        root.assign_paths("");
+        let state = ProjectState {
+            history: vec![
+                record("b", "build"),
+                record("t", "test"),
+                record("x", "removed"),
+                record("b", "build"),
+                record("t", "renamed"),
+            ],
+            ..ProjectState::default()
+        };
+        let recent = recent(&state, &root, MAX_RECENT);
+        let names = recent
+            .iter()
+            .map(|(t, _)| t.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["build", "test"], names);
+        assert_eq!(1, super::recent(&state, &root, 1).len());
+    }
+
+    #[test]
+    fn check_format_age() {
+        assert_eq!("42s", format_age(42));
+        assert_eq!("5m", format_age(300));
+        assert_eq!("2h", format_age(7300));
+        assert_eq!("3d", format_age(3 * 86400));
+    }
+}
@@ -0,0 +1,279 @@
+//! Tasks imported from the build files of other tools (`import: {type: make, path: Makefile}`).
+//!
+//! Imports are expanded into groups of the config before it is parsed, so imported tasks are no
+//! different from the ones written by hand. Keys of the tasks are assigned automatically.
+use crate::Result;
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::{collections::BTreeMap, fs, path::Path};
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum ImportType {
+    /// Targets of a Makefile
+    Make,
+    /// Scripts of a package.json run by the package manager of the project (npm, yarn or pnpm)
+    Npm,
+}
+
+impl ImportType {
+    fn default_path(self) -> &'static str {
+        match self {
+            ImportType::Make => "Makefile",
+            ImportType::Npm => "package.json",
+        }
+    }
+
+    fn default_name(self) -> &'static str {
+        match self {
+            ImportType::Make => "make",
+            ImportType::Npm => "npm",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Import {
+    #[serde(rename = "type")]
+    import_type: ImportType,
+    /// path of the imported file relative to the config (`Makefile` or `package.json` by default)
+    path: Option<String>,
+    /// name of the group (name of the import type by default)
+    name: Option<String>,
+    /// key of the group (first letter of the import type by default)
+    key: Option<char>,
+}
+
+/// Task found in the imported file
+struct ImportedTask {
+    name: String,
+    description: Option<String>,
+    cmd: String,
+}
+
+/// Replaces `import` directive of the config (single import or a list of them) with the groups
+/// of the imported tasks
+pub fn expand(config: &mut Value, config_dir: &Path) -> Result<()> {
+    let Some(mapping) = config.as_mapping_mut() else {
+        return Ok(());
+    };
+    let Some(imports) = mapping.remove("import") else {
+        return Ok(());
+    };
+    let imports: Vec<Import> = match imports {
+        Value::Sequence(_) => serde_yaml::from_value(imports)?,
+        import => vec![serde_yaml::from_value(import)?],
+    };
+    let groups = mapping
+        .entry("groups".into())
+        .or_insert(Value::Sequence(vec![]));
+    let Some(groups) = groups.as_sequence_mut() else {
+        bail!("groups should be a list");
+    };
+    for import in imports {
+        let import_type = import.import_type;
+        let relative_path = import.path.as_deref().unwrap_or(import_type.default_path());
+        let path = config_dir.join(relative_path);
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Unable to import {}", path.display()))?;
+        let tasks = match import_type {
+            ImportType::Make => make_targets(&content, relative_path),
+            ImportType::Npm => {
+                let dir = path.parent().unwrap_or(config_dir);
+                npm_scripts(&content, package_manager(dir))
+                    .with_context(|| format!("Unable to import {}", path.display()))?
+            }
+        };
+        let name = import.name.as_deref().unwrap_or(import_type.default_name());
+        let key = import.key.unwrap_or(name.chars().next().unwrap_or('i'));
+        let working_dir = Path::new(relative_path).parent();
+        groups.push(group(name, key, tasks, working_dir));
+    }
+    Ok(())
+}
+
+fn group(name: &str, key: char, tasks: Vec<ImportedTask>, working_dir: Option<&Path>) -> Value {
+    let keys = assign_keys(tasks.iter().map(|t| t.name.as_str()));
+    let tasks = tasks
+        .into_iter()
+        .zip(keys)
+        .filter_map(|(task, key)| Some((task, key?)))
+        .map(|(task, key)| {
+            let mut mapping = Mapping::new();
+            mapping.insert("name".into(), task.name.into());
+            mapping.insert("key".into(), key.to_string().into());
+            if let Some(description) = task.description {
+                mapping.insert("description".into(), description.into());
+            }
+            mapping.insert("cmd".into(), task.cmd.into());
+            if let Some(dir) = working_dir.filter(|d| !d.as_os_str().is_empty()) {
+                mapping.insert("working_dir".into(), dir.to_string_lossy().as_ref().into());
+            }
+            Value::Mapping(mapping)
+        })
+        .collect::<Vec<_>>();
+    let mut mapping = Mapping::new();
+    mapping.insert("name".into(), name.into());
+    mapping.insert("key".into(), key.to_string().into());
+    mapping.insert("tasks".into(), Value::Sequence(tasks));
+    Value::Mapping(mapping)
+}
+
+/// Assigns a unique key to each name: the first letter of the name not taken yet, falling back
+/// to any free letter or digit. `None` if there are no free keys left
+fn assign_keys<'a>(names: impl Iterator<Item = &'a str>) -> Vec<Option<char>> {
+    let mut taken = vec![];
+    let fallback = ('a'..='z').chain('0'..='9');
+    names
+        .map(|name| {
+            let key = name
+                .chars()
+                .filter(char::is_ascii_alphanumeric)
+                .map(|c| c.to_ascii_lowercase())
+                .chain(fallback.clone())
+                .find(|c| !taken.contains(c));
+            taken.extend(key);
+            key
+        })
+        .collect()
+}
+
+/// Explicit targets of the Makefile. Descriptions are taken from `## ` comments after the target
+/// or from the comment on the line before it
+fn make_targets(makefile: &str, path: &str) -> Vec<ImportedTask> {
+    let mut tasks: Vec<ImportedTask> = vec![];
+    let mut comment = None;
+    for line in makefile.lines() {
+        if let Some(text) = line.strip_prefix('#') {
+            comment = Some(text.trim_start_matches('#').trim().to_string());
+            continue;
+        }
+        let previous_comment = comment.take();
+        if line.starts_with(['\t', ' ']) {
+            continue;
+        }
+        let (rule, inline_comment) = match line.split_once("##") {
+            Some((rule, comment)) => (rule, Some(comment.trim().to_string())),
+            None => (line, None),
+        };
+        let Some((targets, rest)) = rule.split_once(':') else {
+            continue;
+        };
+        // Variable assignments (`X := 1`, `X ::= 1`) and targets with variables or patterns
+        let is_assignment =
+            rest.starts_with('=') || rest.starts_with(":=") || targets.contains('=');
+        if is_assignment || targets.contains(['$', '%']) {
+            continue;
+        }
+        for target in targets.split_whitespace() {
+            if target.starts_with('.') || tasks.iter().any(|t| t.name == target) {
+                continue;
+            }
+            let file = Path::new(path).file_name().unwrap_or_default();
+            let cmd = match file.to_str() {
+                Some("Makefile" | "makefile" | "GNUmakefile") => format!("make {}", target),
+                _ => format!("make -f {} {}", file.to_string_lossy(), target),
+            };
+            tasks.push(ImportedTask {
+                name: target.to_string(),
+                description: inline_comment.clone().or(previous_comment.clone()),
+                cmd,
+            });
+        }
+    }
+    tasks
+}
+
+/// Package manager of the JS project determined by its lock file
+fn package_manager(dir: &Path) -> &'static str {
+    if dir.join("yarn.lock").exists() {
+        "yarn"
+    } else if dir.join("pnpm-lock.yaml").exists() {
+        "pnpm"
+    } else {
+        "npm"
+    }
+}
+
+/// Scripts of the package.json (in alphabetical order). Descriptions are the scripts themselves
+fn npm_scripts(package_json: &str, package_manager: &str) -> Result<Vec<ImportedTask>> {
+    #[derive(Deserialize)]
+    struct Package {
+        #[serde(default)]
+        scripts: BTreeMap<String, String>,
+    }
+    let package: Package = serde_json::from_str(package_json)?;
+    let tasks = package
+        .scripts
+        .into_iter()
+        .map(|(name, script)| ImportedTask {
+            cmd: format!("{} run {}", package_manager, name),
+            name,
+            description: Some(script),
+        })
+        .collect();
+    Ok(tasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_make_targets() {
+        let makefile = "
+CC := gcc
+VERSION = 1.0
+.PHONY: build test
+
+# Build the binary
+build: main.c
+\t$(CC) -o app main.c
+
+test: build ## Run tests
+\t./app --test
+
+%.o: %.c
+\t$(CC) -c $<
+
+clean install:
+\trm -f app
+";
+        let targets = make_targets(makefile, "Makefile");
+        let names = targets.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["build", "test", "clean", "install"], names);
+        assert_eq!(Some("Build the binary"), targets[0].description.as_deref());
+        assert_eq!(Some("Run tests"), targets[1].description.as_deref());
+        assert_eq!(None, targets[2].description.as_deref());
+        assert_eq!("make test", targets[1].cmd);
+
+        let targets = make_targets("all:\n", "build/rules.mk");
+        assert_eq!("make -f rules.mk all", targets[0].cmd);
+    }
+
+    #[test]
+    fn check_npm_scripts() {
+        let package_json = r#"{
+            "name": "app",
+            "scripts": {
+                "test": "jest",
+                "build": "tsc -p ."
+            }
+        }"#;
+        let scripts = npm_scripts(package_json, "yarn").unwrap();
+        let names = scripts.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["build", "test"], names);
+        assert_eq!("yarn run build", scripts[0].cmd);
+        assert_eq!(Some("tsc -p ."), scripts[0].description.as_deref());
+
+        assert!(npm_scripts(r#"{"name": "app"}"#, "npm").unwrap().is_empty());
+    }
+
+    #[test]
+    fn check_assign_keys() {
+        let names = ["build", "bench", "b", "test"];
+        let keys = assign_keys(names.into_iter());
+        assert_eq!(vec![Some('b'), Some('e'), Some('a'), Some('t')], keys);
+    }
+}
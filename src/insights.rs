@@ -0,0 +1,184 @@
+//! Usage insights computed from the local history (`ttr insights`). Nothing leaves the machine.
+use crate::{
+    history,
+    state::{self, RunRecord, State},
+    Group, Result,
+};
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+/// Maximum pause between two runs for them to be considered a chain (seconds)
+const CHAIN_GAP: u64 = 10 * 60;
+
+/// Maximum number of chains reported
+const MAX_CHAINS: usize = 5;
+
+/// Maximum number of the slowest key paths reported
+const MAX_SLOWEST: usize = 10;
+
+pub fn render(state: &State, project: &Path, root: &Group) -> Result<String> {
+    let mut out = String::new();
+    let history = state
+        .projects
+        .get(project)
+        .map(|p| p.history.as_slice())
+        .unwrap_or_default();
+
+    writeln!(out, "Unused tasks (never run in {}):", project.display())?;
+    let unused = root
+        .all_tasks()
+        .into_iter()
+        .filter(|t| !history.iter().any(|r| r.path == t.path))
+        .collect::<Vec<_>>();
+    if unused.is_empty() {
+        writeln!(out, "  none")?;
+    }
+    for task in unused {
+        writeln!(out, "  {:6} {}", task.path, task.name)?;
+    }
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "Deprecated tasks (safe to delete once not run anymore):"
+    )?;
+    let deprecated = root
+        .all_tasks()
+        .into_iter()
+        .filter(|t| t.deprecated.is_some())
+        .collect::<Vec<_>>();
+    if deprecated.is_empty() {
+        writeln!(out, "  none")?;
+    }
+    for task in deprecated {
+        let runs = history.iter().filter(|r| r.path == task.path);
+        let usage = match runs.clone().map(|r| r.started_at).max() {
+            Some(last) => {
+                let age = history::format_age(state::now().saturating_sub(last));
+                format!("{} runs, last {} ago", runs.count(), age)
+            }
+            None => "never run".to_string(),
+        };
+        writeln!(out, "  {:6} {} ({})", task.path, task.name, usage)?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Frequent chains (candidates for pipelines):")?;
+    let chains = chains(history);
+    if chains.is_empty() {
+        writeln!(out, "  none")?;
+    }
+    for ((first, second), count) in chains.iter().take(MAX_CHAINS) {
+        writeln!(out, "  {} → {} ({} times)", first, second, count)?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Slowest key paths to type:")?;
+    let slowest = state
+        .projects
+        .get(project)
+        .map(|p| p.slowest_key_paths(MAX_SLOWEST))
+        .unwrap_or_default();
+    if slowest.is_empty() {
+        writeln!(out, "  none")?;
+    }
+    for (path, timing) in slowest {
+        writeln!(
+            out,
+            "  {:6} {} on average, best {} ({} times)",
+            path,
+            format_duration(timing.average_ms()),
+            format_duration(timing.best_ms),
+            timing.count
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Time spent per project:")?;
+    let mut projects = state.projects.iter().collect::<Vec<_>>();
+    projects.sort_by_key(|(path, _)| *path);
+    for (path, project) in projects {
+        let runs = project.history.len() as u64;
+        if runs == 0 {
+            continue;
+        }
+        let total = project.history.iter().map(|r| r.duration_ms).sum::<u64>();
+        writeln!(
+            out,
+            "  {}: {} runs, {} total, {} on average",
+            path.display(),
+            runs,
+            format_duration(total),
+            format_duration(total / runs)
+        )?;
+    }
+    Ok(out)
+}
+
+/// Pairs of tasks run one after another with the number of times it happened, the most
+/// frequent first. Pairs seen only once are not reported
+fn chains(history: &[RunRecord]) -> Vec<((String, String), usize)> {
+    let mut counts = HashMap::new();
+    for pair in history.windows(2) {
+        let (first, second) = (&pair[0], &pair[1]);
+        let first_end = first.started_at + first.duration_ms / 1000;
+        let is_chain = second.started_at.saturating_sub(first_end) <= CHAIN_GAP;
+        if is_chain && first.path != second.path {
+            let key = (first.name.clone(), second.name.clone());
+            *counts.entry(key).or_insert(0) += 1;
+        }
+    }
+    let mut chains = counts
+        .into_iter()
+        .filter(|(_, c)| *c > 1)
+        .collect::<Vec<_>>();
+    chains.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    chains
+}
+
+pub fn format_duration(ms: u64) -> String {
+    let secs = ms / 1000;
+    match secs {
+        0..=59 => format!("{:.1}s", ms as f64 / 1000.0),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(path: &str, started_at: u64) -> RunRecord {
+        RunRecord {
+            path: path.to_string(),
+            name: path.to_string(),
+            started_at,
+            duration_ms: 1000,
+            exit_code: Some(0),
+            outcome: None,
+            usage: None,
+            timed_out: false,
+        }
+    }
+
+    #[test]
+    fn check_chains() {
+        let history = [
+            record("b", 0),
+            record("t", 10),
+            record("b", 5000),
+            record("t", 5010),
+            record("d", 5020),
+            record("b", 9000),
+        ];
+        let expected = vec![(("b".to_string(), "t".to_string()), 2)];
+        assert_eq!(expected, chains(&history));
+    }
+
+    #[test]
+    fn check_format_duration() {
+        assert_eq!("1.5s", format_duration(1500));
+        assert_eq!("2m 05s", format_duration(125_000));
+        assert_eq!("1h 01m", format_duration(3_660_000));
+    }
+}
@@ -0,0 +1,83 @@
+//! Tasks currently running in all ttr instances (`ttr ps`).
+//!
+//! Every instance registers the process of the running task in a file next to the state file,
+//! which is removed after the task exits. Files left by the instances which were killed are
+//! ignored.
+use crate::{state, Result, Task};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Job {
+    /// Process id of the task
+    pub pid: u32,
+    /// Process id of the ttr instance running the task
+    pub ttr_pid: u32,
+    pub name: String,
+    /// Key path of the task
+    pub path: String,
+    pub project: PathBuf,
+    /// Unix timestamp of the task start
+    pub started_at: u64,
+}
+
+/// Registration of the running task removed when dropped
+pub struct Registration(PathBuf);
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn jobs_dir() -> PathBuf {
+    state::state_file().with_file_name("jobs")
+}
+
+/// Registers the process of the running task
+pub fn register(task: &Task, pid: u32, project: &Path) -> Result<Registration> {
+    let dir = jobs_dir();
+    fs::create_dir_all(&dir)?;
+    let job = Job {
+        pid,
+        ttr_pid: std::process::id(),
+        name: task.name.clone(),
+        path: task.path.clone(),
+        project: project.to_path_buf(),
+        started_at: state::now(),
+    };
+    let path = dir.join(format!("{}.json", job.ttr_pid));
+    fs::write(&path, serde_json::to_vec(&job)?)?;
+    Ok(Registration(path))
+}
+
+/// Tasks running at the moment, the earliest started first
+pub fn list() -> Result<Vec<Job>> {
+    let dir = jobs_dir();
+    let mut jobs = vec![];
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(jobs);
+    };
+    for entry in entries {
+        let content = fs::read(entry?.path())?;
+        let Ok(job) = serde_json::from_slice::<Job>(&content) else {
+            continue;
+        };
+        if is_alive(job.ttr_pid) && is_alive(job.pid) {
+            jobs.push(job);
+        }
+    }
+    jobs.sort_by_key(|j| j.started_at);
+    Ok(jobs)
+}
+
+fn is_alive(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        true
+    }
+}
@@ -0,0 +1,52 @@
+//! Keys typed in non-Latin keyboard layouts (`layout_fallback: true`).
+//!
+//! Keys of the tasks are usually Latin letters, which can't be typed while eg. a Cyrillic layout
+//! is active. Terminals report characters rather than physical keys, so the characters of the
+//! known layouts are translated to the ones at the same position of the QWERTY keyboard
+//! (`й` → `q`, `Ф` → `A`).
+
+/// Characters of the layouts and the QWERTY characters of the same keys
+const LAYOUTS: [(&str, &str); 6] = [
+    // Russian
+    (
+        "йцукенгшщзхъфывапролджэячсмитьбюё",
+        "qwertyuiop[]asdfghjkl;'zxcvbnm,.`",
+    ),
+    (
+        "ЙЦУКЕНГШЩЗХЪФЫВАПРОЛДЖЭЯЧСМИТЬБЮЁ",
+        "QWERTYUIOP{}ASDFGHJKL:\"ZXCVBNM<>~",
+    ),
+    // Letters of Ukrainian and Belarusian missing in Russian
+    ("іїєў", "s]'o"),
+    ("ІЇЄЎ", "S}\"O"),
+    // Greek
+    ("ςερτυθιοπασδφγηξκλζχψωβνμ", "wertyuiopasdfghjklzxcvbnm"),
+    ("ΕΡΤΥΘΙΟΠΑΣΔΦΓΗΞΚΛΖΧΨΩΒΝΜ", "ERTYUIOPASDFGHJKLZXCVBNM"),
+];
+
+/// QWERTY character of the key typing `ch` in one of the known layouts
+pub fn to_latin(ch: char) -> Option<char> {
+    LAYOUTS.iter().find_map(|(layout, qwerty)| {
+        let i = layout.chars().position(|c| c == ch)?;
+        qwerty.chars().nth(i)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_to_latin() {
+        for (layout, qwerty) in LAYOUTS {
+            assert_eq!(layout.chars().count(), qwerty.chars().count(), "{}", layout);
+        }
+        assert_eq!(Some('q'), to_latin('й'));
+        assert_eq!(Some('A'), to_latin('Ф'));
+        assert_eq!(Some('.'), to_latin('ю'));
+        assert_eq!(Some('s'), to_latin('і'));
+        assert_eq!(Some('g'), to_latin('γ'));
+        assert_eq!(None, to_latin('b'));
+        assert_eq!(None, to_latin('7'));
+    }
+}
@@ -0,0 +1,125 @@
+//! Resolved task tree in a machine-readable form (`ttr list --format json`), eg. for shell
+//! completions and editor plugins.
+use crate::{cheatsheet, Group, Result, Task};
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Indented tree for the terminal
+    Tree,
+    Json,
+    Yaml,
+}
+
+#[derive(Serialize)]
+struct GroupEntry<'a> {
+    name: &'a str,
+    key: char,
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    available: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tasks: Vec<TaskEntry<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<GroupEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct TaskEntry<'a> {
+    name: &'a str,
+    key: char,
+    path: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    /// Command or script of the task. Absent for parallel and serial tasks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cmd: Option<&'a str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    depends_on: &'a [String],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    parallel: &'a [String],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    serial: &'a [String],
+    target: &'a str,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    dangerous: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deprecated: Option<&'a str>,
+}
+
+/// Renders the merged task tree in the given format
+pub fn render(root: &Group, format: Format) -> Result<String> {
+    let entry = || group_entry(root, String::new());
+    match format {
+        Format::Tree => cheatsheet::tree(root),
+        Format::Json => Ok(serde_json::to_string_pretty(&entry())? + "\n"),
+        Format::Yaml => Ok(serde_yaml::to_string(&entry())?),
+    }
+}
+
+fn group_entry(group: &Group, path: String) -> GroupEntry<'_> {
+    let groups = group
+        .groups
+        .iter()
+        .map(|child| group_entry(child, format!("{}{}", path, child.key)))
+        .collect();
+    GroupEntry {
+        name: &group.name,
+        key: group.key,
+        description: group.description.as_deref(),
+        available: group.available.as_ref().map(ToString::to_string),
+        tasks: group.tasks.iter().map(task_entry).collect(),
+        groups,
+        path,
+    }
+}
+
+fn task_entry(task: &Task) -> TaskEntry<'_> {
+    TaskEntry {
+        name: &task.name,
+        key: task.key,
+        path: &task.path,
+        description: task.description.as_deref(),
+        cmd: (!task.is_composite()).then(|| task.command()),
+        depends_on: &task.depends_on,
+        parallel: &task.parallel,
+        serial: &task.serial,
+        target: task.target.name(),
+        dangerous: task.dangerous,
+        deprecated: task.deprecated.as_deref(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_json() {
+        let yaml = "
+            name: ROOT
+            key: _
+            tasks:
+            - {name: build, key: b, cmd: make, description: Builds everything}
+            - {name: all, key: a, serial: [b, gs]}
+            groups:
+            - name: git
+              key: g
+              tasks:
+              - {name: status, key: s, cmd: git status}
+        ";
+        let mut root: Group = serde_yaml::from_str(yaml).unwrap();
+        root.assign_paths("");
+        let json = render(&root, Format::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!("Builds everything", value["tasks"][0]["description"]);
+        assert_eq!(None, value["tasks"][1].get("cmd"));
+        assert_eq!(serde_json::json!(["b", "gs"]), value["tasks"][1]["serial"]);
+        assert_eq!("g", value["groups"][0]["path"]);
+        assert_eq!("gs", value["groups"][0]["tasks"][0]["path"]);
+        assert_eq!("git status", value["groups"][0]["tasks"][0]["cmd"]);
+    }
+}
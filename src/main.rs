@@ -1,28 +1,97 @@
-use clap::Parser;
+use anyhow::{anyhow, bail, Context};
+use availability::Availability;
+use cache::Artifact;
+use capture::{Capture, OutputBuffer};
+use changes::Change;
+use chrono::{Local, NaiveDateTime};
+use clap::{ArgAction, CommandFactory, Parser, Subcommand};
+use condition::{Environment, When};
+use container::{Container, Exec};
 use crossterm::{
-    cursor,
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
-    execute,
-    style::Stylize,
-    terminal::{
-        disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
-        LeaveAlternateScreen,
-    },
+    event::{KeyCode, KeyEvent, KeyModifiers},
+    style::{style, Stylize},
 };
+use dotenv::EnvFiles;
+use palette::Action;
+use remote::Remote;
+use rusage::Usage;
+use sandbox::{Network, Sandbox, SandboxDir};
 use serde::Deserialize;
+use state::{Outcome, ProjectState, RunRecord};
 use std::{
-    collections::HashMap,
-    env::current_dir,
-    fs::File,
-    io::stdout,
+    collections::{BTreeMap, HashMap},
+    env::{self, current_dir},
+    ffi::OsStr,
+    fmt::{self, Write},
+    fs::{self, File},
+    io::{self, Write as _},
     path::{Path, PathBuf},
     process::{Child, Command, ExitStatus, Stdio},
-    time::Duration,
+    sync::Mutex,
+    time::{Duration, Instant},
 };
+use target::Target;
+use theme::Theme;
+use timeout::{Timeout, Watchdog};
+use tracing::{debug, info, level_filters::LevelFilter, warn};
+use tracing_subscriber::EnvFilter;
+use ui::{Input, Ui};
+
+mod audit;
+mod availability;
+mod bisect;
+mod cache;
+mod capture;
+mod changes;
+mod cheatsheet;
+mod check;
+mod condition;
+mod config;
+mod container;
+mod dotenv;
+mod envvars;
+mod fzf;
+mod highlight;
+mod history;
+mod import;
+mod insights;
+mod jobs;
+mod layout;
+mod list;
+mod notification;
+mod overrides;
+mod palette;
+mod parallel;
+mod platform;
+mod presets;
+mod proctree;
+mod remote;
+mod rusage;
+mod sandbox;
+mod search;
+mod secrets;
+mod state;
+mod status;
+mod target;
+mod temp;
+mod template;
+mod theme;
+mod timeout;
+mod trust;
+mod ui;
+#[cfg(unix)]
+mod unix;
+mod version;
+mod watch;
+#[cfg(windows)]
+mod windows;
 
 #[derive(Parser)]
 #[command(author, version, about)]
 struct Opts {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// ask for confirmation before exiting the program
     #[arg(short = 'c', long = "confirm")]
     confirm: bool,
@@ -34,32 +103,573 @@ struct Opts {
     /// in loop mode after task completed you can select another task to run
     #[arg(long = "loop")]
     loop_mode: bool,
+
+    /// dim the task names in the selector and show the key paths slowest to type
+    #[arg(long = "train")]
+    training: bool,
+
+    /// present mode for demos: enlarged selector, tasks and groups tagged `personal` are
+    /// hidden and dangerous tasks are disabled
+    #[arg(long = "present")]
+    present: bool,
+
+    /// hide the output of tasks unless they fail
+    #[arg(short = 'q', long = "quiet")]
+    quiet: bool,
+
+    /// choose tasks with the built-in selector or an external fuzzy finder (fzf or sk)
+    #[arg(
+        long = "selector",
+        env = "TTR_SELECTOR",
+        value_enum,
+        default_value = "builtin"
+    )]
+    selector: fzf::Selector,
+
+    /// shell used to run task commands ($SHELL or sh by default). Arguments preceding the
+    /// command can be given as well (eg. `bash -lc`)
+    #[arg(long = "shell", env = "TTR_SHELL")]
+    shell: Option<String>,
+
+    /// command opening a new terminal window for tasks with `target: external-terminal`
+    #[arg(long = "terminal", env = "TTR_TERMINAL", default_value = platform::TERMINAL)]
+    terminal: String,
+
+    /// increase logging verbosity (-v for info, -vv for debug, -vvv for trace). Log filter
+    /// can also be set using TTR_LOG environment variable
+    #[arg(short = 'v', long = "verbose", action = ArgAction::Count)]
+    verbose: u8,
+
+    /// write logs to a file instead of stderr
+    #[arg(long = "log-file")]
+    log_file: Option<PathBuf>,
+
+    /// append the output of each run to a timestamped file in the directory
+    #[arg(long = "log-dir", env = "TTR_LOG_DIR")]
+    log_dir: Option<PathBuf>,
+
+    /// memory limit for captured task output, the rest is spilled to a temporary file
+    #[arg(long = "capture-limit", value_parser = capture::parse_size, default_value_t = capture::DEFAULT_LIMIT)]
+    capture_limit: usize,
+
+    /// don't offer built-in tasks for the detected project type when there is no config
+    #[arg(long = "no-presets", env = "TTR_NO_PRESETS")]
+    no_presets: bool,
+
+    /// run tasks of the groups outside of their availability windows
+    #[arg(long = "override")]
+    override_availability: bool,
+
+    /// append executed tasks to a tamper-evident audit log
+    #[arg(long = "audit-log", env = "TTR_AUDIT_LOG")]
+    audit_log: Option<PathBuf>,
+
+    /// keep the running task and the last result in a JSON file (for prompts and status bars)
+    #[arg(long = "status-file", env = "TTR_STATUS_FILE")]
+    status_file: Option<PathBuf>,
+
+    /// arguments substituted instead of `{{args}}` in the command of the selected task
+    #[arg(last = true)]
+    args: Vec<String>,
+
+    /// read key events from a script instead of the terminal (`-` for stdin)
+    #[arg(long = "input-from", hide = true)]
+    input_from: Option<PathBuf>,
+
+    /// write rendered frames to a file instead of stdout (headless mode only)
+    #[arg(long = "output-to", hide = true, requires = "input_from")]
+    output_to: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// manage state persisted between runs (history, pins etc.)
+    #[command(subcommand)]
+    State(StateCommand),
+
+    /// find the commit which broke the task using `git bisect`
+    Bisect {
+        /// key path of the task used as a test (eg. `ct`)
+        task: String,
+
+        /// known good revision
+        #[arg(long = "good")]
+        good: String,
+
+        /// known bad revision
+        #[arg(long = "bad", default_value = "HEAD")]
+        bad: String,
+    },
+
+    /// run the task without the selector (eg. `ttr run g h` or `ttr run greetings.hello`)
+    Run {
+        /// key path of the task or names of its groups and the task separated by dots
+        #[arg(required = true)]
+        task: Vec<String>,
+
+        /// value of the task argument, the default one is used if not given
+        #[arg(long = "arg", value_name = "NAME=VALUE")]
+        values: Vec<String>,
+
+        /// run the task even if it is up to date
+        #[arg(short = 'f', long = "force")]
+        force: bool,
+
+        /// run the dangerous task or the task asking for confirmation without asking
+        #[arg(short = 'y', long = "yes")]
+        yes: bool,
+
+        /// arguments substituted instead of `{{args}}` in the command of the task
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+
+    /// run the command as an ad-hoc task, without touching the config (eg. `ttr exec -- make`)
+    Exec {
+        /// name of the task shown in the status line and the history (the command by default)
+        #[arg(long = "name")]
+        name: Option<String>,
+
+        /// environment variable of the command
+        #[arg(long = "env", value_name = "NAME=VALUE")]
+        env: Vec<String>,
+
+        /// command to run. A single argument is run by the shell as is, several are quoted
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// print the merged task tree (`--format json|yaml` for scripts and editor plugins)
+    List {
+        #[arg(long = "format", value_enum, default_value = "tree")]
+        format: list::Format,
+    },
+
+    /// print a reference of all the tasks
+    Cheatsheet {
+        #[arg(long = "format", value_enum, default_value = "md")]
+        format: cheatsheet::Format,
+    },
+
+    /// print the help followed by the tasks of the current project
+    HelpTasks,
+
+    /// show usage insights computed from the local history
+    Insights,
+
+    /// check the configs for problems (unreachable tasks, duplicate commands etc.)
+    Check,
+
+    /// show process trees of the running tasks with their CPU and memory usage
+    Ps,
+
+    /// manage the audit log
+    #[command(subcommand)]
+    Audit(AuditCommand),
+
+    /// rewrite the config preserving comments
+    #[command(subcommand)]
+    Config(ConfigCommand),
+
+    /// allow tasks of the project config to be run
+    Trust {
+        /// config to trust (configs in the current directory and its parents by default)
+        config: Option<PathBuf>,
+    },
+
+    /// revoke the trust of the project config
+    Untrust {
+        /// config to untrust (configs in the current directory and its parents by default)
+        config: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum StateCommand {
+    /// forget the state of the current project
+    Clear {
+        /// forget the state of all projects
+        #[arg(long = "all")]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuditCommand {
+    /// check that no entries of the audit log were modified or removed
+    Verify {
+        /// audit log to verify (the one given with --audit-log by default)
+        log: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// order the fields of tasks and groups canonically (name, key, description, cmd...)
+    Fmt {
+        /// config to format (the nearest project config by default)
+        #[arg(long = "file")]
+        file: Option<PathBuf>,
+
+        /// only check that the config is formatted
+        #[arg(long = "check")]
+        check: bool,
+    },
+
+    /// move the task to another group
+    Mv {
+        /// key path of the task (eg. `ct`)
+        task: String,
+
+        /// key path of the target group (`.` for the top level)
+        group: String,
+
+        /// config to edit (the nearest project config by default)
+        #[arg(long = "file")]
+        file: Option<PathBuf>,
+    },
 }
 
 const TTR_CONFIG: &str = ".ttr.yaml";
 
+/// Tag of the tasks and groups hidden in present mode
+const PERSONAL_TAG: &str = "personal";
+
 type Result<T> = anyhow::Result<T>;
 
 #[derive(Deserialize, Debug)]
 struct Task {
     name: String,
     key: char,
+    /// what the task does, shown in the cheatsheet
+    description: Option<String>,
+    /// command run by the shell
+    #[serde(default)]
     cmd: String,
+    /// multi-line script run from a temporary file instead of `cmd`
+    script: Option<String>,
     #[serde(default)]
     confirm: bool,
+    clear: Option<bool>,
+    /// verbose variant of the command used when the task is rerun with `V` after failure
+    verbose_cmd: Option<String>,
+    /// flags substituted instead of `{{verbose_flags}}` in the command when the task is rerun
+    /// with `V` after failure
+    verbose_flags: Option<String>,
+    /// globs of the files the task depends on. Task is skipped if they are not changed
+    /// since the last successful run
     #[serde(default)]
-    clear: bool,
+    inputs: Vec<String>,
+    /// globs of the files produced by the task. Task is not skipped if any of them is missing
+    #[serde(default)]
+    outputs: Vec<String>,
+    /// globs of the files rerunning the task when changed. Task keeps running in the watch mode
+    /// until stopped
+    #[serde(default)]
+    watch: Vec<String>,
+    /// shell used to run the command instead of the default one, optionally with the arguments
+    /// preceding the command (`bash -lc` or `[bash, -lc]`)
+    shell: Option<Shell>,
+    /// where the task is run (current terminal by default)
+    #[serde(default, alias = "run_in")]
+    target: Target,
+    /// command opening a new terminal window (for `external-terminal` target)
+    terminal: Option<String>,
+    /// stream the output through ttr so it can be viewed after the task completes
+    #[serde(default)]
+    capture: bool,
+    /// file the output of each run is appended to, relative to the working directory.
+    /// strftime-style specifiers are replaced with the start time (eg. `logs/build-%Y%m%d.log`)
+    log: Option<String>,
+    /// task is a full-screen program (eg. `lazygit`). Terminal is handed over to it as is and
+    /// restored when it exits
+    #[serde(default)]
+    tui: bool,
+    /// locale of the command (sets `LANG` and `LC_ALL`, eg. `de_DE.UTF-8`)
+    locale: Option<String>,
+    /// timezone of the command (sets `TZ`, eg. `UTC`)
+    tz: Option<String>,
+    /// environment variables of the command
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    /// dotenv files the variables of the command are loaded from (a path or a list), relative
+    /// to the working directory
+    #[serde(default)]
+    env_file: EnvFiles,
+    /// environment variables asked with masked input before the task is run (eg. `API_TOKEN`)
+    #[serde(default)]
+    secrets: Vec<String>,
+    /// run the command with only the variables from `env` and the few needed by the shell
+    /// (`PATH`, `HOME`...)
+    clear_env: Option<bool>,
+    /// network access of the task (`none` to run without network)
+    #[serde(default)]
+    network: Network,
+    /// run the task in a temporary copy of the working directory (`temp`) or in an overlay over
+    /// it (`overlay`) discarding the changes
+    sandbox_dir: Option<SandboxDir>,
+    /// container image the command is run in (eg. `node:20`)
+    image: Option<String>,
+    /// name of the running container the command is run in with `docker exec`
+    container: Option<String>,
+    /// service of the Compose project the command is run in with `docker compose exec`
+    compose_service: Option<String>,
+    /// host the command is run on over SSH (`user@host` or an alias of `~/.ssh/config`)
+    #[serde(alias = "host")]
+    ssh: Option<String>,
+    /// directory the command is run in on the remote host or in the running container
+    remote_dir: Option<String>,
+    /// run the command in the development shell of the flake (`nix develop`)
+    #[serde(default)]
+    nix_shell: bool,
+    /// name of the development shell of the flake the command is run in (implies `nix_shell`)
+    devshell: Option<String>,
+    /// exit codes treated as success (`[0]` by default)
+    allow_exit_codes: Option<Vec<i32>>,
+    /// exit codes treated as success with warnings (eg. linter findings)
+    #[serde(default)]
+    warn_exit_codes: Vec<i32>,
+    /// substitute arguments into the command as is, without shell quoting
+    #[serde(default)]
+    raw: bool,
+    /// hide the output unless the task fails
+    #[serde(default)]
+    quiet: bool,
+    /// report files created, modified or removed by the task in the working directory and
+    /// dotfiles in the home directory
+    #[serde(default)]
+    track_changes: bool,
+    /// file (relative to the working directory) or URL opened after a successful run (eg.
+    /// `coverage/index.html`). Argument placeholders are substituted
+    open: Option<String>,
+    /// ask before opening `open` instead of opening it right away
+    #[serde(default)]
+    confirm_open: bool,
     working_dir: Option<PathBuf>,
+    /// print the resolved working directory and the environment overrides before the run and
+    /// in the confirmation
+    show_context: Option<bool>,
+    /// how to notify about the completion of the task: tmux, desktop, bell or none (tmux if
+    /// ttr is running inside it)
+    notify_backend: Option<notification::Backend>,
+    /// command which must exit successfully before the task is run (eg. checking that
+    /// the change is approved). Its output is shown as the reason if it fails
+    approval_cmd: Option<String>,
+    /// count down before running the task giving the last chance to cancel it
+    #[serde(default)]
+    dangerous: bool,
+    /// task is kept for the transition only: what to use instead (eg. `use 'make deploy'
+    /// instead`). A warning is shown before the run and the task is marked in the selector
+    deprecated: Option<String>,
+    /// ask before running the task: `true` or the question to ask (eg. `Destroy the staging
+    /// environment?`)
+    confirm_before: Option<ConfirmBefore>,
+    /// tasks run before this one in the given order (key paths from the root or names). Task
+    /// is not run if any of them fails
+    #[serde(default)]
+    depends_on: Vec<String>,
+    /// tasks run at the same time instead of the command (key paths from the root or names).
+    /// Their output is shown prefixed with the task names
+    #[serde(default)]
+    parallel: Vec<String>,
+    /// tasks run one after another instead of the command (key paths from the root or names).
+    /// The first failed task stops the run
+    #[serde(default)]
+    serial: Vec<String>,
+    /// arguments asked from the user before the task is run and substituted into the command
+    /// (`{{branch}}`)
+    #[serde(default)]
+    args: Vec<TaskArg>,
+    /// minimum version of ttr required to run the task (eg. `>=0.6`)
+    requires_ttr: Option<String>,
+    /// minimum size of the terminal the task (eg. a TUI) needs. User is warned before the task
+    /// is run in a smaller one
+    min_size: Option<TerminalSize>,
+    /// maximum duration of the run (eg. `30s`, `5m`). Task is terminated when it's exceeded
+    timeout: Option<Timeout>,
+    /// conditions on the environment (eg. `{devcontainer: true}`). Task is hidden if they
+    /// don't hold
+    #[serde(default)]
+    when: When,
+    /// tags of the task (`personal` hides it in present mode)
+    #[serde(default)]
+    tags: Vec<String>,
+    /// Key path from the root group (eg. `gd`)
+    #[serde(skip)]
+    path: String,
+}
+
+/// Argument of the task asked from the user before the run
+#[derive(Deserialize, Debug)]
+struct TaskArg {
+    /// name of the placeholder in the command
+    name: String,
+    /// text shown when asking for the value (name of the argument by default)
+    prompt: Option<String>,
+    /// value used when nothing is entered
+    default: Option<String>,
+    /// values to choose from. Any value can be entered if empty
+    #[serde(default)]
+    choices: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+enum ConfirmBefore {
+    Enabled(bool),
+    Question(String),
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+struct TerminalSize {
+    cols: u16,
+    rows: u16,
+}
+
+impl TerminalSize {
+    /// Returns true if the terminal of the given size is large enough
+    fn fits(&self, (cols, rows): (u16, u16)) -> bool {
+        cols >= self.cols && rows >= self.rows
+    }
+}
+
+impl fmt::Display for TerminalSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.cols, self.rows)
+    }
+}
+
+/// Template variables set by ttr, they can't be used as argument names
+const RESERVED_VARIABLES: [&str; 4] = ["cwd", "verbose_flags", "args", "devcontainer"];
+
+impl Task {
+    /// Script of the task if present, command otherwise
+    fn command(&self) -> &str {
+        self.script.as_deref().unwrap_or(&self.cmd)
+    }
+
+    /// Key path of the group the task belongs to
+    fn group_path(&self) -> &str {
+        &self.path[..self.path.len() - self.key.len_utf8()]
+    }
+
+    /// Question asked before running the task, if it needs to be confirmed
+    fn confirmation(&self) -> Option<String> {
+        match self.confirm_before.as_ref()? {
+            ConfirmBefore::Enabled(false) => None,
+            ConfirmBefore::Enabled(true) => Some(format!("Run {}?", self.name)),
+            ConfirmBefore::Question(question) => Some(question.clone()),
+        }
+    }
+
+    /// File or URL opened after a successful run with the argument values substituted
+    fn open_target(&self, answers: &HashMap<String, String>) -> Result<Option<String>> {
+        let Some(open) = &self.open else {
+            return Ok(None);
+        };
+        let working_dir = task_working_dir(self)?;
+        let mut context = template::Context::default();
+        context.set("cwd", working_dir.to_string_lossy());
+        for arg in &self.args {
+            if let Some(value) = answers.get(&arg.name).or(arg.default.as_ref()) {
+                context.set(&arg.name, value);
+            }
+        }
+        let target = template::render(open, &context)?;
+        Ok(Some(match target.contains("://") {
+            true => target,
+            false => working_dir.join(target).to_string_lossy().to_string(),
+        }))
+    }
+
+    /// Globs of the files rerunning the task in the watch mode: `watch` or, if not given,
+    /// `inputs`
+    fn watched_files(&self) -> &[String] {
+        match self.watch.is_empty() {
+            true => &self.inputs,
+            false => &self.watch,
+        }
+    }
+
+    /// Name of the running container the task is run in and whether it's a Compose service
+    fn running_container(&self) -> Option<(&str, bool)> {
+        match (&self.container, &self.compose_service) {
+            (Some(name), _) => Some((name, false)),
+            (None, Some(service)) => Some((service, true)),
+            (None, None) => None,
+        }
+    }
+
+    /// Returns true if the task runs other tasks (`parallel` or `serial`) instead of a command
+    fn is_composite(&self) -> bool {
+        !self.parallel.is_empty() || !self.serial.is_empty()
+    }
+
+    /// Maps exit status of the task to the outcome. Process terminated by a signal is a failure
+    fn outcome(&self, exit_status: ExitStatus) -> Outcome {
+        let Some(code) = exit_status.code() else {
+            return Outcome::Failure;
+        };
+        let allowed = match &self.allow_exit_codes {
+            Some(codes) => codes.contains(&code),
+            None => code == 0,
+        };
+        if self.warn_exit_codes.contains(&code) {
+            Outcome::Warning
+        } else if allowed {
+            Outcome::Success
+        } else {
+            Outcome::Failure
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Default)]
 struct Group {
     name: String,
     key: char,
+    /// what the tasks of the group are for, shown with `?` in the selector
+    description: Option<String>,
+    /// time window when the tasks of the group can be run
+    available: Option<Availability>,
+    /// environment variables of the tasks of the group
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    /// working directory of the tasks of the group
+    working_dir: Option<PathBuf>,
+    /// dotenv files loaded for the tasks of the group before their own ones
+    #[serde(default)]
+    env_file: EnvFiles,
+    /// tags of the group (`personal` hides it in present mode)
+    #[serde(default)]
+    tags: Vec<String>,
+    /// run the tasks of the group with the environment cleared
+    clear_env: Option<bool>,
+    /// clear the screen before running the tasks of the group
+    clear: Option<bool>,
+    /// show the working directory and the environment of the tasks of the group before the run
+    show_context: Option<bool>,
+    /// how to notify about the completion of the tasks of the group
+    notify_backend: Option<notification::Backend>,
     #[serde(default)]
     groups: Vec<Group>,
     #[serde(default)]
     tasks: Vec<Task>,
+    /// Selector is opened at the group it was left in. Set for the root group only
+    #[serde(skip)]
+    remember_position: bool,
+    /// Built-in tasks are offered when the project has no config. Set for the root group only
+    #[serde(skip)]
+    presets: Option<bool>,
+    /// Maximum number of the groups shown in the breadcrumb. Set for the root group only
+    #[serde(skip)]
+    breadcrumb_depth: Option<usize>,
+    /// Keys typed in non-Latin layouts are matched by their position. Set for the root group only
+    #[serde(skip)]
+    layout_fallback: bool,
+    /// Colors of the selector. Set for the root group only
+    #[serde(skip)]
+    theme: Theme,
 }
 
 impl Group {
@@ -74,9 +684,215 @@ impl Group {
         }
     }
 
+    /// Passes the settings of the group down to the nested tasks and groups. Values set on them
+    /// take precedence, relative working directories are resolved against the one of the group
+    fn inherit_settings(&mut self) {
+        fn inherit_env(env: &mut BTreeMap<String, String>, parent: &BTreeMap<String, String>) {
+            for (name, value) in parent {
+                env.entry(name.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        fn inherit_dir(dir: &mut Option<PathBuf>, parent: &Option<PathBuf>) {
+            if let Some(parent) = parent {
+                *dir = Some(dir.as_ref().map_or(parent.clone(), |dir| parent.join(dir)));
+            }
+        }
+        for group in &mut self.groups {
+            inherit_env(&mut group.env, &self.env);
+            group.env_file.inherit(&self.env_file);
+            inherit_dir(&mut group.working_dir, &self.working_dir);
+            group.clear_env = group.clear_env.or(self.clear_env);
+            group.clear = group.clear.or(self.clear);
+            group.show_context = group.show_context.or(self.show_context);
+            group.notify_backend = group.notify_backend.or(self.notify_backend);
+            group.inherit_settings();
+        }
+        for task in &mut self.tasks {
+            inherit_env(&mut task.env, &self.env);
+            task.env_file.inherit(&self.env_file);
+            inherit_dir(&mut task.working_dir, &self.working_dir);
+            task.clear_env = task.clear_env.or(self.clear_env);
+            task.clear = task.clear.or(self.clear);
+            task.show_context = task.show_context.or(self.show_context);
+            task.notify_backend = task.notify_backend.or(self.notify_backend);
+        }
+    }
+
+    fn validate_availability(&self) -> Result<()> {
+        if let Some(available) = &self.available {
+            available
+                .validate()
+                .with_context(|| format!("Invalid availability of group {}", self.name))?;
+        }
+        self.groups
+            .iter()
+            .try_for_each(Group::validate_availability)
+    }
+
+    /// Removes the tasks whose conditions don't hold in the current environment
+    fn remove_inapplicable(&mut self) {
+        self.tasks.retain(|task| task.when.holds());
+        self.groups.iter_mut().for_each(Group::remove_inapplicable);
+    }
+
+    /// Removes the tasks and groups tagged `personal`, so they are not shown in present mode
+    fn remove_personal(&mut self) {
+        let is_personal = |tags: &[String]| tags.iter().any(|tag| tag == PERSONAL_TAG);
+        self.tasks.retain(|task| !is_personal(&task.tags));
+        self.groups.retain(|group| !is_personal(&group.tags));
+        self.groups.iter_mut().for_each(Group::remove_personal);
+    }
+
+    /// All tasks of the group and its subgroups
+    fn all_tasks(&self) -> Vec<&Task> {
+        let mut tasks = self.tasks.iter().collect::<Vec<_>>();
+        for group in &self.groups {
+            tasks.extend(group.all_tasks());
+        }
+        tasks
+    }
+
+    /// Assigns key paths to all tasks in the group
+    fn assign_paths(&mut self, prefix: &str) {
+        for task in self.tasks.iter_mut() {
+            task.path = format!("{}{}", prefix, task.key);
+        }
+        for group in self.groups.iter_mut() {
+            group.assign_paths(&format!("{}{}", prefix, group.key));
+        }
+    }
+
+    /// Finds the task by the key path from this group (eg. `gd`)
+    fn find_task(&self, path: &str) -> Option<&Task> {
+        let mut chars = path.chars();
+        let key = chars.next()?;
+        let rest = chars.as_str();
+        if rest.is_empty() {
+            return self.tasks.iter().find(|t| t.key == key);
+        }
+        let group = self.groups.iter().find(|g| g.key == key)?;
+        group.find_task(rest)
+    }
+
+    /// Finds the task by the key path from the root or by the name
+    fn resolve(&self, reference: &str) -> Option<&Task> {
+        self.find_task(reference)
+            .or_else(|| self.all_tasks().into_iter().find(|t| t.name == reference))
+    }
+
+    /// Finds the task by the key path given in parts (eg. `g h` or `gh`) or by the names of its
+    /// groups and the task separated by dots (eg. `greetings.hello`)
+    fn find_by_path(&self, parts: &[String]) -> Option<&Task> {
+        self.find_task(&parts.concat()).or_else(|| {
+            let path = parts.join(".");
+            self.find_by_names(&path.split('.').collect::<Vec<_>>())
+        })
+    }
+
+    fn find_by_names(&self, names: &[&str]) -> Option<&Task> {
+        match names {
+            [] => None,
+            [name] => self
+                .tasks
+                .iter()
+                .find(|t| t.name.eq_ignore_ascii_case(name)),
+            [group, rest @ ..] => self
+                .groups
+                .iter()
+                .find(|g| g.name.eq_ignore_ascii_case(group))?
+                .find_by_names(rest),
+        }
+    }
+
+    /// Dependencies of the task (including the transitive ones) in the order they should be run
+    fn dependencies(&self, task: &Task) -> Result<Vec<&Task>> {
+        fn visit<'a>(
+            root: &'a Group,
+            task: &Task,
+            stack: &mut Vec<String>,
+            result: &mut Vec<&'a Task>,
+        ) -> Result<()> {
+            stack.push(task.path.clone());
+            for reference in &task.depends_on {
+                let Some(dependency) = root.resolve(reference) else {
+                    bail!("Task {} depends on unknown task {}", task.name, reference);
+                };
+                if stack.contains(&dependency.path) {
+                    bail!(
+                        "Task {} has circular dependency on {}",
+                        task.name,
+                        reference
+                    );
+                }
+                if !result.iter().any(|t| t.path == dependency.path) {
+                    visit(root, dependency, stack, result)?;
+                    result.push(dependency);
+                }
+            }
+            stack.pop();
+            Ok(())
+        }
+        let mut result = vec![];
+        visit(self, task, &mut vec![], &mut result)?;
+        Ok(result)
+    }
+
+    /// Tasks run by the composite task (`parallel` or `serial`)
+    fn composite_tasks(&self, task: &Task) -> Result<Vec<&Task>> {
+        let references = match (task.parallel.is_empty(), task.serial.is_empty()) {
+            (true, true) => return Ok(vec![]),
+            (false, true) => &task.parallel,
+            (true, false) => &task.serial,
+            (false, false) => bail!("Task {} can not be both parallel and serial", task.name),
+        };
+        let mut result = vec![];
+        for reference in references {
+            let Some(part) = self.resolve(reference) else {
+                bail!("Task {} runs unknown task {}", task.name, reference);
+            };
+            if part.is_composite() {
+                bail!(
+                    "Task {} can not run composite task {}",
+                    task.name,
+                    part.name
+                );
+            }
+            result.push(part);
+        }
+        Ok(result)
+    }
+
+    /// Finds the group on the key path of the task which is not available at the given time
+    fn unavailable_group(&self, path: &str, now: NaiveDateTime) -> Result<Option<&Group>> {
+        let mut group = self;
+        for key in path.chars() {
+            if let Some(available) = &group.available {
+                if !available.contains(now)? {
+                    return Ok(Some(group));
+                }
+            }
+            let Some(child) = group.groups.iter().find(|g| g.key == key) else {
+                break;
+            };
+            group = child;
+        }
+        Ok(None)
+    }
+
+    /// Returns true if the key is bound to a task or a group
+    fn has_key(&self, key: char) -> bool {
+        self.tasks.iter().any(|t| t.key == key) || self.groups.iter().any(|g| g.key == key)
+    }
+
     fn is_empty(&self) -> bool {
         self.tasks.is_empty() && self.groups.is_empty()
     }
+
+    /// Keys of the groups and the tasks in the order they are shown in the selector
+    fn item_keys(&self) -> Vec<char> {
+        let groups = self.groups.iter().map(|g| g.key);
+        groups.chain(self.tasks.iter().map(|t| t.key)).collect()
+    }
 }
 
 struct TaskIterator<'a> {
@@ -93,9 +909,7 @@ impl<'a> Iterator for TaskIterator<'a> {
                 return Some(task);
             }
 
-            let Some(group) = self.groups.pop() else {
-                return None;
-            };
+            let group = self.groups.pop()?;
             for task in group.tasks.iter_mut() {
                 self.tasks.push(task)
             }
@@ -112,138 +926,1175 @@ enum NextAction {
     Exit,
     SelectTask,
     RepeatTask,
+    RepeatInLoginShell,
+    RepeatVerbose,
+    /// Rerun the task every time the watched files change
+    Watch,
 }
 
-struct AlternateScreen;
-
-impl AlternateScreen {
-    fn enter() -> Self {
-        execute!(stdout(), EnterAlternateScreen, cursor::Hide)
-            .expect("Unable to enter alternative screen");
-        Self
-    }
+/// Modifiers of a single task run
+#[derive(Default, Clone, Copy)]
+struct RunOptions {
+    /// run the command in the login interactive shell, so shell aliases and functions are
+    /// available
+    login_shell: bool,
+    /// run the verbose variant of the command (`verbose_cmd` or `{{verbose_flags}}`)
+    verbose: bool,
+    /// capture the output without showing it, so it is shown only if the task fails
+    quiet: bool,
+    /// pipe the output through ttr, so it is written to the log file
+    log: bool,
 }
 
-impl Drop for AlternateScreen {
-    fn drop(&mut self) {
-        // No need to unpack Result. We can't do anything about it anyway
-        let _ = execute!(stdout(), LeaveAlternateScreen, cursor::Show);
+fn main() -> Result<()> {
+    let mut opts = Opts::parse();
+    init_logging(opts.verbose, opts.log_file.as_deref())?;
+    theme::init(Theme::default())?;
+    if let Some(fallback) = state::fallback() {
+        print_warning("State directory is unavailable", &anyhow!("{}", fallback));
     }
-}
-
-struct RawMode;
-
-impl RawMode {
-    fn enter() -> Self {
-        enable_raw_mode().expect("Unable to enable raw mode");
-        Self
+    let project = state::project_root(&current_dir()?);
+    if let Some(command) = opts.command.take() {
+        return match command {
+            Commands::State(StateCommand::Clear { all }) => {
+                state::clear((!all).then_some(project.as_path()))
+            }
+            Commands::Bisect { task, good, bad } => {
+                let tasks = load_tasks(!opts.no_presets, require_trust)?;
+                let Some(task) = tasks.find_task(&task) else {
+                    bail!("No task for key path: {}", task);
+                };
+                let shell = task_shell(task, &opts);
+                let invocation =
+                    Invocation::new(task, &shell, RunOptions::default(), &[], &HashMap::new())?;
+                bisect::run(&invocation, &good, &bad)
+            }
+            Commands::Run {
+                task,
+                values,
+                force,
+                yes,
+                args,
+            } => {
+                let tasks = load_tasks(!opts.no_presets, require_trust)?;
+                let Some(task) = tasks.find_by_path(&task) else {
+                    bail!("No task for key path or name: {}", task.join(" "));
+                };
+                if !force && is_up_to_date(task, &project) {
+                    let up_to_date = "is up to date".stylize().green();
+                    println!(
+                        "{}",
+                        theme::plain(format!(
+                            "Task {} {} (use --force to run anyway)",
+                            task.name, up_to_date
+                        ))
+                    );
+                    return Ok(());
+                }
+                if task.dangerous && !yes {
+                    bail!("Task {} is dangerous, use --yes to run it", task.name);
+                }
+                if let Some(question) = task.confirmation().filter(|_| !yes) {
+                    bail!("{} Use --yes to run task {}", question, task.name);
+                }
+                let answers = arg_values(task, &values)?;
+                check_audit_log(&opts)?;
+                match run_without_selector(&tasks, task, &opts, &project, &args, &answers)? {
+                    0 => Ok(()),
+                    code => std::process::exit(code),
+                }
+            }
+            Commands::Exec { name, env, command } => {
+                let task = adhoc_task(name, &env, &command)?;
+                check_audit_log(&opts)?;
+                let (outcome, exit_status) =
+                    run_in_terminal(&task, &opts, &project, &[], &HashMap::new())?;
+                notify_completion(&task, outcome);
+                match outcome {
+                    Outcome::Failure => std::process::exit(exit_status.code().unwrap_or(1)),
+                    Outcome::Success | Outcome::Warning => Ok(()),
+                }
+            }
+            Commands::List { format } => {
+                let tasks = load_tasks(!opts.no_presets, require_trust)?;
+                print!("{}", list::render(&tasks, format)?);
+                Ok(())
+            }
+            Commands::Cheatsheet { format } => {
+                let tasks = load_tasks(!opts.no_presets, require_trust)?;
+                print!("{}", cheatsheet::render(&tasks, format)?);
+                Ok(())
+            }
+            Commands::HelpTasks => {
+                let tasks = load_tasks(!opts.no_presets, require_trust)?;
+                println!("{}", Opts::command().render_help());
+                println!("Tasks:");
+                print!("{}", cheatsheet::tree(&tasks)?);
+                Ok(())
+            }
+            Commands::Insights => {
+                let tasks = load_tasks(!opts.no_presets, require_trust)?;
+                print!(
+                    "{}",
+                    insights::render(&state::read_all()?, &project, &tasks)?
+                );
+                Ok(())
+            }
+            Commands::Check => {
+                let configs = read_tasks(!opts.no_presets, require_trust)?;
+                let tasks = load_tasks(!opts.no_presets, require_trust)?;
+                let report = check::configs(&configs) + &check::run(&tasks);
+                print!("{}", report);
+                if !report.is_empty() {
+                    bail!("Problems found in the config");
+                }
+                println!("No problems found");
+                Ok(())
+            }
+            Commands::Ps => proctree::run(&mut Ui::terminal()),
+            Commands::Audit(AuditCommand::Verify { log }) => {
+                let Some(log) = log.or(opts.audit_log) else {
+                    bail!("Audit log is not specified");
+                };
+                let entries = audit::verify(&log)?;
+                println!("Audit log is intact ({} entries)", entries);
+                Ok(())
+            }
+            Commands::Config(ConfigCommand::Fmt { file, check }) => {
+                let path = nearest_config(file)?;
+                let original = fs::read_to_string(&path)?;
+                let mut document = config::Document::parse(&original)?;
+                document.format();
+                let formatted = document.render();
+                config::ensure_equivalent(&original, &formatted)?;
+                if formatted == original {
+                    println!("{} is formatted", path.display());
+                } else if check {
+                    bail!("{} is not formatted", path.display());
+                } else {
+                    write_config(&path, &formatted)?;
+                    println!("Formatted {}", path.display());
+                }
+                Ok(())
+            }
+            Commands::Config(ConfigCommand::Mv { task, group, file }) => {
+                let path = nearest_config(file)?;
+                let original = fs::read_to_string(&path)?;
+                let group = group.trim_start_matches('.');
+                let mut document = config::Document::parse(&original)?;
+                document.move_task(&task, group)?;
+                let moved = document.render();
+                config::ensure_moved(&original, &moved, &task, group)?;
+                write_config(&path, &moved)?;
+                match group {
+                    "" => println!("Moved task {} to the top level", task),
+                    group => println!("Moved task {} to group {}", task, group),
+                }
+                Ok(())
+            }
+            Commands::Trust { config } => {
+                for config in configs_or_project(config)? {
+                    trust::trust(&config)?;
+                    println!("Trusted {}", config.display());
+                }
+                Ok(())
+            }
+            Commands::Untrust { config } => {
+                for config in configs_or_project(config)? {
+                    if trust::untrust(&config)? {
+                        println!("Untrusted {}", config.display());
+                    }
+                }
+                Ok(())
+            }
+        };
     }
-}
 
-impl Drop for RawMode {
-    fn drop(&mut self) {
-        // No need to unpack Result. We can't do anything about it anyway
-        let _ = disable_raw_mode();
+    check_audit_log(&opts)?;
+    if opts.selector != fzf::Selector::Builtin {
+        return run_with_finder(&opts, &project);
     }
-}
 
-fn main() -> Result<()> {
-    let opts = Opts::parse();
-    let tasks = merge_groups(read_tasks()?);
+    let mut ui = match &opts.input_from {
+        Some(input) => Ui::headless(input, opts.output_to.as_deref())?,
+        None => Ui::terminal(),
+    };
+    let mut tasks = load_tasks(!opts.no_presets, |config| trust_screen(&mut ui, config))?;
+    if opts.present {
+        tasks.remove_personal();
+    }
 
     let mut status_line: Option<String> = None;
+    // Full-screen task the selector returns to, so the navigation isn't lost after using a tool
+    let mut focus: Option<String> = None;
     'select_loop: loop {
-        let Some(task) = select_task(&tasks, &status_line)? else {
-            return Ok(())
+        let project_state = state::read(&project).unwrap_or_else(|e| {
+            warn!("unable to read state: {:#}", e);
+            ProjectState::default()
+        });
+        let focused = focus.as_deref().and_then(|path| tasks.find_task(path));
+        let mut position = match focused {
+            Some(task) => task.group_path().to_string(),
+            None if tasks.remember_position => project_state.position.clone().unwrap_or_default(),
+            None => String::new(),
         };
-
-        'task_loop: loop {
-            if task.clear || opts.clear {
-                execute!(stdout(), Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+        let highlight = focused.map(|task| task.path.as_str());
+        let modes = Modes {
+            loop_mode: opts.loop_mode,
+            clear: opts.clear,
+            confirm: opts.confirm,
+            training: opts.training,
+            present: opts.present,
+            ..Modes::default()
+        };
+        let choice = select_task(
+            &mut ui,
+            &tasks,
+            &project_state,
+            &status_line,
+            &mut position,
+            highlight,
+            modes,
+        )?;
+        if tasks.remember_position && project_state.position.as_ref() != Some(&position) {
+            let save = state::update(&project, |state| state.position = Some(position));
+            if let Err(e) = save {
+                warn!("unable to save state: {:#}", e);
             }
-            let exit_status = create_process(task)?.wait()?;
-            status_line = Some(format_status_line(task, exit_status));
-
-            if !exit_status.success() || task.confirm || opts.confirm {
-                match confirm_task(exit_status) {
-                    NextAction::Continue if opts.loop_mode => continue 'select_loop,
-                    NextAction::Continue | NextAction::Exit => break 'select_loop,
-                    NextAction::RepeatTask => continue 'task_loop,
-                    NextAction::SelectTask => continue 'select_loop,
-                }
+        }
+        let Selection {
+            task,
+            force,
+            typed_ms,
+        } = match choice {
+            Choice::Task(selection) => selection,
+            Choice::Quit => return Ok(()),
+            Choice::Action(
+                action @ (Action::ToggleLoop
+                | Action::ToggleClear
+                | Action::ToggleConfirm
+                | Action::ToggleTraining),
+            ) => {
+                let (name, mode) = match action {
+                    Action::ToggleLoop => ("Loop", &mut opts.loop_mode),
+                    Action::ToggleClear => ("Clear", &mut opts.clear),
+                    Action::ToggleTraining => ("Training", &mut opts.training),
+                    _ => ("Confirm", &mut opts.confirm),
+                };
+                *mode = !*mode;
+                let state = if *mode { "on" } else { "off" };
+                status_line = Some(format!("{} mode is {}", name, state.stylize().bold()));
+                continue 'select_loop;
             }
-            if opts.loop_mode {
+            Choice::Action(Action::OpenConfig) if opts.present => {
+                let disabled = "is disabled in present mode".stylize().yellow();
+                status_line = Some(format!("Editing the config {}", disabled));
+                continue 'select_loop;
+            }
+            Choice::Action(action) => {
+                if action == Action::OpenConfig {
+                    match nearest_config(None) {
+                        Ok(config) => run_user_program("EDITOR", platform::EDITOR, config)?,
+                        Err(e) => {
+                            let error = theme::current().error();
+                            status_line = Some(format!("{:#}", e).with(error).to_string());
+                            continue 'select_loop;
+                        }
+                    }
+                }
+                status_line = Some(
+                    match load_tasks(!opts.no_presets, |config| trust_screen(&mut ui, config)) {
+                        Ok(mut reloaded) => {
+                            if opts.present {
+                                reloaded.remove_personal();
+                            }
+                            tasks = reloaded;
+                            "Config reloaded".to_string()
+                        }
+                        Err(e) => format!("Unable to reload config: {:#}", e)
+                            .stylize()
+                            .red()
+                            .to_string(),
+                    },
+                );
                 continue 'select_loop;
-            } else {
-                break 'select_loop;
             }
+        };
+        if opts.present && task.dangerous {
+            let disabled = "is disabled in present mode".stylize().yellow();
+            status_line = Some(format!("Dangerous task {} {}", task.name, disabled));
+            continue 'select_loop;
         }
-    }
-
-    Ok(())
-}
+        // Longer pauses are not typing (eg. the user was away)
+        if let Some(ms) = typed_ms.filter(|ms| *ms <= MAX_TYPING_MS) {
+            if let Err(e) = state::update(&project, |state| state.record_keys(&task.path, ms)) {
+                warn!("unable to save state: {:#}", e);
+            }
+        }
+        focus = task.tui.then(|| task.path.clone());
 
-fn format_status_line(task: &Task, exit_status: ExitStatus) -> String {
-    if exit_status.success() {
-        let completed = "completed".stylize().green();
-        format!("Task {} {}", task.name, completed)
-    } else {
-        let failed = "failed".stylize().red();
-        format!("Task {} {} ({})", task.name, failed, exit_status)
-    }
-}
+        if !force && is_up_to_date(task, &project) {
+            let up_to_date = "is up to date".stylize().green();
+            let message = format!("Task {} {} (use ! to force)", task.name, up_to_date);
+            match skip_task(&mut status_line, message, opts.loop_mode) {
+                NextAction::SelectTask => continue 'select_loop,
+                _ => break 'select_loop,
+            }
+        }
+
+        let now = Local::now().naive_local();
+        let unavailable = tasks.unavailable_group(&task.path, now)?;
+        if let Some(group) = unavailable.filter(|_| !opts.override_availability) {
+            let available = group.available.as_ref().unwrap();
+            let unavailable = "is not available now".stylize().red();
+            let message = format!(
+                "Group {} {} ({}), use --override to run anyway",
+                group.name, unavailable, available
+            );
+            match skip_task(&mut status_line, message, opts.loop_mode) {
+                NextAction::SelectTask => continue 'select_loop,
+                _ => break 'select_loop,
+            }
+        }
+
+        let last_answers = project_state.prompts.get(&task.path);
+        let Some(answers) = prompt_args(&mut ui, task, last_answers)? else {
+            continue 'select_loop;
+        };
+        if !prompt_secrets(&mut ui, task)? {
+            continue 'select_loop;
+        }
+        if !answers.is_empty() {
+            let save = state::update(&project, |state| {
+                state.prompts.insert(task.path.clone(), answers.clone());
+            });
+            if let Err(e) = save {
+                warn!("unable to save state: {:#}", e);
+            }
+        }
+
+        let confirmed = match task.confirmation() {
+            Some(question) => confirm_screen(&mut ui, task, &question)?,
+            None => true,
+        };
+        if !confirmed || (task.dangerous && !countdown(&mut ui, task)?) {
+            let message = format!("Task {} {}", task.name, "cancelled".stylize().yellow());
+            match skip_task(&mut status_line, message, opts.loop_mode) {
+                NextAction::SelectTask => continue 'select_loop,
+                _ => break 'select_loop,
+            }
+        }
+
+        let too_small = task.min_size.filter(|_| task.target == Target::Local);
+        if let Some(min_size) = too_small.filter(|size| !size.fits(ui.size().unwrap_or_default())) {
+            if !size_screen(&mut ui, task, min_size)? {
+                let message = format!("Task {} {}", task.name, "cancelled".stylize().yellow());
+                match skip_task(&mut status_line, message, opts.loop_mode) {
+                    NextAction::SelectTask => continue 'select_loop,
+                    _ => break 'select_loop,
+                }
+            }
+        }
+
+        for dependency in tasks.dependencies(task)? {
+            let no_answers = HashMap::new();
+            let outcome = match run_in_terminal(dependency, &opts, &project, &[], &no_answers) {
+                Ok((outcome, _)) => outcome,
+                Err(e) => {
+                    warn!(task = dependency.name, "unable to start task: {:#}", e);
+                    let error = "Unable to start task".with(theme::current().error());
+                    eprintln!("{}", theme::plain(format!("{}: {:#}", error, e)));
+                    Outcome::Failure
+                }
+            };
+            if outcome == Outcome::Failure {
+                let failed = "failed".stylize().red();
+                let message = format!(
+                    "Task {} is not run, dependency {} {}",
+                    task.name, dependency.name, failed
+                );
+                match skip_task(&mut status_line, message, opts.loop_mode) {
+                    NextAction::SelectTask => continue 'select_loop,
+                    _ => break 'select_loop,
+                }
+            }
+        }
+
+        if task.is_composite() {
+            let result = match task.serial.is_empty() {
+                true => run_parallel(task, &tasks, &opts, &project)
+                    .map(|(_, message)| (message, NextAction::Continue)),
+                false => run_serial(&mut ui, task, &tasks, &opts, &project),
+            };
+            let (message, next_action) = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    warn!(task = task.name, "unable to start task: {:#}", e);
+                    let error = "Unable to start task".with(theme::current().error());
+                    eprintln!("{}", theme::plain(format!("{}: {:#}", error, e)));
+                    let failed = "failed to start".stylize().red();
+                    let message = format!("Task {} {}", task.name, failed);
+                    (message, NextAction::Continue)
+                }
+            };
+            status_line = Some(message);
+            match next_action {
+                NextAction::Continue if opts.loop_mode => continue 'select_loop,
+                NextAction::SelectTask => continue 'select_loop,
+                _ => break 'select_loop,
+            }
+        }
+
+        let mut run_options = RunOptions {
+            // Output of a full-screen program can't be captured
+            quiet: (task.quiet || opts.quiet) && !task.tui,
+            ..RunOptions::default()
+        };
+        // Task is rerun when the watched files change
+        let mut watching = !task.watch.is_empty();
+        let mut opened = false;
+        'task_loop: loop {
+            if task.clear == Some(true) || opts.clear {
+                ui.clear()?;
+            }
+            let started_at = state::now();
+            let start = Instant::now();
+            let outputs =
+                cache::outputs_snapshot(task, &task_working_dir(task)?).unwrap_or_else(|e| {
+                    print_warning("Unable to read outputs", &e);
+                    HashMap::new()
+                });
+            // Task is run without tracking changes if the files can't be listed
+            let snapshot = task
+                .track_changes
+                .then(|| task_working_dir(task).and_then(|dir| changes::Snapshot::take(&dir)))
+                .transpose()
+                .unwrap_or_else(|e| {
+                    print_warning("Unable to track changes", &e);
+                    None
+                });
+            let shell = task_shell(task, &opts);
+            let terminal = task.terminal.as_ref().unwrap_or(&opts.terminal);
+            let log = task_log(task, &opts)?;
+            run_options.log = log.is_some();
+            let started = task
+                .sandbox_dir
+                .map(|kind| task_working_dir(task).and_then(|dir| Sandbox::create(kind, &dir)))
+                .transpose()
+                .and_then(|sandbox| {
+                    let (args, sandbox_ref) = (&opts.args, sandbox.as_ref());
+                    let child = start_task(
+                        task,
+                        &shell,
+                        run_options,
+                        terminal,
+                        args,
+                        &answers,
+                        sandbox_ref,
+                    )?;
+                    Ok((child, sandbox))
+                });
+            let (mut child, scripts, sandbox) = match started {
+                Ok((Some((child, scripts)), sandbox)) => (child, scripts, sandbox),
+                Ok((None, _)) => {
+                    audit(&opts, &project, task, run_options, started_at, 0, None);
+                    let started = format!("started in {}", task.target).stylize().green();
+                    status_line = Some(format!("Task {} {}", task.name, started));
+                    if opts.loop_mode {
+                        continue 'select_loop;
+                    } else {
+                        break 'select_loop;
+                    }
+                }
+                Err(e) => {
+                    warn!(task = task.name, "unable to start task: {:#}", e);
+                    let failed = "failed to start".stylize().red();
+                    status_line = Some(format!("Task {} {}", task.name, failed));
+                    match error_screen(&mut ui, task, &e)? {
+                        NextAction::RepeatTask => continue 'task_loop,
+                        NextAction::Exit => break 'select_loop,
+                        _ => continue 'select_loop,
+                    }
+                }
+            };
+            let job = register_job(task, &child, &opts, &project);
+            let capture = (task.capture || run_options.quiet || log.is_some())
+                .then(|| Capture::start(&mut child, opts.capture_limit, run_options.quiet, log));
+            let watchdog = task
+                .timeout
+                .map(|timeout| Watchdog::start(child.id(), timeout));
+            let (exit_status, usage) = rusage::wait(&mut child)?;
+            let timed_out = watchdog.is_some_and(Watchdog::stop);
+            drop(scripts);
+            drop(job);
+            #[cfg(unix)]
+            unix::task_exited();
+            #[cfg(windows)]
+            windows::task_exited();
+            if task.tui {
+                ui.restore()?;
+            }
+            let outcome = task.outcome(exit_status);
+            info!(task = task.name, status = %exit_status, ?outcome, "task exited");
+            let mut output = capture.map(Capture::finish).transpose()?;
+            if let Some(output) = output.as_mut().filter(|_| run_options.quiet) {
+                if outcome == Outcome::Success {
+                    let hidden = format!(
+                        "{} of output hidden",
+                        capture::format_size(output.len() as u64)
+                    );
+                    println!(
+                        "{}",
+                        theme::plain(format!(
+                            "Task {} {} ({})",
+                            task.name,
+                            "completed".stylize().green(),
+                            hidden
+                        ))
+                    );
+                } else {
+                    output.replay(&mut io::stdout())?;
+                }
+            }
+            let changes = match (&sandbox, &snapshot) {
+                (Some(sandbox), _) => sandbox.changes()?,
+                (None, Some(before)) => task_working_dir(task)
+                    .and_then(|dir| changes::Snapshot::take(&dir))
+                    .map(|after| before.diff(&after))
+                    .unwrap_or_else(|e| {
+                        print_warning("Unable to track changes", &e);
+                        vec![]
+                    }),
+                (None, None) => vec![],
+            };
+            let status = format_status_line(task, exit_status, outcome, usage, timed_out);
+            notify_completion(task, outcome);
+            if timed_out {
+                println!("{}", theme::plain(&status));
+            }
+            status_line = Some(status);
+            let record = RunRecord {
+                path: task.path.clone(),
+                name: task.name.clone(),
+                started_at,
+                duration_ms: start.elapsed().as_millis() as u64,
+                exit_code: exit_status.code(),
+                outcome: Some(outcome),
+                usage,
+                timed_out,
+            };
+            let (duration_ms, exit_code) = (record.duration_ms, record.exit_code);
+            report_run(&opts, &record);
+            audit(
+                &opts,
+                &project,
+                task,
+                run_options,
+                started_at,
+                duration_ms,
+                exit_code,
+            );
+            // Outputs of the sandboxed task are discarded, so it is never up to date
+            let inputs_hash = match outcome == Outcome::Success && sandbox.is_none() {
+                true => cache::inputs_hash(task, &task_working_dir(task)?).unwrap_or_else(|e| {
+                    print_warning("Unable to read inputs", &e);
+                    None
+                }),
+                false => None,
+            };
+            let save = state::update(&project, |state| {
+                state.record_run(record);
+                if let Some(inputs_hash) = inputs_hash {
+                    state.inputs_hashes.insert(task.path.clone(), inputs_hash);
+                }
+            });
+            if let Err(e) = save {
+                print_warning("Unable to save state", &e);
+            }
+
+            let artifacts = cache::updated_outputs(task, &task_working_dir(task)?, &outputs)
+                .unwrap_or_else(|e| {
+                    print_warning("Unable to read outputs", &e);
+                    vec![]
+                });
+            let open = match outcome {
+                Outcome::Failure => None,
+                _ => task.open_target(&answers)?,
+            };
+            // Result is opened once, not on every rerun
+            if let Some(open) = open.as_ref().filter(|_| !task.confirm_open && !opened) {
+                if let Err(e) = open_path(open) {
+                    print_warning(&format!("Unable to open {}", open), &e);
+                }
+                opened = true;
+            }
+            let open = open.filter(|_| task.confirm_open);
+            let has_summary = !task.outputs.is_empty() || task.track_changes || sandbox.is_some();
+            let failed = outcome != Outcome::Success;
+            if !watching
+                && (failed || task.confirm || opts.confirm || has_summary || open.is_some())
+            {
+                let output = output.as_mut();
+                let results = RunResults {
+                    artifacts: &artifacts,
+                    changes: &changes,
+                    open: open.as_deref(),
+                };
+                match confirm_task(&mut ui, task, exit_status, usage, output, &results)? {
+                    NextAction::Continue if opts.loop_mode => continue 'select_loop,
+                    NextAction::Continue | NextAction::Exit => break 'select_loop,
+                    NextAction::RepeatTask => continue 'task_loop,
+                    NextAction::RepeatInLoginShell => {
+                        run_options.login_shell = true;
+                        continue 'task_loop;
+                    }
+                    NextAction::RepeatVerbose => {
+                        run_options.verbose = true;
+                        continue 'task_loop;
+                    }
+                    NextAction::SelectTask => continue 'select_loop,
+                    NextAction::Watch => watching = true,
+                }
+            }
+            if watching && watch::wait(&mut ui, &task_working_dir(task)?, task.watched_files())? {
+                continue 'task_loop;
+            }
+            if opts.loop_mode {
+                continue 'select_loop;
+            } else {
+                break 'select_loop;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Initializes logging. Logs are disabled unless `-v` flag or `TTR_LOG` variable is given
+fn init_logging(verbose: u8, log_file: Option<&Path>) -> Result<()> {
+    let level = match verbose {
+        0 => LevelFilter::OFF,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .with_env_var("TTR_LOG")
+        .from_env()?;
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    if let Some(log_file) = log_file {
+        let file = File::options().create(true).append(true).open(log_file)?;
+        subscriber
+            .with_ansi(false)
+            .with_writer(Mutex::new(file))
+            .init();
+    } else {
+        subscriber.with_writer(io::stderr).init();
+    }
+    Ok(())
+}
+
+fn format_status_line(
+    task: &Task,
+    exit_status: ExitStatus,
+    outcome: Outcome,
+    usage: Option<Usage>,
+    timed_out: bool,
+) -> String {
+    let line = match outcome {
+        _ if timed_out => {
+            let timed_out = "timed out".stylize().red();
+            let timeout = task.timeout.map(|t| t.to_string()).unwrap_or_default();
+            format!("Task {} {} after {}", task.name, timed_out, timeout)
+        }
+        Outcome::Success => {
+            let completed = "completed".stylize().green();
+            format!("Task {} {}", task.name, completed)
+        }
+        Outcome::Warning => {
+            let warnings = "completed with warnings".stylize().yellow();
+            format!("Task {} {} ({})", task.name, warnings, exit_status)
+        }
+        Outcome::Failure => {
+            let failed = "failed".stylize().red();
+            format!("Task {} {} ({})", task.name, failed, exit_status)
+        }
+    };
+    match usage {
+        Some(usage) => format!("{} {}", line, format!("· {}", usage).stylize().grey()),
+        None => line,
+    }
+}
+
+/// Shows the error occurred while starting the task and asks user what to do next
+fn error_screen(ui: &mut Ui, task: &Task, error: &anyhow::Error) -> Result<NextAction> {
+    let _alt = ui.alternate_screen();
+    let mut frame = String::new();
+    writeln!(frame)?;
+    let prefix = "   ";
+    writeln!(
+        frame,
+        "{}Unable to start task {}",
+        prefix,
+        task.name.as_str().stylize().bold()
+    )?;
+    writeln!(frame)?;
+    for cause in error.chain() {
+        writeln!(frame, "{}{}", prefix, style(cause).red())?;
+    }
+    writeln!(frame)?;
+    writeln!(
+        frame,
+        "{}Press {} to return to the menu, {}etry or {}uit...",
+        prefix,
+        "Enter".stylize().yellow().bold(),
+        "r".stylize().yellow().bold(),
+        "q".stylize().yellow().bold(),
+    )?;
+    ui.draw(&frame)?;
+
+    loop {
+        match ui.next_key_event()?.code {
+            KeyCode::Enter | KeyCode::Esc => break Ok(NextAction::SelectTask),
+            KeyCode::Char('r') => break Ok(NextAction::RepeatTask),
+            KeyCode::Char('q') => break Ok(NextAction::Exit),
+            _ => continue,
+        }
+    }
+}
+
+/// Leaves the selected task without running it. In loop mode the selector is shown again with
+/// the message in the status line, otherwise the message is printed and ttr exits
+fn skip_task(status_line: &mut Option<String>, message: String, loop_mode: bool) -> NextAction {
+    if !loop_mode {
+        println!("{}", theme::plain(&message));
+    }
+    *status_line = Some(message);
+    match loop_mode {
+        true => NextAction::SelectTask,
+        false => NextAction::Exit,
+    }
+}
+
+/// Asks the user to trust the project config unless it is already trusted. Returns false
+/// if the config should be ignored
+fn trust_screen(ui: &mut Ui, config: &Path) -> Result<bool> {
+    if trust::is_trusted(config)? {
+        return Ok(true);
+    }
+    let mut frame = String::new();
+    writeln!(frame)?;
+    let prefix = "   ";
+    writeln!(
+        frame,
+        "{}Config {} is not trusted",
+        prefix,
+        style(config.display()).bold()
+    )?;
+    writeln!(frame)?;
+    writeln!(
+        frame,
+        "{}Its tasks can run arbitrary commands. Trust it only if you've reviewed it",
+        prefix
+    )?;
+    writeln!(
+        frame,
+        "{}Fingerprint: {}",
+        prefix,
+        trust::fingerprint(config)?
+    )?;
+    writeln!(frame)?;
+    writeln!(
+        frame,
+        "{}Press {} to trust it, {} to ignore it or {} to view it...",
+        prefix,
+        "y".stylize().yellow().bold(),
+        "n".stylize().yellow().bold(),
+        "v".stylize().yellow().bold(),
+    )?;
+    ui.print(&frame)?;
+
+    loop {
+        match ui.next_key_event()?.code {
+            KeyCode::Char('y') => {
+                trust::trust(config)?;
+                break Ok(true);
+            }
+            KeyCode::Char('n') | KeyCode::Esc => break Ok(false),
+            KeyCode::Char('v') => {
+                run_user_program("PAGER", platform::PAGER, config)?;
+                ui.print(&frame)?;
+            }
+            _ => continue,
+        }
+    }
+}
+
+/// What the run produced, offered in the confirmation dialog
+#[derive(Clone, Copy)]
+struct RunResults<'a> {
+    /// outputs of the task updated by the run
+    artifacts: &'a [Artifact],
+    /// files changed by the run (for tasks tracking changes)
+    changes: &'a [Change],
+    /// file or URL to open if the task asks to confirm opening it
+    open: Option<&'a str>,
+}
+
+fn confirm_task(
+    ui: &mut Ui,
+    task: &Task,
+    exit_status: ExitStatus,
+    usage: Option<Usage>,
+    mut output: Option<&mut OutputBuffer>,
+    results: &RunResults,
+) -> Result<NextAction> {
+    let working_dir = task_working_dir(task)?;
+    let RunResults {
+        artifacts,
+        changes,
+        open,
+    } = *results;
 
-fn confirm_task(exit_status: ExitStatus) -> NextAction {
     // Print confirmation dialog
-    println!();
+    let mut frame = String::new();
+    writeln!(frame)?;
     let prefix = "   ";
-    if exit_status.success() {
-        println!("{}Task {}", prefix, "completed".stylize().green().bold(),);
-    } else {
-        println!(
+    let outcome = task.outcome(exit_status);
+    let failed = outcome == Outcome::Failure;
+    match outcome {
+        Outcome::Success => writeln!(
+            frame,
+            "{}Task {}",
+            prefix,
+            "completed".stylize().green().bold(),
+        )?,
+        Outcome::Warning => writeln!(
+            frame,
+            "{}Task {} ({})",
+            prefix,
+            "completed with warnings".stylize().yellow().bold(),
+            exit_status,
+        )?,
+        Outcome::Failure => writeln!(
+            frame,
             "{}Task {} ({})",
             prefix,
             "failed".stylize().red().bold(),
             exit_status,
-        );
+        )?,
+    };
+    if let Some(usage) = usage {
+        writeln!(frame, "{}{}", prefix, usage.to_string().stylize().grey())?;
+    }
+    let hints = match failed {
+        true => failure_hints(task, exit_status),
+        false => vec![],
     };
-    println!();
-    println!(
+    if !hints.is_empty() {
+        writeln!(frame)?;
+    }
+    for hint in &hints {
+        writeln!(frame, "{}{} {}", prefix, "hint:".stylize().yellow(), hint)?;
+    }
+    if !task.outputs.is_empty() {
+        writeln!(frame)?;
+        if artifacts.is_empty() {
+            writeln!(frame, "{}No outputs were updated", prefix)?;
+        }
+        for (i, artifact) in artifacts.iter().enumerate().take(MAX_ARTIFACTS) {
+            let path = artifact.path.strip_prefix(&working_dir);
+            writeln!(
+                frame,
+                "{}  {} → {} ({})",
+                prefix,
+                style(i + 1).bold().green(),
+                path.unwrap_or(&artifact.path).display(),
+                capture::format_size(artifact.size),
+            )?;
+        }
+        if artifacts.len() > MAX_ARTIFACTS {
+            let more = artifacts.len() - MAX_ARTIFACTS;
+            writeln!(frame, "{}  … and {} more", prefix, more)?;
+        }
+    }
+    if task.track_changes || task.sandbox_dir.is_some() {
+        writeln!(frame)?;
+        if task.sandbox_dir.is_some() {
+            writeln!(frame, "{}Changes made in the sandbox are discarded", prefix)?;
+        }
+        if changes.is_empty() {
+            writeln!(frame, "{}No files were changed", prefix)?;
+        }
+        let home = dirs::home_dir();
+        for change in changes.iter().take(MAX_CHANGES) {
+            let path = change.path();
+            let path = match (path.strip_prefix(&working_dir), &home) {
+                (Ok(relative), _) => relative.display().to_string(),
+                (_, Some(home)) if path.starts_with(home) => {
+                    format!("~/{}", path.strip_prefix(home)?.display())
+                }
+                _ => path.display().to_string(),
+            };
+            let marker = match change {
+                Change::Created(_) => style(change.marker()).green(),
+                Change::Modified(_) => style(change.marker()).yellow(),
+                Change::Removed(_) => style(change.marker()).red(),
+            };
+            writeln!(frame, "{}  {} {}", prefix, marker, path)?;
+        }
+        if changes.len() > MAX_CHANGES {
+            let more = changes.len() - MAX_CHANGES;
+            writeln!(frame, "{}  … and {} more", prefix, more)?;
+        }
+    }
+    writeln!(frame)?;
+    writeln!(
+        frame,
         "{}Press {} to continue. {}epeat or {}elect another task...",
         prefix,
         "Enter".stylize().yellow().bold(),
         "r".stylize().yellow().bold(),
         "s".stylize().yellow().bold(),
-    );
+    )?;
+    if let Some(output) = &output {
+        writeln!(
+            frame,
+            "{}Press {} to view the output ({} bytes) or {} to copy its path",
+            prefix,
+            "o".stylize().yellow().bold(),
+            output.len(),
+            "y".stylize().yellow().bold(),
+        )?;
+    }
+    writeln!(
+        frame,
+        "{}Press {} to open the working directory in the file manager or {} in the editor",
+        prefix,
+        "f".stylize().yellow().bold(),
+        "e".stylize().yellow().bold(),
+    )?;
+    let not_found = failed && exit_status.code() == Some(NOT_FOUND_EXIT_CODE);
+    if not_found {
+        writeln!(
+            frame,
+            "{}Press {} to retry in a login shell",
+            prefix,
+            "l".stylize().yellow().bold(),
+        )?;
+    }
+    if !artifacts.is_empty() {
+        writeln!(
+            frame,
+            "{}Press {} to open the output or {} to copy the paths of the outputs",
+            prefix,
+            format!("1-{}", artifacts.len().min(MAX_ARTIFACTS))
+                .stylize()
+                .yellow()
+                .bold(),
+            "Y".stylize().yellow().bold(),
+        )?;
+    }
+    if let Some(open) = open {
+        let path = Path::new(open);
+        writeln!(
+            frame,
+            "{}Press {} to open {}",
+            prefix,
+            "O".stylize().yellow().bold(),
+            path.strip_prefix(&working_dir).unwrap_or(path).display(),
+        )?;
+    }
+    let can_watch = !task.watched_files().is_empty();
+    if can_watch {
+        writeln!(
+            frame,
+            "{}Press {} to rerun on changes of {}",
+            prefix,
+            "w".stylize().yellow().bold(),
+            task.watched_files().join(", "),
+        )?;
+    }
+    let has_verbose_variant = task.verbose_cmd.is_some() || task.verbose_flags.is_some();
+    if failed && has_verbose_variant {
+        writeln!(
+            frame,
+            "{}Press {} to rerun with extra verbosity",
+            prefix,
+            "V".stylize().yellow().bold(),
+        )?;
+    }
+    ui.print(&frame)?;
 
     // Reading user decision
     loop {
-        match next_key_event().code {
-            KeyCode::Enter => break NextAction::Continue,
-            KeyCode::Char('q') | KeyCode::Esc => break NextAction::Exit,
-            KeyCode::Char('r') => break NextAction::RepeatTask,
-            KeyCode::Char('s') => break NextAction::SelectTask,
+        match ui.next_key_event()?.code {
+            KeyCode::Enter => break Ok(NextAction::Continue),
+            KeyCode::Char('q') | KeyCode::Esc => break Ok(NextAction::Exit),
+            KeyCode::Char('r') => break Ok(NextAction::RepeatTask),
+            KeyCode::Char('s') => break Ok(NextAction::SelectTask),
+            KeyCode::Char('w') if can_watch => break Ok(NextAction::Watch),
+            KeyCode::Char('l') if not_found => break Ok(NextAction::RepeatInLoginShell),
+            KeyCode::Char('V') if failed && has_verbose_variant => {
+                break Ok(NextAction::RepeatVerbose)
+            }
+            KeyCode::Char('o') => {
+                if let Some(output) = output.as_deref_mut() {
+                    print_failure(ui, prefix, view_output(output))?;
+                }
+            }
+            KeyCode::Char('y') => {
+                if let Some(output) = output.as_deref_mut() {
+                    // The file is left in place after ttr exits, so the path stays valid
+                    let path = output.keep()?.to_string_lossy().to_string();
+                    ui.copy_to_clipboard(&path)?;
+                    ui.print(&format!("{}Copied: {}\n", prefix, path))?;
+                }
+            }
+            KeyCode::Char('Y') if !artifacts.is_empty() => {
+                let paths = artifacts
+                    .iter()
+                    .map(|a| a.path.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                ui.copy_to_clipboard(&paths)?;
+                ui.print(&format!("{}Copied {} paths\n", prefix, artifacts.len()))?;
+            }
+            KeyCode::Char(ch @ '1'..='9') => {
+                let i = ch as usize - '1' as usize;
+                if let Some(artifact) = artifacts.get(i).filter(|_| i < MAX_ARTIFACTS) {
+                    print_failure(ui, prefix, open_path(&artifact.path))?;
+                }
+            }
+            KeyCode::Char('O') => {
+                if let Some(open) = open {
+                    print_failure(ui, prefix, open_path(open))?;
+                }
+            }
+            KeyCode::Char('f') => print_failure(ui, prefix, open_path(&working_dir))?,
+            KeyCode::Char('e') => {
+                let edited = run_user_program("EDITOR", platform::EDITOR, &working_dir);
+                print_failure(ui, prefix, edited)?;
+            }
             _ => continue,
         }
     }
 }
 
+/// Prints the error of the action chosen in the dialog, so it doesn't end the session
+fn print_failure(ui: &mut Ui, prefix: &str, result: Result<()>) -> Result<()> {
+    if let Err(e) = result {
+        let error = format!("{:#}", e).stylize().with(theme::current().error());
+        ui.print(&format!("{}{}\n", prefix, error))?;
+    }
+    Ok(())
+}
+
+/// Opens the file, directory or URL with the default application of the platform
+fn open_path(path: impl AsRef<OsStr>) -> Result<()> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    let status = Command::new(opener)
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("Unable to run {}", opener))?;
+    // Explorer exits with 1 even when the path is opened
+    if !status.success() && !cfg!(windows) {
+        bail!("{} failed: {}", opener, status);
+    }
+    Ok(())
+}
+
+/// Seconds counted down before running a dangerous task
+const COUNTDOWN_SECS: u64 = 5;
+
+/// Maximum number of task outputs listed in the confirmation dialog
+const MAX_ARTIFACTS: usize = 9;
+
+/// Maximum number of changed files listed in the confirmation dialog
+const MAX_CHANGES: usize = 20;
+
+/// Exit code of the shell when command is not found
+const NOT_FOUND_EXIT_CODE: i32 = 127;
+
+/// Exit code of the shell when command is found but can not be executed
+const NOT_EXECUTABLE_EXIT_CODE: i32 = 126;
+
+/// Suggests likely fixes for the well-known shell exit codes
+fn failure_hints(task: &Task, exit_status: ExitStatus) -> Vec<String> {
+    let program = first_program(task.command());
+    let path = env::var("PATH").unwrap_or_default();
+    match exit_status.code() {
+        Some(NOT_FOUND_EXIT_CODE)
+            if !program.is_empty() && find_in_path(program, &path).is_none() =>
+        {
+            vec![
+                format!("`{}` is not found in PATH: {}", program, path),
+                "If it is a shell alias or function, try to run the task in a login shell"
+                    .to_string(),
+            ]
+        }
+        Some(NOT_FOUND_EXIT_CODE) => vec![
+            "Some command is not found. Check the PATH of the command".to_string(),
+            format!("PATH: {}", path),
+        ],
+        Some(NOT_EXECUTABLE_EXIT_CODE) => {
+            vec!["Command can not be executed. Check the file permissions".to_string()]
+        }
+        _ => vec![],
+    }
+}
+
+/// Program the command or the script starts with. Comments and the shebang line are skipped
+fn first_program(command: &str) -> &str {
+    command
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .and_then(|line| line.split_whitespace().next())
+        .unwrap_or_default()
+}
+
+/// Looks for an executable in the directories listed in `path`
+fn find_in_path(program: &str, path: &str) -> Option<PathBuf> {
+    if platform::is_path(program) {
+        return Some(PathBuf::from(program)).filter(|p| p.is_file());
+    }
+    env::split_paths(path)
+        .map(|dir| dir.join(program))
+        .find(|p| p.is_file())
+}
+
 /// Deduplicate tasks by checking if there are tasks assigned to the same key.
 ///
 /// The earlier task will win and the latter will be removed from the result
 fn merge_groups(groups: Vec<Group>) -> Group {
     let mut tasks: HashMap<char, Task> = HashMap::new();
     let mut similar_groups: HashMap<char, Vec<Group>> = HashMap::new();
-    let Some(first_group) = groups.get(0) else {
+    let Some(first_group) = groups.first() else {
         return Group::default();
     };
     let group_name = first_group.name.clone();
     let group_key = first_group.key;
+    let available = first_group.available.clone();
+    let remember_position = groups.iter().any(|g| g.remember_position);
+    let breadcrumb_depth = groups.iter().find_map(|g| g.breadcrumb_depth);
+    let layout_fallback = groups.iter().any(|g| g.layout_fallback);
+    let theme = groups.iter().fold(Theme::default(), |t, g| t.or(g.theme));
     let mut groups = groups
         .into_iter()
         .filter(|g| g.name == group_name)
         .filter(|g| g.key == group_key)
         .collect::<Vec<_>>();
+    let description = groups.iter().find_map(|g| g.description.clone());
     if groups.len() == 1 {
         return groups.swap_remove(0);
     }
@@ -251,16 +2102,21 @@ fn merge_groups(groups: Vec<Group>) -> Group {
         for child_group in group.groups.into_iter() {
             similar_groups
                 .entry(child_group.key)
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(child_group)
         }
 
         for task in group.tasks.into_iter() {
             if similar_groups.contains_key(&task.key) {
                 // key is already binded to a group
+                debug!(key = %task.key, task = task.name, "task is shadowed by a group");
+                continue;
+            }
+            if let Some(existing) = tasks.get(&task.key) {
+                debug!(key = %task.key, task = task.name, by = existing.name, "task is shadowed");
                 continue;
             }
-            tasks.entry(task.key).or_insert(task);
+            tasks.insert(task.key, task);
         }
     }
 
@@ -269,25 +2125,155 @@ fn merge_groups(groups: Vec<Group>) -> Group {
         .map(merge_groups)
         .collect::<Vec<_>>();
     let merged_tasks = tasks.into_values().collect::<Vec<_>>();
+    debug!(
+        group = group_name,
+        groups = merged_groups.len(),
+        tasks = merged_tasks.len(),
+        "groups merged"
+    );
 
     Group {
         name: group_name,
         key: group_key,
+        available,
         groups: merged_groups,
         tasks: merged_tasks,
+        description,
+        remember_position,
+        breadcrumb_depth,
+        layout_fallback,
+        theme,
+        // Settings of the groups are already inherited by the tasks
+        ..Group::default()
+    }
+}
+
+/// Configs in the current directory and its parents up to the home directory
+fn project_configs() -> Result<Vec<PathBuf>> {
+    let mut configs = vec![];
+    let stop_dir = dirs::home_dir().unwrap_or(PathBuf::from("/"));
+    let start_dir = current_dir()?;
+    let mut dir = Some(start_dir.as_path());
+
+    while let Some(d) = dir {
+        if d == stop_dir {
+            break;
+        }
+        let config = d.join(TTR_CONFIG);
+        debug!(path = %config.display(), exists = config.is_file(), "looking for config");
+        if config.is_file() {
+            configs.push(config);
+        }
+        dir = d.parent()
+    }
+    Ok(configs)
+}
+
+fn configs_or_project(config: Option<PathBuf>) -> Result<Vec<PathBuf>> {
+    match config {
+        Some(config) => Ok(vec![config]),
+        None => {
+            let configs = project_configs()?;
+            if configs.is_empty() {
+                bail!(
+                    "No {} found in the current directory or its parents",
+                    TTR_CONFIG
+                );
+            }
+            Ok(configs)
+        }
+    }
+}
+
+/// Config given explicitly or the nearest one of the project
+fn nearest_config(config: Option<PathBuf>) -> Result<PathBuf> {
+    Ok(configs_or_project(config)?.remove(0))
+}
+
+/// Replaces the content of the config keeping it trusted if it was trusted before the change
+fn write_config(path: &Path, content: &str) -> Result<()> {
+    let trusted = trust::is_trusted(path)?;
+    let tmp = path.with_extension("yaml.tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)?;
+    if trusted {
+        trust::trust(path)?;
+    }
+    Ok(())
+}
+
+/// Used instead of [`trust_screen`] when there is no UI to ask the user
+fn require_trust(config: &Path) -> Result<bool> {
+    if !trust::is_trusted(config)? {
+        bail!(
+            "Config {} is not trusted. Run `ttr trust` to allow its tasks",
+            config.display()
+        );
+    }
+    Ok(true)
+}
+
+/// Loads tasks from all the configs. Project configs are loaded only if `is_trusted` allows it
+fn load_tasks(presets: bool, is_trusted: impl FnMut(&Path) -> Result<bool>) -> Result<Group> {
+    let configs = read_tasks(presets, is_trusted)?;
+    let mut tasks = merge_groups(configs.into_iter().map(|(_, group)| group).collect());
+    theme::init(tasks.theme)?;
+    tasks.assign_paths("");
+    for task in tasks.all_tasks() {
+        tasks.dependencies(task)?;
+        tasks.composite_tasks(task)?;
     }
+    Ok(tasks)
 }
 
-fn read_tasks() -> Result<Vec<Group>> {
+/// Reads the configs in the order they are merged, along with their paths
+fn read_tasks(
+    presets: bool,
+    mut is_trusted: impl FnMut(&Path) -> Result<bool>,
+) -> Result<Vec<(PathBuf, Group)>> {
     // Basically mirror [`Group`] struct without some arguments meaningless for the root group
     #[derive(Deserialize)]
     struct Root {
         groups: Option<Vec<Group>>,
         tasks: Option<Vec<Task>>,
+        #[serde(default)]
+        remember_position: bool,
+        presets: Option<bool>,
+        show_context: Option<bool>,
+        notify_backend: Option<notification::Backend>,
+        breadcrumb_depth: Option<usize>,
+        #[serde(default)]
+        layout_fallback: bool,
+        #[serde(default)]
+        theme: Theme,
     }
     fn tasks_from_file(path: impl AsRef<Path>) -> Result<Group> {
+        info!(path = %path.as_ref().display(), "reading config");
         let file = File::open(path.as_ref())?;
-        let config: Root = serde_yaml::from_reader(file)?;
+        let config: serde_yaml::Value = serde_yaml::from_reader(file)?;
+        let config_dir = path.as_ref().parent().unwrap_or(Path::new(""));
+        tasks_from_value(config, config_dir, true)
+    }
+    /// Relative paths of the config are resolved against `config_dir`. Overrides of the local
+    /// config in `config_dir` are applied if `local` is set
+    fn tasks_from_value(
+        mut config: serde_yaml::Value,
+        config_dir: &Path,
+        local: bool,
+    ) -> Result<Group> {
+        version::strip_unsupported(&mut config)?;
+        import::expand(&mut config, config_dir)?;
+        if local {
+            overrides::apply(&mut config, config_dir)?;
+        }
+        let config: Root = serde_yaml::from_value(config)?;
+        let remember_position = config.remember_position;
+        let presets = config.presets;
+        let show_context = config.show_context;
+        let notify_backend = config.notify_backend;
+        let breadcrumb_depth = config.breadcrumb_depth;
+        let layout_fallback = config.layout_fallback;
+        let theme = config.theme;
         let tasks = config.tasks.unwrap_or_default();
         let groups = config.groups.unwrap_or_default();
         let key = '_';
@@ -297,206 +2283,2355 @@ fn read_tasks() -> Result<Vec<Group>> {
             groups,
             name,
             key,
+            remember_position,
+            presets,
+            show_context,
+            notify_backend,
+            breadcrumb_depth,
+            layout_fallback,
+            theme,
+            ..Group::default()
         };
+        config.validate_availability()?;
+        config.remove_inapplicable();
+        config.inherit_settings();
         // working directories if provided interpreted as relative to the file they are defined in
-        let context_dir = path.as_ref().parent();
         for task in config.iter_mut() {
+            if version::require(task.requires_ttr.as_deref()).is_err() {
+                // Task is stripped to the name and key, so there is nothing to check
+                continue;
+            }
+            match (task.cmd.is_empty(), &task.script, task.is_composite()) {
+                (true, None, false) => bail!("Task {} has neither cmd nor script", task.name),
+                (false, Some(_), _) => bail!("Task {} has both cmd and script", task.name),
+                (false, None, true) | (true, Some(_), true) => {
+                    bail!("Task {} has both a command and tasks to run", task.name)
+                }
+                _ => {}
+            }
+            if task.image.is_some() && (task.nix_shell || task.devshell.is_some()) {
+                bail!("Task {} in a container can not use nix shell", task.name);
+            }
+            if task.image.is_some() && task.sandbox_dir == Some(SandboxDir::Overlay) {
+                bail!(
+                    "Task {} in a container can not use overlay sandbox",
+                    task.name
+                );
+            }
+            let places = [
+                task.image.is_some(),
+                task.container.is_some(),
+                task.compose_service.is_some(),
+                task.ssh.is_some(),
+            ];
+            if places.iter().filter(|p| **p).count() > 1 {
+                bail!(
+                    "Task {} can only have one of image, container, compose_service and ssh",
+                    task.name
+                );
+            }
+            if (task.ssh.is_some() || task.running_container().is_some())
+                && (task.nix_shell
+                    || task.devshell.is_some()
+                    || task.sandbox_dir.is_some()
+                    || task.network == Network::None
+                    || task.clear_env == Some(true))
+            {
+                bail!(
+                    "Task {} run over SSH or in a running container can not use nix shell, sandbox or cleared environment",
+                    task.name
+                );
+            }
+            if task.tui && task.capture {
+                bail!(
+                    "Output of the full-screen task {} can not be captured",
+                    task.name
+                );
+            }
+            if let Some(log) = &task.log {
+                if task.tui || task.target != Target::Local {
+                    bail!(
+                        "Output of task {} can only be logged when it is run in the current terminal",
+                        task.name
+                    );
+                }
+                capture::log_path(log, Local::now().naive_local())?;
+            }
+            if task.timeout.is_some() && task.target != Target::Local {
+                bail!(
+                    "Task {} with timeout can only be run in the current terminal",
+                    task.name
+                );
+            }
+            if task.sandbox_dir.is_some() && task.target != Target::Local {
+                bail!(
+                    "Task {} with sandbox_dir can only be run in the current terminal",
+                    task.name
+                );
+            }
+            for arg in &task.args {
+                if RESERVED_VARIABLES.contains(&arg.name.as_str()) {
+                    bail!(
+                        "Task {} has argument with reserved name {}",
+                        task.name,
+                        arg.name
+                    );
+                }
+            }
+            let var = |name: &str| env::var(name).ok();
+            task.cmd = envvars::expand_cmd(&task.cmd, var);
+            for value in task.env.values_mut() {
+                *value = envvars::expand(value, var);
+            }
             if let Some(working_dir) = &task.working_dir {
-                task.working_dir = context_dir.map(|p| p.join(working_dir));
+                let working_dir = envvars::expand(&working_dir.to_string_lossy(), var);
+                task.working_dir = Some(config_dir.join(working_dir));
             }
         }
-        Ok(config)
+        Ok(config)
+    }
+
+    let mut tasks = vec![];
+
+    // $TTR_EXTRA_TASKS: inline config or a path to one, taking precedence over all the configs
+    let extra_tasks = env::var("TTR_EXTRA_TASKS").unwrap_or_default();
+    if !extra_tasks.trim().is_empty() {
+        let (path, group) = if Path::new(&extra_tasks).is_file() {
+            (PathBuf::from(&extra_tasks), tasks_from_file(&extra_tasks))
+        } else {
+            info!("reading config from TTR_EXTRA_TASKS");
+            let group = serde_yaml::from_str(&extra_tasks)
+                .map_err(anyhow::Error::from)
+                .and_then(|config| tasks_from_value(config, &current_dir()?, false));
+            (PathBuf::from("$TTR_EXTRA_TASKS"), group)
+        };
+        let group = group.context("Invalid config in TTR_EXTRA_TASKS")?;
+        tasks.push((path, group));
+    }
+
+    let project_configs = project_configs()?;
+    let has_project_config = !project_configs.is_empty();
+    for config in project_configs {
+        if is_trusted(&config)? {
+            tasks.push((config.clone(), tasks_from_file(&config)?));
+        } else {
+            info!(path = %config.display(), "skipping untrusted config");
+        }
+    }
+
+    // ~/.ttr.yaml
+    let home_dir_config = dirs::home_dir()
+        .map(|home| home.join(TTR_CONFIG))
+        .filter(|config| config.is_file());
+    if let Some(config) = home_dir_config {
+        tasks.push((config.clone(), tasks_from_file(&config)?));
+    }
+
+    // ~/.config/ttr/.ttr.yaml
+    let config_dir_config = dirs::config_dir()
+        .map(|home| home.join("ttr").join(TTR_CONFIG))
+        .filter(|config| config.is_file());
+    if let Some(config) = config_dir_config {
+        tasks.push((config.clone(), tasks_from_file(&config)?));
+    }
+
+    // Built-in tasks of the detected project type, unless disabled in any of the configs
+    let disabled = tasks.iter().any(|(_, group)| group.presets == Some(false));
+    if presets && !has_project_config && !disabled {
+        if let Some((dir, config)) = presets::detect(&current_dir()?) {
+            info!(dir = %dir.display(), "using built-in presets");
+            let group = tasks_from_value(config, &dir, false).context("Invalid preset")?;
+            tasks.push((PathBuf::from("<presets>"), group));
+        }
+    }
+
+    Ok(tasks)
+}
+
+/// Fails if the audit log is enabled but can not be written, so no task is run unaudited
+fn check_audit_log(opts: &Opts) -> Result<()> {
+    match &opts.audit_log {
+        Some(log) => audit::check(log),
+        None => Ok(()),
+    }
+}
+
+/// Writes the run of the task to the audit log. The log is checked to be writable before any
+/// task is run, so failing to write it after the task has run is only reported
+fn audit(
+    opts: &Opts,
+    project: &Path,
+    task: &Task,
+    options: RunOptions,
+    started_at: u64,
+    duration_ms: u64,
+    exit_code: Option<i32>,
+) {
+    let Some(log) = &opts.audit_log else {
+        return;
+    };
+    let cmd = match &task.verbose_cmd {
+        Some(verbose_cmd) if options.verbose => verbose_cmd,
+        _ => task.command(),
+    };
+    let entry = audit::Entry {
+        started_at,
+        user: audit::user(),
+        project: project.to_path_buf(),
+        path: task.path.clone(),
+        name: task.name.clone(),
+        cmd: cmd.to_string(),
+        working_dir: task_working_dir(task).unwrap_or_default(),
+        duration_ms,
+        exit_code,
+        ..audit::Entry::default()
+    };
+    if let Err(e) = audit::append(log, entry) {
+        print_warning("Unable to write audit log", &e);
+    }
+}
+
+/// Makes the running task visible in `ttr ps` and the status file. Failure to register doesn't
+/// prevent the task from running
+fn register_job(
+    task: &Task,
+    child: &Child,
+    opts: &Opts,
+    project: &Path,
+) -> Option<jobs::Registration> {
+    report_status(opts, |status| {
+        status.running = Some(status::Running {
+            name: task.name.clone(),
+            path: task.path.clone(),
+            pid: child.id(),
+            started_at: state::now(),
+        })
+    });
+    jobs::register(task, child.id(), project)
+        .map_err(|e| warn!(task = task.name, "unable to register running task: {:#}", e))
+        .ok()
+}
+
+/// Updates the status file if it is enabled. Failure to update it doesn't affect the task
+fn report_status(opts: &Opts, f: impl FnOnce(&mut status::Status)) {
+    let Some(path) = &opts.status_file else {
+        return;
+    };
+    if let Err(e) = status::update(path, f) {
+        warn!(path = %path.display(), "unable to update status file: {:#}", e);
+    }
+}
+
+/// Lets the user know the task has completed, so it's noticed from another window
+fn notify_completion(task: &Task, outcome: Outcome) {
+    let backend = task
+        .notify_backend
+        .unwrap_or_else(notification::Backend::detect);
+    let result = match outcome {
+        Outcome::Success => "completed",
+        Outcome::Warning => "completed with warnings",
+        Outcome::Failure => "failed",
+    };
+    let summary = format!("Task {} {}", task.name, result);
+    if let Err(e) = notification::send(backend, &summary) {
+        warn!(task = task.name, "unable to send notification: {:#}", e);
+    }
+}
+
+/// Records the completed run in the status file
+fn report_run(opts: &Opts, record: &RunRecord) {
+    report_status(opts, |status| {
+        status.running = None;
+        status.last = Some(status::Last {
+            name: record.name.clone(),
+            path: record.path.clone(),
+            outcome: record.outcome(),
+            exit_code: record.exit_code,
+            duration_ms: record.duration_ms,
+        });
+    });
+}
+
+/// Runs the task in the current terminal reporting its status (eg. a dependency of the
+/// selected task)
+fn run_in_terminal(
+    task: &Task,
+    opts: &Opts,
+    project: &Path,
+    args: &[String],
+    answers: &HashMap<String, String>,
+) -> Result<(Outcome, ExitStatus)> {
+    let shell = task_shell(task, opts);
+    let options = RunOptions::default();
+    let sandbox = match task.sandbox_dir {
+        Some(kind) => Some(Sandbox::create(kind, &task_working_dir(task)?)?),
+        None => None,
+    };
+    let invocation = task_invocation(task, &shell, options, args, answers, sandbox.as_ref())?;
+    approve(task, &shell)?;
+    let log = task_log(task, opts)?;
+    warn_deprecated(task);
+    println!("{} {}", "Running".stylize().bold(), task.name);
+    print_context(task, &invocation);
+    let started_at = state::now();
+    let start = Instant::now();
+    let mut child = create_process(task, &invocation, log.is_some(), true)?;
+    let capture = log.map(|log| Capture::start(&mut child, opts.capture_limit, false, Some(log)));
+    let job = register_job(task, &child, opts, project);
+    let watchdog = task
+        .timeout
+        .map(|timeout| Watchdog::start(child.id(), timeout));
+    let (exit_status, usage) = rusage::wait(&mut child)?;
+    let timed_out = watchdog.is_some_and(Watchdog::stop);
+    capture.map(Capture::finish).transpose()?;
+    drop(job);
+    #[cfg(unix)]
+    unix::task_exited();
+    #[cfg(windows)]
+    windows::task_exited();
+    if task.tui {
+        ui::restore_terminal()?;
+    }
+    let outcome = task.outcome(exit_status);
+    info!(task = task.name, status = %exit_status, ?outcome, "task exited");
+    let record = RunRecord {
+        path: task.path.clone(),
+        name: task.name.clone(),
+        started_at,
+        duration_ms: start.elapsed().as_millis() as u64,
+        exit_code: exit_status.code(),
+        outcome: Some(outcome),
+        usage,
+        timed_out,
+    };
+    save_run(opts, project, task, options, record);
+    let status = format_status_line(task, exit_status, outcome, usage, timed_out);
+    println!("{}", theme::plain(&status));
+    Ok((outcome, exit_status))
+}
+
+/// Runs the task given on the command line (`ttr run`) with its dependencies. Returns the exit
+/// code of ttr: the exit code of the task if it failed, 0 otherwise
+fn run_without_selector(
+    tasks: &Group,
+    task: &Task,
+    opts: &Opts,
+    project: &Path,
+    args: &[String],
+    answers: &HashMap<String, String>,
+) -> Result<i32> {
+    let now = Local::now().naive_local();
+    let unavailable = tasks.unavailable_group(&task.path, now)?;
+    if let Some(group) = unavailable.filter(|_| !opts.override_availability) {
+        let available = group.available.as_ref().unwrap();
+        bail!(
+            "Group {} is not available now ({}), use --override to run anyway",
+            group.name,
+            available
+        );
+    }
+    if !secrets::ask(&task.name, &task.secrets)? {
+        bail!("Task {} is cancelled", task.name);
+    }
+    let terminal_size = crossterm::terminal::size().ok();
+    if let (Some(min_size), Some(size)) = (task.min_size, terminal_size) {
+        if task.target == Target::Local && !min_size.fits(size) {
+            let warning = "Warning".stylize().yellow();
+            eprintln!(
+                "{}",
+                theme::plain(format!(
+                    "{}: terminal is {}x{}, task {} needs at least {}",
+                    warning, size.0, size.1, task.name, min_size
+                ))
+            );
+        }
+    }
+    let dependencies = tasks.dependencies(task)?;
+    for (i, dependency) in dependencies.iter().enumerate() {
+        // The task itself is waiting for the dependencies as well
+        report_status(opts, |status| status.queued = dependencies.len() - i);
+        let (outcome, _) = run_in_terminal(dependency, opts, project, &[], &HashMap::new())?;
+        report_status(opts, |status| status.queued = 0);
+        if outcome == Outcome::Failure {
+            bail!(
+                "Task {} is not run, dependency {} failed",
+                task.name,
+                dependency.name
+            );
+        }
+    }
+    if task.is_composite() {
+        let outcome = match task.serial.is_empty() {
+            true => run_parallel(task, tasks, opts, project)?.0,
+            false => {
+                let (outcome, message, _) = run_steps(task, tasks, opts, project)?;
+                println!("{}", theme::plain(&message));
+                outcome
+            }
+        };
+        return Ok(if outcome == Outcome::Failure { 1 } else { 0 });
+    }
+    if task.target != Target::Local {
+        let shell = task_shell(task, opts);
+        let terminal = task.terminal.as_ref().unwrap_or(&opts.terminal);
+        let options = RunOptions::default();
+        start_task(task, &shell, options, terminal, args, answers, None)?;
+        audit(opts, project, task, options, state::now(), 0, None);
+        let started = format!("started in {}", task.target).stylize().green();
+        println!(
+            "{}",
+            theme::plain(format!("Task {} {}", task.name, started))
+        );
+        return Ok(0);
+    }
+    let (outcome, exit_status) = run_in_terminal(task, opts, project, args, answers)?;
+    notify_completion(task, outcome);
+    if outcome != Outcome::Failure {
+        if let Some(open) = task.open_target(answers)? {
+            if !task.confirm_open || fzf::ask(&format!("Open {}?", open))? {
+                if let Err(e) = open_path(&open) {
+                    print_warning(&format!("Unable to open {}", open), &e);
+                }
+            }
+        }
+    }
+    Ok(match outcome {
+        // Task killed by a signal has no exit code
+        Outcome::Failure => exit_status.code().filter(|c| *c != 0).unwrap_or(1),
+        Outcome::Success | Outcome::Warning => 0,
+    })
+}
+
+/// Task running the command given on the command line (`ttr exec`). It has no key path, so
+/// it is recorded in the history, but never offered to be run again
+fn adhoc_task(name: Option<String>, env: &[String], command: &[String]) -> Result<Task> {
+    let cmd = match command {
+        [cmd] => cmd.clone(),
+        args => args
+            .iter()
+            .map(|arg| template::quote(arg))
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    let mut vars = serde_yaml::Mapping::new();
+    for var in env {
+        let Some((name, value)) = var.split_once('=') else {
+            bail!("Invalid variable {}, expected NAME=VALUE", var);
+        };
+        vars.insert(name.into(), value.into());
+    }
+    let mut task = serde_yaml::Mapping::new();
+    task.insert("name".into(), name.unwrap_or_else(|| cmd.clone()).into());
+    task.insert("key".into(), "_".into());
+    task.insert("cmd".into(), cmd.into());
+    task.insert("env".into(), vars.into());
+    Ok(serde_yaml::from_value(task.into())?)
+}
+
+/// Runs the task chosen with the external finder (`--selector fzf`). In loop mode the finder
+/// is shown again after the task completes until it is closed
+fn run_with_finder(opts: &Opts, project: &Path) -> Result<()> {
+    let tasks = load_tasks(!opts.no_presets, require_trust)?;
+    while let Some(task) = fzf::select(opts.selector, &tasks)? {
+        let dangerous = format!("Task {} is dangerous. Run it anyway?", task.name);
+        let question = task.confirmation().or(task.dangerous.then_some(dangerous));
+        if let Some(question) = question {
+            if !fzf::ask(&question)? {
+                continue;
+            }
+        }
+        let code = match is_up_to_date(task, project) {
+            true => {
+                let up_to_date = "is up to date".stylize().green();
+                println!(
+                    "{}",
+                    theme::plain(format!("Task {} {}", task.name, up_to_date))
+                );
+                0
+            }
+            false => {
+                let answers = arg_values(task, &[])?;
+                run_without_selector(&tasks, task, opts, project, &opts.args, &answers)?
+            }
+        };
+        if !opts.loop_mode {
+            match code {
+                0 => break,
+                code => std::process::exit(code),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Values of the task arguments given as `NAME=VALUE`. Arguments not given take their default
+/// values
+fn arg_values(task: &Task, values: &[String]) -> Result<HashMap<String, String>> {
+    let mut answers = HashMap::new();
+    for value in values {
+        let Some((name, value)) = value.split_once('=') else {
+            bail!("Invalid argument {}, expected NAME=VALUE", value);
+        };
+        let Some(arg) = task.args.iter().find(|arg| arg.name == name) else {
+            bail!("Task {} has no argument {}", task.name, name);
+        };
+        if !arg.choices.is_empty() && !arg.choices.iter().any(|c| c == value) {
+            bail!(
+                "Invalid value of argument {}, expected one of: {}",
+                name,
+                arg.choices.join(", ")
+            );
+        }
+        answers.insert(name.to_string(), value.to_string());
+    }
+    for arg in &task.args {
+        if answers.contains_key(&arg.name) {
+            continue;
+        }
+        let Some(default) = &arg.default else {
+            bail!(
+                "Argument {} of task {} has no default value, use --arg {}=VALUE",
+                arg.name,
+                task.name,
+                arg.name
+            );
+        };
+        answers.insert(arg.name.clone(), default.clone());
+    }
+    Ok(answers)
+}
+
+/// Runs the tasks of the parallel task at the same time. Returns the outcome and the status line
+fn run_parallel(
+    task: &Task,
+    root: &Group,
+    opts: &Opts,
+    project: &Path,
+) -> Result<(Outcome, String)> {
+    let options = RunOptions::default();
+    let mut invocations = vec![];
+    for parallel in root.composite_tasks(task)? {
+        let shell = task_shell(parallel, opts);
+        let invocation = Invocation::new(parallel, &shell, options, &[], &HashMap::new())?;
+        approve(parallel, &shell)?;
+        invocations.push((parallel, invocation));
+    }
+    let names = invocations.iter().map(|(t, _)| t.name.as_str());
+    let names = names.collect::<Vec<_>>().join(", ");
+    println!("{} {}", "Running".stylize().bold(), names);
+    let started_at = state::now();
+    let start = Instant::now();
+    let statuses = parallel::run(&invocations)?;
+    let outcome = statuses
+        .iter()
+        .map(|(parallel, status)| parallel.outcome(*status))
+        .max()
+        .unwrap_or(Outcome::Success);
+    info!(task = task.name, ?outcome, "parallel tasks exited");
+    let exit_code = statuses
+        .iter()
+        .find(|(parallel, status)| parallel.outcome(*status) == outcome)
+        .and_then(|(_, status)| status.code());
+    let record = RunRecord {
+        path: task.path.clone(),
+        name: task.name.clone(),
+        started_at,
+        duration_ms: start.elapsed().as_millis() as u64,
+        exit_code,
+        outcome: Some(outcome),
+        usage: None,
+        timed_out: false,
+    };
+    save_run(opts, project, task, options, record);
+    let unsuccessful = statuses
+        .iter()
+        .filter(|(parallel, status)| parallel.outcome(*status) != Outcome::Success)
+        .map(|(parallel, status)| format!("{}: {}", parallel.name, status))
+        .collect::<Vec<_>>();
+    let message = format_composite_status_line(task, outcome, &unsuccessful);
+    println!("{}", theme::plain(&message));
+    notify_completion(task, outcome);
+    Ok((outcome, message))
+}
+
+/// Step of the serial task with its outcome and details (eg. exit status). `None` if the step
+/// is not run
+type Step<'a> = (&'a Task, Option<(Outcome, String)>);
+
+/// Runs the serial task asking user what to do next if it failed. Returns the status line and
+/// the action chosen on the confirmation screen
+fn run_serial(
+    ui: &mut Ui,
+    task: &Task,
+    root: &Group,
+    opts: &Opts,
+    project: &Path,
+) -> Result<(String, NextAction)> {
+    loop {
+        let (outcome, message, steps) = run_steps(task, root, opts, project)?;
+        if outcome == Outcome::Success && !task.confirm && !opts.confirm {
+            println!("{}", theme::plain(&message));
+            return Ok((message, NextAction::Continue));
+        }
+        match confirm_steps(ui, outcome, &steps)? {
+            NextAction::RepeatTask => continue,
+            next => return Ok((message, next)),
+        }
+    }
+}
+
+/// Runs the tasks of the serial task one after another stopping on the first failure. Returns
+/// the outcome, the status line and the steps
+fn run_steps<'a>(
+    task: &Task,
+    root: &'a Group,
+    opts: &Opts,
+    project: &Path,
+) -> Result<(Outcome, String, Vec<Step<'a>>)> {
+    let mut steps: Vec<Step> = root
+        .composite_tasks(task)?
+        .into_iter()
+        .map(|step| (step, None))
+        .collect();
+    let started_at = state::now();
+    let start = Instant::now();
+    let mut exit_code = None;
+    let count = steps.len();
+    for (i, (step, result)) in steps.iter_mut().enumerate() {
+        report_status(opts, |status| status.queued = count - i - 1);
+        let (outcome, details) = match run_in_terminal(step, opts, project, &[], &HashMap::new()) {
+            Ok((outcome, exit_status)) => {
+                exit_code = exit_status.code();
+                (outcome, exit_status.to_string())
+            }
+            Err(e) => {
+                warn!(task = step.name, "unable to start task: {:#}", e);
+                let error = "Unable to start task".with(theme::current().error());
+                eprintln!("{}", theme::plain(format!("{}: {:#}", error, e)));
+                exit_code = None;
+                (Outcome::Failure, "failed to start".to_string())
+            }
+        };
+        *result = Some((outcome, details));
+        if outcome == Outcome::Failure {
+            break;
+        }
+    }
+    report_status(opts, |status| status.queued = 0);
+    let outcome = steps
+        .iter()
+        .filter_map(|(_, result)| result.as_ref().map(|(outcome, _)| *outcome))
+        .max()
+        .unwrap_or(Outcome::Success);
+    info!(task = task.name, ?outcome, "serial tasks exited");
+    let record = RunRecord {
+        path: task.path.clone(),
+        name: task.name.clone(),
+        started_at,
+        duration_ms: start.elapsed().as_millis() as u64,
+        exit_code,
+        outcome: Some(outcome),
+        usage: None,
+        timed_out: false,
+    };
+    save_run(opts, project, task, RunOptions::default(), record);
+    let unsuccessful = steps
+        .iter()
+        .filter_map(|(step, result)| match result {
+            Some((outcome, details)) if *outcome != Outcome::Success => {
+                Some(format!("{}: {}", step.name, details))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let message = format_composite_status_line(task, outcome, &unsuccessful);
+    notify_completion(task, outcome);
+    Ok((outcome, message, steps))
+}
+
+/// Shows the status of every step of the serial task and asks user what to do next
+fn confirm_steps(ui: &mut Ui, outcome: Outcome, steps: &[Step]) -> Result<NextAction> {
+    let mut frame = String::new();
+    writeln!(frame)?;
+    let prefix = "   ";
+    let status = match outcome {
+        Outcome::Success => "completed".stylize().green().bold(),
+        Outcome::Warning => "completed with warnings".stylize().yellow().bold(),
+        Outcome::Failure => "failed".stylize().red().bold(),
+    };
+    writeln!(frame, "{}Task {}", prefix, status)?;
+    writeln!(frame)?;
+    let width = steps
+        .iter()
+        .map(|(step, _)| step.name.chars().count())
+        .max()
+        .unwrap_or_default();
+    for (step, result) in steps {
+        let (marker, status) = match result {
+            Some((Outcome::Success, _)) => ("✓".stylize().green(), "completed".to_string()),
+            Some((Outcome::Warning, details)) => (
+                "!".stylize().yellow(),
+                format!("completed with warnings ({})", details),
+            ),
+            Some((Outcome::Failure, details)) => {
+                ("✗".stylize().red(), format!("failed ({})", details))
+            }
+            None => ("·".stylize().grey(), "not run".to_string()),
+        };
+        writeln!(
+            frame,
+            "{}  {} {:width$}  {}",
+            prefix,
+            marker,
+            step.name,
+            status,
+            width = width
+        )?;
+    }
+    writeln!(frame)?;
+    writeln!(
+        frame,
+        "{}Press {} to continue. {}epeat or {}elect another task...",
+        prefix,
+        "Enter".stylize().yellow().bold(),
+        "r".stylize().yellow().bold(),
+        "s".stylize().yellow().bold(),
+    )?;
+    ui.print(&frame)?;
+    loop {
+        match ui.next_key_event()?.code {
+            KeyCode::Enter => break Ok(NextAction::Continue),
+            KeyCode::Char('q') | KeyCode::Esc => break Ok(NextAction::Exit),
+            KeyCode::Char('r') => break Ok(NextAction::RepeatTask),
+            KeyCode::Char('s') => break Ok(NextAction::SelectTask),
+            _ => continue,
+        }
+    }
+}
+
+fn format_composite_status_line(task: &Task, outcome: Outcome, unsuccessful: &[String]) -> String {
+    match outcome {
+        Outcome::Success => format!("Task {} {}", task.name, "completed".stylize().green()),
+        Outcome::Warning => format!(
+            "Task {} {} ({})",
+            task.name,
+            "completed with warnings".stylize().yellow(),
+            unsuccessful.join(", ")
+        ),
+        Outcome::Failure => format!(
+            "Task {} {} ({})",
+            task.name,
+            "failed".stylize().red(),
+            unsuccessful.join(", ")
+        ),
+    }
+}
+
+/// Writes the run of the task to the audit log and the history
+fn save_run(opts: &Opts, project: &Path, task: &Task, options: RunOptions, record: RunRecord) {
+    audit(
+        opts,
+        project,
+        task,
+        options,
+        record.started_at,
+        record.duration_ms,
+        record.exit_code,
+    );
+    report_run(opts, &record);
+    if let Err(e) = state::update(project, |state| state.record_run(record)) {
+        warn!("unable to save state: {:#}", e);
+    }
+}
+
+/// Opens the log file the output of the run is appended to: `log` of the task or a timestamped
+/// file in `--log-dir`. Output of full-screen tasks and tasks run elsewhere is not logged
+fn task_log(task: &Task, opts: &Opts) -> Result<Option<File>> {
+    if task.tui || task.target != Target::Local {
+        return Ok(None);
+    }
+    let now = Local::now().naive_local();
+    let path = match (&task.log, &opts.log_dir) {
+        (Some(template), _) => task_working_dir(task)?.join(capture::log_path(template, now)?),
+        (None, Some(dir)) => {
+            let name = task.name.replace(|c: char| !c.is_alphanumeric(), "-");
+            let template = format!("{}-%Y%m%d-%H%M%S.log", name);
+            dir.join(capture::log_path(&template, now)?)
+        }
+        (None, None) => return Ok(None),
+    };
+    debug!(task = task.name, path = %path.display(), "logging output");
+    capture::open_log(&path).map(Some)
+}
+
+/// Working directory of the task
+fn task_working_dir(task: &Task) -> Result<PathBuf> {
+    match &task.working_dir {
+        Some(working_dir) => Ok(working_dir.clone()),
+        None => Ok(current_dir()?),
+    }
+}
+
+/// Returns true if the inputs of the task are not changed since the last successful run
+/// and all of its outputs exist. Task is out of date if its inputs or outputs can't be read
+fn is_up_to_date(task: &Task, project: &Path) -> bool {
+    match check_up_to_date(task, project) {
+        Ok(up_to_date) => up_to_date,
+        Err(e) => {
+            print_warning(
+                &format!("Unable to check if task {} is up to date", task.name),
+                &e,
+            );
+            false
+        }
+    }
+}
+
+fn check_up_to_date(task: &Task, project: &Path) -> Result<bool> {
+    let working_dir = task_working_dir(task)?;
+    let Some(hash) = cache::inputs_hash(task, &working_dir)? else {
+        return Ok(false);
+    };
+    let last_hash = state::read(project)?.inputs_hashes.remove(&task.path);
+    Ok(last_hash.as_ref() == Some(&hash) && cache::outputs_exist(task, &working_dir)?)
+}
+
+/// Shell used to run the task
+fn task_shell(task: &Task, opts: &Opts) -> Shell {
+    // Shell of the user is not necessarily installed in the image
+    let in_container =
+        task.image.is_some() || task.container.is_some() || task.compose_service.is_some();
+    let image_shell = in_container.then(|| Shell::program("sh"));
+    task.shell
+        .clone()
+        .or(image_shell)
+        .or(opts.shell.as_deref().map(Shell::parse))
+        .unwrap_or_else(|| Shell::program(platform::default_shell()))
+}
+
+/// Fully resolved command line of the task
+struct Invocation {
+    program: String,
+    args: Vec<String>,
+    working_dir: PathBuf,
+    /// Environment variables set for the process on top of the inherited ones
+    env: Vec<(String, String)>,
+    /// Process doesn't inherit the environment of ttr, only `env` is set
+    clear_env: bool,
+    /// Secrets set for the process. Unlike `env` they are never put on the command line
+    secrets: Vec<(String, String)>,
+    /// Script files of the task, removed when the invocation is dropped
+    scripts: temp::Files,
+}
+
+impl Invocation {
+    /// Prepares the task to be run using the given shell. `args` are substituted instead of
+    /// `{{args}}` in the command. Values of the task arguments are taken from `answers` falling
+    /// back to the defaults
+    fn new(
+        task: &Task,
+        shell: &Shell,
+        options: RunOptions,
+        args: &[String],
+        answers: &HashMap<String, String>,
+    ) -> Result<Self> {
+        Self::in_dir(task, shell, options, args, answers, task_working_dir(task)?)
+    }
+
+    /// Prepares the task to be run in the given directory instead of its working directory
+    fn in_dir(
+        task: &Task,
+        shell: &Shell,
+        options: RunOptions,
+        args: &[String],
+        answers: &HashMap<String, String>,
+        working_dir: PathBuf,
+    ) -> Result<Self> {
+        version::require(task.requires_ttr.as_deref())?;
+        if !working_dir.is_dir() {
+            bail!("Working directory doesn't exist: {}", working_dir.display());
+        }
+        let mut context = template::Context::default();
+        context.set("cwd", working_dir.to_string_lossy());
+        let devcontainer = Environment::current().devcontainer;
+        context.set("devcontainer", devcontainer.to_string());
+        let (cmd, verbose_flags) = match (&task.verbose_cmd, &task.verbose_flags) {
+            (Some(verbose_cmd), _) if options.verbose => (verbose_cmd.as_str(), ""),
+            (_, Some(verbose_flags)) if options.verbose => (task.command(), verbose_flags.as_str()),
+            _ => (task.command(), ""),
+        };
+        // Verbose command replaces the script as well
+        let is_script = task.script.is_some() && cmd == task.command();
+        context.set("verbose_flags", verbose_flags);
+        let args = args.iter().map(|arg| match task.raw {
+            true => arg.clone(),
+            false => template::quote(arg),
+        });
+        context.set("args", args.collect::<Vec<_>>().join(" "));
+        for arg in &task.args {
+            let Some(value) = answers.get(&arg.name).or(arg.default.as_ref()) else {
+                bail!("No value given for argument {}", arg.name);
+            };
+            match task.raw {
+                true => context.set(&arg.name, value),
+                false => context.set(&arg.name, template::quote(value)),
+            }
+        }
+        let cmd = template::render(cmd, &context)?;
+        // Container doesn't inherit the environment anyway, while the engine needs it
+        let clear_env = task.clear_env == Some(true) && task.image.is_none();
+        let mut env = match clear_env {
+            true => platform::kept_variables(),
+            false => vec![],
+        };
+        if let Some(locale) = &task.locale {
+            // LC_ALL takes precedence over LC_* variables possibly set in the environment
+            env.push(("LANG".to_string(), locale.clone()));
+            env.push(("LC_ALL".to_string(), locale.clone()));
+        }
+        if let Some(tz) = &task.tz {
+            env.push(("TZ".to_string(), tz.clone()));
+        }
+        env.extend(task.env_file.load(&working_dir)?);
+        env.extend(task.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        let secrets = secrets::env(&task.secrets);
+        if let Some(host) = &task.ssh {
+            if is_script && shebang(&cmd).is_some() {
+                bail!("Script with a shebang can not be run on the remote host");
+            }
+            if !secrets.is_empty() {
+                bail!("Secrets can not be passed to the remote host without exposing them");
+            }
+            let remote = Remote {
+                host,
+                dir: task.remote_dir.as_deref(),
+                env: &env,
+            };
+            let (program, args) = remote.wrap(&cmd);
+            return Ok(Self {
+                program,
+                args,
+                working_dir,
+                env: vec![],
+                clear_env: false,
+                secrets,
+                scripts: temp::Files::default(),
+            });
+        }
+        let mut scripts = temp::Files::default();
+        let (program, args) = match (is_script, shebang(&cmd)) {
+            (true, Some(mut interpreter)) => {
+                let program = interpreter.remove(0);
+                let script = write_script(&shell.program, &cmd)?;
+                interpreter.push(script.to_string_lossy().to_string());
+                scripts.push(script);
+                (program, interpreter)
+            }
+            // Shell with the given arguments is run the same way for scripts and commands
+            (true, None) if shell.args.is_empty() => {
+                let script = write_script(&shell.program, &cmd)?;
+                let args = script_args(&shell.program, &script, options.login_shell);
+                scripts.push(script);
+                (shell.program.clone(), args)
+            }
+            _ => shell.command(&cmd, options.login_shell),
+        };
+        if let Some((name, compose)) = task.running_container() {
+            if is_script && shebang(&cmd).is_some() {
+                bail!("Script with a shebang can not be run in a running container");
+            }
+            let exec = Exec {
+                name,
+                compose,
+                dir: task.remote_dir.as_deref(),
+                env: &env,
+                secrets: &secrets,
+            };
+            let (program, args) = shell.command(&cmd, options.login_shell);
+            let (program, args) = exec.wrap(program, args)?;
+            return Ok(Self {
+                program,
+                args,
+                working_dir,
+                env: vec![],
+                clear_env: false,
+                secrets,
+                scripts: temp::Files::default(),
+            });
+        }
+        let (program, args) = match &task.image {
+            Some(image) => {
+                let container = Container {
+                    image,
+                    working_dir: &working_dir,
+                    env: &env,
+                    secrets: &secrets,
+                    files: scripts.paths().to_vec(),
+                    network: task.network,
+                };
+                container.wrap(program, args)?
+            }
+            None => sandbox::wrap(task.network, program, args)?,
+        };
+        let (program, args) = nix_develop(task, program, args);
+        Ok(Self {
+            program,
+            args,
+            working_dir,
+            env,
+            clear_env,
+            secrets,
+            scripts,
+        })
+    }
+
+    /// Program and its arguments. Environment variables are set using `env` program, so the
+    /// command line can be passed to other programs (terminal emulators, multiplexers etc.).
+    /// Secrets are not included
+    fn command_line(&self) -> impl Iterator<Item = String> + '_ {
+        let env = match (self.clear_env, self.env.is_empty()) {
+            (true, _) => vec!["env".to_string(), "-i".to_string()],
+            (false, false) => vec!["env".to_string()],
+            (false, true) => vec![],
+        };
+        let vars = self.env.iter().map(|(k, v)| format!("{}={}", k, v));
+        env.into_iter()
+            .chain(vars)
+            .chain(std::iter::once(self.program.clone()))
+            .chain(self.args.iter().cloned())
+    }
+}
+
+/// Starts the task along with the guard removing its scripts when the task exits. Returns `None`
+/// if the task was launched on an external target
+fn start_task(
+    task: &Task,
+    shell: &Shell,
+    options: RunOptions,
+    terminal: &str,
+    args: &[String],
+    answers: &HashMap<String, String>,
+    sandbox: Option<&Sandbox>,
+) -> Result<Option<(Child, temp::Files)>> {
+    let mut invocation = task_invocation(task, shell, options, args, answers, sandbox)?;
+    approve(task, shell)?;
+    warn_deprecated(task);
+    print_context(task, &invocation);
+    if task.target != Target::Local {
+        target::launch(task, &invocation, terminal)?;
+        // Launched task may read the script after ttr exits
+        invocation.scripts.keep();
+        return Ok(None);
+    }
+    let capture = task.capture || options.quiet || options.log;
+    let child = create_process(task, &invocation, capture, true)?;
+    Ok(Some((child, std::mem::take(&mut invocation.scripts))))
+}
+
+/// Working directory and environment overrides of the task, so it's clear where the relative
+/// `working_dir` points to
+fn context_lines(task: &Task, working_dir: &Path) -> Vec<String> {
+    let mut lines = vec![format!("working dir: {}", working_dir.display())];
+    if !task.env.is_empty() {
+        let env = task
+            .env
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value));
+        lines.push(format!("env: {}", env.collect::<Vec<_>>().join(" ")));
+    }
+    if !task.env_file.paths().is_empty() {
+        let files = task
+            .env_file
+            .paths()
+            .iter()
+            .map(|p| p.display().to_string());
+        lines.push(format!(
+            "env files: {}",
+            files.collect::<Vec<_>>().join(", ")
+        ));
+    }
+    if let Some(host) = &task.ssh {
+        let dir = task.remote_dir.as_deref().unwrap_or("~");
+        lines.push(format!("remote: {}:{}", host, dir));
+    }
+    if let Some((name, compose)) = task.running_container() {
+        let kind = if compose {
+            "compose service"
+        } else {
+            "container"
+        };
+        match &task.remote_dir {
+            Some(dir) => lines.push(format!("{}: {}:{}", kind, name, dir)),
+            None => lines.push(format!("{}: {}", kind, name)),
+        }
+    }
+    if !task.secrets.is_empty() {
+        lines.push(format!("secrets: {}", task.secrets.join(", ")));
+    }
+    if task.clear_env == Some(true) {
+        lines.push("environment is cleared".to_string());
+    }
+    lines
+}
+
+/// Reports the error which doesn't prevent ttr from going on
+fn print_warning(message: &str, e: &anyhow::Error) {
+    warn!("{}: {:#}", message, e);
+    let message = message.stylize().yellow();
+    eprintln!("{}", theme::plain(format!("{}: {:#}", message, e)));
+}
+
+/// Prints the banner asking to move off the deprecated task before it is run
+fn warn_deprecated(task: &Task) {
+    if let Some(message) = &task.deprecated {
+        let warning = format!("Task {} is deprecated", task.name).stylize();
+        println!(
+            "{}",
+            theme::plain(format!("{}: {}", warning.yellow().bold(), message))
+        );
+    }
+}
+
+/// Prints the context of the task about to be run, if it asks for it (`show_context: true`)
+fn print_context(task: &Task, invocation: &Invocation) {
+    if task.show_context != Some(true) {
+        return;
+    }
+    for line in context_lines(task, &invocation.working_dir) {
+        println!("{}", theme::plain(format!("  {}", line.stylize().grey())));
+    }
+}
+
+/// Invocation of the task, run in the sandbox if given
+fn task_invocation(
+    task: &Task,
+    shell: &Shell,
+    options: RunOptions,
+    args: &[String],
+    answers: &HashMap<String, String>,
+    sandbox: Option<&Sandbox>,
+) -> Result<Invocation> {
+    match sandbox {
+        Some(sandbox) => {
+            let dir = sandbox.dir().to_path_buf();
+            let invocation = Invocation::in_dir(task, shell, options, args, answers, dir)?;
+            Ok(sandbox.wrap(invocation))
+        }
+        None => Invocation::new(task, shell, options, args, answers),
+    }
+}
+
+/// Runs the approval command of the task. The task can be run only if it exits successfully
+fn approve(task: &Task, shell: &Shell) -> Result<()> {
+    let Some(approval_cmd) = &task.approval_cmd else {
+        return Ok(());
+    };
+    info!(task = task.name, approval_cmd, "asking for approval");
+    let (program, args) = shell.command(approval_cmd, false);
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(task_working_dir(task)?)
+        .env("TTR_TASK", &task.name)
+        .env("TTR_TASK_PATH", &task.path)
+        .output()
+        .context("Unable to run approval command")?;
+    if !output.status.success() {
+        let reason = [output.stdout, output.stderr]
+            .iter()
+            .map(|o| String::from_utf8_lossy(o).trim().to_string())
+            .filter(|o| !o.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        match reason.is_empty() {
+            true => bail!("Task is not approved ({})", output.status),
+            false => bail!("Task is not approved: {}", reason),
+        }
+    }
+    Ok(())
+}
+
+/// Spawns the task process. Process run in the `foreground` gets its own process group and the
+/// terminal until the task exits, the others share the group of ttr
+fn create_process(
+    task: &Task,
+    invocation: &Invocation,
+    capture: bool,
+    foreground: bool,
+) -> Result<Child> {
+    let output = || {
+        if capture {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        }
+    };
+    let Invocation {
+        program,
+        args,
+        working_dir,
+        env,
+        clear_env,
+        secrets,
+        ..
+    } = invocation;
+    info!(task = task.name, program, ?args, ?env, working_dir = %working_dir.display(), "spawning task");
+    let mut command = Command::new(program);
+    #[cfg(windows)]
+    {
+        windows::configure(&mut command);
+        windows::add_args(&mut command, program, args);
+    }
+    #[cfg(not(windows))]
+    command.args(args);
+    #[cfg(unix)]
+    if foreground {
+        unix::configure(&mut command);
+    }
+    if *clear_env {
+        command.env_clear();
+    }
+    command.envs(env.iter().chain(secrets).map(|(k, v)| (k, v)));
+    let child = command
+        .current_dir(working_dir)
+        .stdin(Stdio::inherit())
+        .stdout(output())
+        .stderr(output())
+        .spawn()
+        .with_context(|| format!("Unable to spawn process in {}", working_dir.display()))?;
+    debug!(pid = child.id(), "task spawned");
+    #[cfg(unix)]
+    if foreground {
+        unix::task_started(child.id());
+    }
+    #[cfg(windows)]
+    windows::task_started(child.id());
+    Ok(child)
+}
+
+/// Shell running the task commands with the arguments preceding the command (eg. `bash -lc`).
+/// Without arguments they are chosen by the name of the shell
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "ShellSpec")]
+struct Shell {
+    program: String,
+    args: Vec<String>,
+}
+
+/// Shell as written in the config: a command line (`fish -c`) or a list (`[bash, -lc]`)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ShellSpec {
+    Line(String),
+    Argv(Vec<String>),
+}
+
+impl TryFrom<ShellSpec> for Shell {
+    type Error = String;
+
+    fn try_from(spec: ShellSpec) -> std::result::Result<Self, Self::Error> {
+        match spec {
+            ShellSpec::Line(line) if line.trim().is_empty() => Err("shell is empty".to_string()),
+            ShellSpec::Line(line) => Ok(Shell::parse(&line)),
+            ShellSpec::Argv(mut argv) if !argv.is_empty() => Ok(Shell {
+                program: argv.remove(0),
+                args: argv,
+            }),
+            ShellSpec::Argv(_) => Err("shell is empty".to_string()),
+        }
+    }
+}
+
+impl Shell {
+    fn program(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: vec![],
+        }
+    }
+
+    /// Splits the command line of the shell by whitespace unless it is a path of an existing
+    /// file (eg. `C:\Program Files\PowerShell\7\pwsh.exe`)
+    fn parse(line: &str) -> Self {
+        let mut words = line.split_whitespace().map(str::to_string);
+        match (Path::new(line).is_file(), words.next()) {
+            (false, Some(program)) => Self {
+                program,
+                args: words.collect(),
+            },
+            _ => Self::program(line),
+        }
+    }
+
+    /// Program and its arguments running the command. Login shell is only used when the
+    /// arguments of the shell are not given
+    fn command(&self, cmd: &str, login_shell: bool) -> (String, Vec<String>) {
+        let args = match self.args.is_empty() {
+            true => shell_args(&self.program, cmd, login_shell),
+            false => self.args.iter().cloned().chain([cmd.to_string()]).collect(),
+        };
+        (self.program.clone(), args)
+    }
+}
+
+/// Arguments to run the command in the shell. Besides POSIX shells `cmd.exe` and PowerShell
+/// are supported, so Windows tools can be run from WSL
+fn shell_args(shell: &str, cmd: &str, login_shell: bool) -> Vec<String> {
+    let args = match shell_name(shell).as_deref() {
+        Some("cmd") => vec!["/C", cmd],
+        Some("powershell" | "pwsh") if login_shell => vec!["-Command", cmd],
+        Some("powershell" | "pwsh") => vec!["-NoProfile", "-Command", cmd],
+        _ if login_shell => vec!["-lic", cmd],
+        _ => return vec!["-c".to_string(), format!("exec {}", cmd)],
+    };
+    args.into_iter().map(str::to_string).collect()
+}
+
+/// Wraps the program, so it is run in the development shell of the flake if the task requires
+/// it. Flake is looked up by nix in the working directory and its parents
+fn nix_develop(task: &Task, program: String, args: Vec<String>) -> (String, Vec<String>) {
+    if !task.nix_shell && task.devshell.is_none() {
+        return (program, args);
+    }
+    let mut nix_args = vec!["develop".to_string()];
+    if let Some(devshell) = &task.devshell {
+        nix_args.push(format!(".#{}", devshell));
+    }
+    nix_args.push("-c".to_string());
+    nix_args.push(program);
+    nix_args.extend(args);
+    ("nix".to_string(), nix_args)
+}
+
+/// Arguments of the shell to run the script file
+fn script_args(shell: &str, script: &Path, login_shell: bool) -> Vec<String> {
+    let script = script.to_string_lossy();
+    let args = match shell_name(shell).as_deref() {
+        Some("cmd") => vec!["/C", script.as_ref()],
+        Some("powershell" | "pwsh") => {
+            vec!["-NoProfile", "-ExecutionPolicy", "Bypass", "-File", &script]
+        }
+        _ if login_shell => vec!["-l", &script],
+        _ => vec![script.as_ref()],
+    };
+    args.into_iter().map(str::to_string).collect()
+}
+
+/// Writes the script to a new file in the private temporary directory of the session. The file
+/// is removed along with the invocation, unless the task is launched on an external target
+fn write_script(shell: &str, script: &str) -> Result<PathBuf> {
+    let extension = match shell_name(shell).as_deref() {
+        _ if shebang(script).is_some() => "script",
+        Some("cmd") => "cmd",
+        Some("powershell" | "pwsh") => "ps1",
+        _ => "sh",
+    };
+    let path = temp::path("script")?.with_extension(extension);
+    temp::create(&path)
+        .and_then(|mut file| file.write_all(script.as_bytes()))
+        .with_context(|| format!("Unable to write script: {}", path.display()))?;
+    Ok(path)
+}
+
+/// Interpreter and its arguments from the shebang line of the script (eg. `#!/usr/bin/env
+/// python3`). `env` is skipped, so the interpreter is looked up in PATH on Windows as well
+fn shebang(script: &str) -> Option<Vec<String>> {
+    let line = script.lines().next()?.strip_prefix("#!")?;
+    let mut args = line.split_whitespace().collect::<Vec<_>>();
+    if args.first().and_then(|p| Path::new(p).file_stem()) == Some(OsStr::new("env")) {
+        args.remove(0);
+        if args.first() == Some(&"-S") {
+            args.remove(0);
+        }
+    }
+    let args = args.into_iter().map(str::to_string).collect::<Vec<_>>();
+    (!args.is_empty()).then_some(args)
+}
+
+/// Lowercase name of the shell executable without extension (eg. `pwsh`)
+fn shell_name(shell: &str) -> Option<String> {
+    Path::new(shell)
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_lowercase())
+}
+
+/// Opens captured output in the `$PAGER` (`less` by default)
+fn view_output(output: &mut OutputBuffer) -> Result<()> {
+    run_user_program("PAGER", platform::PAGER, output.persist()?)
+}
+
+/// Runs the program set in the environment `variable` (eg. `$EDITOR`) with an argument
+fn run_user_program(variable: &str, default: &str, arg: impl AsRef<OsStr>) -> Result<()> {
+    let program = env::var(variable).unwrap_or(default.to_string());
+    let mut program = program.split_whitespace();
+    let Some(name) = program.next() else {
+        return Ok(());
+    };
+    let status = Command::new(name)
+        .args(program)
+        .arg(arg)
+        .status()
+        .with_context(|| format!("Unable to run {}", name))?;
+    if !status.success() {
+        bail!("{} failed: {}", name, status);
+    }
+    Ok(())
+}
+
+enum DrawItem<'a> {
+    Task(&'a Task),
+    Group(&'a Group),
+}
+
+impl<'a> DrawItem<'a> {
+    fn key(&'a self) -> char {
+        match self {
+            DrawItem::Group(g) => g.key,
+            DrawItem::Task(t) => t.key,
+        }
+    }
+
+    fn name(&'a self) -> &'a str {
+        match self {
+            DrawItem::Group(g) => &g.name,
+            DrawItem::Task(t) => &t.name,
+        }
+    }
+}
+
+/// Task chosen in the selector
+struct Selection<'a> {
+    task: &'a Task,
+    /// run the task even if it is up to date
+    force: bool,
+    /// time it took to type the key path of the task, if it was typed from the root group
+    typed_ms: Option<u64>,
+}
+
+/// Maximum time of typing the key path recorded in the training statistics
+const MAX_TYPING_MS: u64 = 10_000;
+
+/// What the user has chosen in the selector
+enum Choice<'a> {
+    Task(Selection<'a>),
+    /// Action of the palette handled outside of the selector
+    Action(Action),
+    Quit,
+}
+
+/// Presents a user with the list of tasks and reads the selected task. Selector is opened at the
+/// group with `position` key path, which is updated to the group the user has left it in. Task
+/// with `highlight` key path is underlined
+fn select_task<'a>(
+    ui: &mut Ui,
+    group: &'a Group,
+    state: &ProjectState,
+    status_line: &Option<String>,
+    position: &mut String,
+    highlight: Option<&str>,
+    modes: Modes,
+) -> Result<Choice<'a>> {
+    let mut stack = vec![group];
+    // Groups which are no longer in the config are skipped
+    for key in position.chars() {
+        match stack.last().unwrap().groups.iter().find(|g| g.key == key) {
+            Some(group) => stack.push(group),
+            None => break,
+        }
+    }
+    let _alt = ui.alternate_screen();
+    let opened = Instant::now();
+    let from_root = stack.len() == 1;
+    // Item of the current group focused with the arrow keys (or the highlighted task)
+    let mut focus = highlight
+        .filter(|path| path.strip_suffix(|_| true) == Some(position.as_str()))
+        .and_then(|path| path.chars().last());
+    let mut browsed = false;
+    // `?` was pressed without a focused item, the next key chooses the item to describe
+    let mut describing = false;
+
+    // Rendered selector is cached and redrawn only after navigation or terminal resize
+    let mut layout = String::new();
+    let mut layout_width = None;
+    let mut frame = String::new();
+    let mut error: Option<SelectError> = None;
+    let Modes {
+        mut force,
+        mut preview,
+        ..
+    } = modes;
+    loop {
+        *position = stack[1..].iter().map(|g| g.key).collect();
+        let (width, _) = ui.size()?;
+        // Enlarged characters take two columns each
+        let layout_columns = if modes.present { width / 2 } else { width };
+        if layout_width != Some(width) {
+            layout.clear();
+            let modes = Modes {
+                force,
+                preview,
+                ..modes
+            };
+            draw_selector(
+                &mut layout,
+                &stack,
+                focus,
+                state,
+                status_line,
+                modes,
+                layout_columns,
+            )?;
+            layout_width = Some(width);
+        }
+        frame.clear();
+        frame.push_str(&layout);
+        if let Some(e) = error.take() {
+            writeln!(frame)?;
+            writeln!(frame, "   {}", style(e).with(theme::current().error()))?;
+            writeln!(frame)?;
+        }
+        if describing {
+            writeln!(frame)?;
+            let hint = "Press the key of the task or group to describe";
+            writeln!(frame, "   {}", hint.stylize().cyan())?;
+        }
+        match modes.present {
+            true => ui.draw(&ui::enlarge(&frame))?,
+            false => ui.draw(&frame)?,
+        }
+
+        let current_group = *stack.last().unwrap();
+        let KeyEvent {
+            code, modifiers, ..
+        } = match ui.next_input()? {
+            Input::Key(key) => key,
+            // The grid is laid out again for the new width
+            Input::Resize(..) => continue,
+        };
+        // Keys typed in a non-Latin layout are matched to the Latin keys at the same position
+        let code = match code {
+            KeyCode::Char(ch) if group.layout_fallback && !current_group.has_key(ch) => {
+                layout::to_latin(ch).map_or(code, KeyCode::Char)
+            }
+            _ => code,
+        };
+        // Arrows (and hjkl while an item is focused) move the focus over the grid of the items
+        let direction = match code {
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right => Some(code),
+            KeyCode::Char(ch) if focus.is_some() && !current_group.has_key(ch) => match ch {
+                'h' => Some(KeyCode::Left),
+                'j' => Some(KeyCode::Down),
+                'k' => Some(KeyCode::Up),
+                'l' => Some(KeyCode::Right),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(direction) = direction {
+            let keys = current_group.item_keys();
+            let rows = grid_rows(keys.len(), modes, layout_columns);
+            let current = focus.and_then(|key| keys.iter().position(|k| *k == key));
+            let next = match current {
+                Some(i) => move_focus(i, direction, rows, keys.len()),
+                None => 0,
+            };
+            focus = keys.get(next).copied();
+            browsed = true;
+            layout_width = None;
+            continue;
+        }
+        // Enter chooses the focused item as if its key was pressed
+        let code = match (code, focus) {
+            (KeyCode::Enter, Some(key)) => KeyCode::Char(key),
+            _ => code,
+        };
+        let describe = match code {
+            KeyCode::Char('?') if !describing && !current_group.has_key('?') => match focus {
+                Some(key) => Some(key),
+                None => {
+                    describing = true;
+                    continue;
+                }
+            },
+            KeyCode::Char(ch) if describing => Some(ch),
+            _ => None,
+        };
+        describing = false;
+        if let Some(key) = describe {
+            layout_width = None;
+            if let Some(task) = current_group.tasks.iter().find(|t| t.key == key) {
+                if preview_screen(ui, task, width)? {
+                    return Ok(Choice::Task(Selection {
+                        task,
+                        force,
+                        typed_ms: None,
+                    }));
+                }
+            } else if let Some(group) = current_group.groups.iter().find(|g| g.key == key) {
+                if group_screen(ui, group)? {
+                    stack.push(group);
+                    focus = None;
+                }
+            } else {
+                error = Some(SelectError::NoTask(key));
+            }
+            continue;
+        }
+        // Keys of the selector actions can be used as task keys unless they are essential
+        let action = match code {
+            KeyCode::Char('p') if modifiers == KeyModifiers::CONTROL => {
+                layout_width = None;
+                match palette::select(ui)? {
+                    Some(action) => action,
+                    None => continue,
+                }
+            }
+            KeyCode::Char('q') => Action::Quit,
+            KeyCode::Char('!') => Action::ToggleForce,
+            KeyCode::Tab => Action::TogglePreview,
+            KeyCode::Char('/') if !current_group.has_key('/') => Action::Search,
+            KeyCode::Char('.') if !current_group.has_key('.') => Action::RepeatLast,
+            KeyCode::Char('h') if !current_group.has_key('h') => Action::History,
+            KeyCode::Char('c') if modifiers == KeyModifiers::CONTROL => return Ok(Choice::Quit),
+            KeyCode::Char(' ') => {
+                error = Some(SelectError::Whitespace);
+                continue;
+            }
+            KeyCode::Backspace | KeyCode::Esc if stack.len() <= 1 => {
+                error = Some(SelectError::Root);
+                continue;
+            }
+            KeyCode::Backspace | KeyCode::Esc => {
+                // Group the selector returns from is focused
+                focus = stack.pop().map(|group| group.key).filter(|_| browsed);
+                layout_width = None;
+                continue;
+            }
+            KeyCode::Char(ch) => {
+                let task = current_group.tasks.iter().find(|t| t.key == ch);
+                if let Some(task) = task {
+                    if preview && !preview_screen(ui, task, width)? {
+                        layout_width = None;
+                        continue;
+                    }
+                    // Time spent in the preview or browsing is not typing
+                    let typed = from_root && !preview && !browsed;
+                    let typed_ms = typed.then(|| opened.elapsed().as_millis());
+                    let typed_ms = typed_ms.map(|ms| ms as u64);
+                    return Ok(Choice::Task(Selection {
+                        task,
+                        force,
+                        typed_ms,
+                    }));
+                }
+                let next_group = current_group.groups.iter().find(|g| g.key == ch);
+                if let Some(next_group) = next_group {
+                    stack.push(next_group);
+                    focus = None;
+                    layout_width = None;
+                    continue;
+                }
+                error = Some(SelectError::NoTask(ch));
+                continue;
+            }
+            _ => {
+                error = Some(SelectError::NotCharacter);
+                continue;
+            }
+        };
+        match action {
+            Action::Quit => return Ok(Choice::Quit),
+            Action::ToggleForce => force = !force,
+            Action::TogglePreview => preview = !preview,
+            Action::Search => {
+                if let Some(task) = search::select(ui, group)? {
+                    if !preview || preview_screen(ui, task, width)? {
+                        return Ok(Choice::Task(Selection {
+                            task,
+                            force,
+                            typed_ms: None,
+                        }));
+                    }
+                }
+            }
+            Action::RepeatLast => match history::recent(state, group, 1).first() {
+                Some((task, _)) => {
+                    return Ok(Choice::Task(Selection {
+                        task,
+                        force,
+                        typed_ms: None,
+                    }))
+                }
+                None => error = Some(SelectError::NoHistory),
+            },
+            Action::History => {
+                if let Some(task) = history::select(ui, state, group)? {
+                    return Ok(Choice::Task(Selection {
+                        task,
+                        force,
+                        typed_ms: None,
+                    }));
+                }
+            }
+            Action::ToggleLoop
+            | Action::ToggleClear
+            | Action::ToggleConfirm
+            | Action::ToggleTraining
+            | Action::ReloadConfig
+            | Action::OpenConfig => return Ok(Choice::Action(action)),
+        }
+        layout_width = None;
+    }
+}
+
+/// Modes shown in the header of the selector. Force and preview affect the selected task only,
+/// the rest are the modes of the session toggled from the palette
+#[derive(Clone, Copy, Default)]
+struct Modes {
+    force: bool,
+    preview: bool,
+    loop_mode: bool,
+    clear: bool,
+    confirm: bool,
+    training: bool,
+    present: bool,
+}
+
+/// Shows the details of the task before it is run. Returns true if the task should be run
+fn preview_screen(ui: &mut Ui, task: &Task, width: u16) -> Result<bool> {
+    let mut frame = String::new();
+    writeln!(frame)?;
+    let prefix = "   ";
+    writeln!(frame, "{}{}", prefix, task.name.as_str().stylize().bold())?;
+    if let Some(description) = &task.description {
+        for line in description.lines() {
+            writeln!(frame, "{}{}", prefix, line)?;
+        }
+    }
+    if let Some(message) = &task.deprecated {
+        let deprecated = "deprecated:".stylize().yellow();
+        writeln!(frame, "{}{} {}", prefix, deprecated, message)?;
+    }
+    writeln!(frame)?;
+    for line in context_lines(task, &task_working_dir(task)?) {
+        writeln!(frame, "{}{}", prefix, line.stylize().grey())?;
+    }
+    if task.target != Target::Local {
+        writeln!(
+            frame,
+            "{}{}",
+            prefix,
+            format!("run in: {}", task.target).grey()
+        )?;
+    }
+    writeln!(frame)?;
+    // Line numbers are shown only for multi-line commands
+    let cmd = task.command().trim_end();
+    let numbered = cmd.lines().count() > 1;
+    let indent = if numbered { 4 } else { 0 };
+    let width = (width as usize).saturating_sub(prefix.len() + 4 + indent);
+    for (i, rows) in highlight::highlight(cmd, width).iter().enumerate() {
+        for (j, row) in rows.iter().enumerate() {
+            // Wrapped rows are indented
+            let wrap = if j > 0 { "  " } else { "" };
+            match numbered && j == 0 {
+                true => write!(frame, "{}  {:>3} ", prefix, style(i + 1).grey())?,
+                false => write!(frame, "{}  {:indent$}", prefix, "", indent = indent)?,
+            }
+            writeln!(frame, "{}{}", wrap, row)?;
+        }
+    }
+    writeln!(frame)?;
+    writeln!(
+        frame,
+        "{}Press {} to run the task or {} to return...",
+        prefix,
+        "Enter".stylize().yellow().bold(),
+        "Esc".stylize().yellow().bold(),
+    )?;
+    ui.draw(&frame)?;
+
+    loop {
+        match ui.next_key_event()?.code {
+            KeyCode::Enter => break Ok(true),
+            KeyCode::Esc | KeyCode::Backspace => break Ok(false),
+            _ => continue,
+        }
+    }
+}
+
+/// Shows the description and the contents of the group. Returns true if the group should be
+/// opened
+fn group_screen(ui: &mut Ui, group: &Group) -> Result<bool> {
+    let mut frame = String::new();
+    writeln!(frame)?;
+    let prefix = "   ";
+    writeln!(frame, "{}{}", prefix, group.name.as_str().stylize().bold())?;
+    if let Some(description) = &group.description {
+        for line in description.lines() {
+            writeln!(frame, "{}{}", prefix, line)?;
+        }
+    }
+    writeln!(frame)?;
+    let width = group.groups.iter().map(|g| g.name.chars().count() + 1);
+    let width = width.chain(group.tasks.iter().map(|t| t.name.chars().count()));
+    let width = width.max().unwrap_or_default();
+    for child in &group.groups {
+        let description = child.description.as_deref().unwrap_or_default();
+        let name = format!("{}/", child.name);
+        writeln!(
+            frame,
+            "{}  {} → {:width$}  {}",
+            prefix,
+            child
+                .key
+                .stylize()
+                .with(theme::current().group_key())
+                .bold(),
+            name,
+            description.lines().next().unwrap_or_default().grey(),
+        )?;
+    }
+    for task in &group.tasks {
+        let description = task.description.as_deref().unwrap_or_default();
+        writeln!(
+            frame,
+            "{}  {} → {:width$}  {}",
+            prefix,
+            task.key.stylize().with(theme::current().key()).bold(),
+            task.name,
+            description.lines().next().unwrap_or_default().grey(),
+        )?;
+    }
+    writeln!(frame)?;
+    writeln!(
+        frame,
+        "{}Press {} to open the group or {} to return...",
+        prefix,
+        "Enter".stylize().yellow().bold(),
+        "Esc".stylize().yellow().bold(),
+    )?;
+    ui.draw(&frame)?;
+
+    loop {
+        match ui.next_key_event()?.code {
+            KeyCode::Enter => break Ok(true),
+            KeyCode::Esc | KeyCode::Backspace => break Ok(false),
+            _ => continue,
+        }
+    }
+}
+
+/// Warns that the terminal is smaller than the task needs offering to resize it. Returns false
+/// if the user cancelled the task
+fn size_screen(ui: &mut Ui, task: &Task, min_size: TerminalSize) -> Result<bool> {
+    let _alt = ui.alternate_screen();
+    let prefix = "   ";
+    loop {
+        let (cols, rows) = ui.size()?;
+        if min_size.fits((cols, rows)) {
+            return Ok(true);
+        }
+        let mut frame = String::new();
+        writeln!(frame)?;
+        writeln!(frame, "{}{}", prefix, task.name.as_str().stylize().bold())?;
+        writeln!(frame)?;
+        writeln!(
+            frame,
+            "{}Terminal is {}, the task needs at least {}",
+            prefix,
+            format!("{}x{}", cols, rows).stylize().yellow(),
+            min_size
+        )?;
+        writeln!(frame)?;
+        writeln!(
+            frame,
+            "{}Press {} to run anyway, {} to resize the terminal or {} to cancel...",
+            prefix,
+            "Enter".stylize().yellow().bold(),
+            "r".stylize().yellow().bold(),
+            "Esc".stylize().yellow().bold(),
+        )?;
+        ui.draw(&frame)?;
+        match ui.next_key_event()?.code {
+            KeyCode::Enter => return Ok(true),
+            KeyCode::Char('r') => ui.resize(min_size.cols.max(cols), min_size.rows.max(rows))?,
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            _ => {}
+        }
     }
+}
 
-    let mut tasks = vec![];
-
-    let stop_dir = dirs::home_dir().unwrap_or(PathBuf::from("/"));
-    let start_dir = current_dir()?;
-    let mut dir = Some(start_dir.as_path());
-
-    while let Some(d) = dir {
-        if d == stop_dir {
-            break;
+/// Asks the question before running the task. Returns false if the user declined
+fn confirm_screen(ui: &mut Ui, task: &Task, question: &str) -> Result<bool> {
+    let _alt = ui.alternate_screen();
+    let prefix = "   ";
+    let mut frame = String::new();
+    writeln!(frame)?;
+    writeln!(frame, "{}{}", prefix, task.name.as_str().stylize().bold())?;
+    writeln!(frame)?;
+    let cmd = task.command().lines().next().unwrap_or_default();
+    writeln!(frame, "{}{}", prefix, cmd.stylize().grey())?;
+    if task.show_context == Some(true) {
+        for line in context_lines(task, &task_working_dir(task)?) {
+            writeln!(frame, "{}{}", prefix, line.stylize().grey())?;
         }
-        let config = d.join(TTR_CONFIG);
-        if config.is_file() {
-            tasks.push(tasks_from_file(config)?);
+    }
+    writeln!(frame)?;
+    writeln!(
+        frame,
+        "{}{} [{}/{}]",
+        prefix,
+        question,
+        "y".stylize().yellow().bold(),
+        "N".stylize().yellow().bold()
+    )?;
+    ui.draw(&frame)?;
+    loop {
+        match ui.next_key_event()?.code {
+            KeyCode::Char('y' | 'Y') => return Ok(true),
+            KeyCode::Char('n' | 'N' | 'q') | KeyCode::Enter | KeyCode::Esc => return Ok(false),
+            _ => {}
         }
-        dir = d.parent()
     }
+}
 
-    // ~/.ttr.yaml
-    let home_dir_config = dirs::home_dir()
-        .map(|home| home.join(TTR_CONFIG))
-        .filter(|config| config.is_file());
-    if let Some(config) = home_dir_config {
-        tasks.push(tasks_from_file(config)?);
+/// Counts down before running the dangerous task. Returns false if the user pressed a key to
+/// cancel the task
+fn countdown(ui: &mut Ui, task: &Task) -> Result<bool> {
+    let cmd = task.command().lines().next().unwrap_or_default();
+    for remaining in (1..=COUNTDOWN_SECS).rev() {
+        let frame = format!(
+            "\rRunning `{}` in {}… {}",
+            cmd.bold(),
+            remaining,
+            "(press any key to cancel)".grey()
+        );
+        ui.print(&frame)?;
+        if ui.poll_key_event(Duration::from_secs(1))?.is_some() {
+            ui.print("\n")?;
+            return Ok(false);
+        }
     }
+    ui.print("\n")?;
+    Ok(true)
+}
 
-    // ~/.config/ttr/.ttr.yaml
-    let config_dir_config = dirs::config_dir()
-        .map(|home| home.join("ttr").join(TTR_CONFIG))
-        .filter(|config| config.is_file());
-    if let Some(config) = config_dir_config {
-        tasks.push(tasks_from_file(config)?);
+/// Asks the user for the values of the task arguments. Last answers are offered as defaults.
+/// Returns `None` if the user cancelled
+fn prompt_args(
+    ui: &mut Ui,
+    task: &Task,
+    last_answers: Option<&HashMap<String, String>>,
+) -> Result<Option<HashMap<String, String>>> {
+    let mut answers = HashMap::new();
+    if task.args.is_empty() {
+        return Ok(Some(answers));
     }
+    let _alt = ui.alternate_screen();
+    let prefix = "   ";
+    for arg in &task.args {
+        let default = last_answers
+            .and_then(|answers| answers.get(&arg.name))
+            .or(arg.default.as_ref());
+        let mut input = String::new();
+        let value = loop {
+            let mut frame = String::new();
+            writeln!(frame)?;
+            writeln!(frame, "{}{}", prefix, task.name.as_str().stylize().bold())?;
+            writeln!(frame)?;
+            let prompt = arg.prompt.as_ref().unwrap_or(&arg.name);
+            for (i, choice) in arg.choices.iter().take(9).enumerate() {
+                writeln!(frame, "{}  {} → {}", prefix, style(i + 1).green(), choice)?;
+            }
+            if !arg.choices.is_empty() {
+                writeln!(frame)?;
+            }
+            match default {
+                Some(default) => write!(frame, "{}{} [{}]: ", prefix, prompt, default)?,
+                None => write!(frame, "{}{}: ", prefix, prompt)?,
+            }
+            writeln!(frame, "{}", input.as_str().stylize().bold())?;
+            writeln!(frame)?;
+            writeln!(
+                frame,
+                "{}Press {} to accept or {} to return...",
+                prefix,
+                "Enter".stylize().yellow().bold(),
+                "Esc".stylize().yellow().bold(),
+            )?;
+            ui.draw(&frame)?;
 
-    Ok(tasks)
+            match ui.next_key_event()?.code {
+                KeyCode::Enter if input.is_empty() => {
+                    if let Some(default) = default {
+                        break default.clone();
+                    }
+                }
+                KeyCode::Enter if arg.choices.is_empty() || arg.choices.contains(&input) => {
+                    break input;
+                }
+                KeyCode::Char(ch) if !arg.choices.is_empty() && ch.is_ascii_digit() => {
+                    let choice = ch.to_digit(10).unwrap() as usize;
+                    if let Some(choice) = choice.checked_sub(1).and_then(|i| arg.choices.get(i)) {
+                        break choice.clone();
+                    }
+                }
+                KeyCode::Char(ch) => input.push(ch),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Esc => return Ok(None),
+                _ => {}
+            }
+        };
+        answers.insert(arg.name.clone(), value);
+    }
+    Ok(Some(answers))
 }
 
-fn create_process(task: &Task) -> Result<Child> {
-    let current_dir = current_dir()?;
-    let working_dir = task.working_dir.as_ref().unwrap_or(&current_dir);
-    let child = Command::new("sh")
-        .args(["-c", &format!("exec {}", task.cmd)])
-        .current_dir(working_dir)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()?;
-    Ok(child)
-}
+/// Asks for the secrets of the task not known yet with masked input. Returns false if the user
+/// cancelled
+fn prompt_secrets(ui: &mut Ui, task: &Task) -> Result<bool> {
+    let missing = secrets::missing(&task.secrets);
+    if missing.is_empty() {
+        return Ok(true);
+    }
+    let _alt = ui.alternate_screen();
+    let prefix = "   ";
+    for name in missing {
+        let mut input = String::new();
+        loop {
+            let mut frame = String::new();
+            writeln!(frame)?;
+            writeln!(frame, "{}{}", prefix, task.name.as_str().stylize().bold())?;
+            writeln!(frame)?;
+            let masked = "*".repeat(input.chars().count());
+            writeln!(frame, "{}{}: {}", prefix, name, masked.stylize().bold())?;
+            writeln!(frame)?;
+            writeln!(
+                frame,
+                "{}Press {} to accept or {} to return...",
+                prefix,
+                "Enter".stylize().yellow().bold(),
+                "Esc".stylize().yellow().bold(),
+            )?;
+            ui.draw(&frame)?;
 
-fn next_key_event() -> KeyEvent {
-    let _raw = RawMode::enter();
-    loop {
-        let Ok(true) = event::poll(Duration::from_secs(60)) else {
-            continue;
-        };
-        if let Event::Key(e) = event::read().expect("Unable to read event") {
-            break e;
+            match ui.next_key_event()?.code {
+                KeyCode::Enter if !input.is_empty() => break,
+                KeyCode::Char(ch) => input.push(ch),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Esc => return Ok(false),
+                _ => {}
+            }
         }
+        secrets::set(name, input);
     }
+    Ok(true)
 }
 
-enum DrawItem<'a> {
-    Task(&'a Task),
-    Group(&'a Group),
+/// Reason of the rejected key press in the selector
+enum SelectError {
+    Whitespace,
+    Root,
+    NoTask(char),
+    NotCharacter,
+    NoHistory,
 }
 
-impl<'a> DrawItem<'a> {
-    fn key(&'a self) -> char {
+impl fmt::Display for SelectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            DrawItem::Group(g) => g.key,
-            DrawItem::Task(t) => t.key,
+            SelectError::Whitespace => write!(f, "Whitespace is not allowed"),
+            SelectError::Root => write!(f, "This is the root"),
+            SelectError::NoTask(ch) => write!(f, "No task for key: {}", ch),
+            SelectError::NotCharacter => write!(f, "Please enter character key"),
+            SelectError::NoHistory => write!(f, "No tasks were run yet"),
         }
     }
+}
 
-    fn name(&'a self) -> &str {
-        match self {
-            DrawItem::Group(g) => &g.name,
-            DrawItem::Task(t) => &t.name,
+/// Draws the selector screen for the group on top of the stack
+fn draw_selector(
+    frame: &mut String,
+    stack: &[&Group],
+    focus: Option<char>,
+    state: &ProjectState,
+    status_line: &Option<String>,
+    modes: Modes,
+    width: u16,
+) -> Result<()> {
+    let theme = theme::current();
+    writeln!(frame)?;
+    if let Some(status) = status_line {
+        match theme.status() {
+            Some(color) => writeln!(frame, "  {}", status.as_str().with(color))?,
+            None => writeln!(frame, "  {}", status)?,
         }
+        writeln!(frame)?;
     }
-}
-
-/// Presents a user with the list of tasks and reads the selected task
-fn select_task<'a>(group: &'a Group, status_line: &Option<String>) -> Result<Option<&'a Task>> {
-    let mut stack = vec![group];
-    let _alt = AlternateScreen::enter();
-    let mut stdout = stdout().lock();
-
-    let mut error: Option<String> = None;
-    loop {
-        execute!(stdout, Clear(ClearType::All), cursor::MoveTo(0, 0))?;
-        println!();
-        if let Some(status) = status_line {
-            println!("  {}", status);
-            println!();
+    let current_group = *stack.last().unwrap();
+    if !current_group.is_empty() {
+        let title = "SELECT A TASK";
+        let mut flags = vec![];
+        if modes.force {
+            flags.push("FORCE".stylize().yellow().bold());
         }
-        let current_group = *stack.last().unwrap();
-        if !current_group.is_empty() {
-            print!("  {}", "SELECT A TASK".stylize().grey());
-            if stack.len() > 1 {
-                let breadcrumbs = stack[1..]
-                    .iter()
-                    .map(|g| g.name.as_str())
-                    .collect::<Vec<_>>()
-                    .join(" → ");
-                print!(" → {}", breadcrumbs);
-            }
-            println!();
-            println!();
-
-            draw_tasks(current_group)?;
-        } else {
-            println!("    {}", "No tasks configured".stylize().bold());
-            println!("    Create file {} in the current directory", TTR_CONFIG);
+        if modes.preview {
+            flags.push("PREVIEW".stylize().cyan().bold());
         }
-        println!();
-        println!("    {} → {:12}", "q".stylize().red(), "quit");
-        if stack.len() > 1 {
-            println!(" {} → {:12}", "<BS>".stylize().red(), "up");
+        let session = [
+            (modes.loop_mode, "LOOP"),
+            (modes.clear, "CLEAR"),
+            (modes.confirm, "CONFIRM"),
+            (modes.training, "TRAINING"),
+            (modes.present, "PRESENT"),
+        ];
+        for (_, name) in session.iter().filter(|(on, _)| *on) {
+            flags.push(name.stylize().blue().bold());
         }
-
-        if let Some(e) = error.take() {
-            println!();
-            println!("   {}", e.stylize().red());
-            println!();
+        // Breadcrumb takes the room left by the title and the flags
+        let flags_width: usize = flags.iter().map(|f| f.content().chars().count() + 1).sum();
+        let room = (width as usize).saturating_sub(2 + title.len() + flags_width);
+        let names = stack[1..]
+            .iter()
+            .map(|g| g.name.as_str())
+            .collect::<Vec<_>>();
+        let depth = stack[0].breadcrumb_depth;
+        let breadcrumb = breadcrumb(&names, depth, room);
+        write!(frame, "  {}", title.with(theme.breadcrumb()))?;
+        write!(frame, "{}", breadcrumb.with(theme.breadcrumb()))?;
+        for flag in flags {
+            write!(frame, " {}", flag)?;
         }
+        writeln!(frame)?;
+        writeln!(frame)?;
 
-        let KeyEvent {
-            code, modifiers, ..
-        } = next_key_event();
-        let reason = match code {
-            KeyCode::Char('q') => return Ok(None),
-            KeyCode::Char('c') if modifiers == KeyModifiers::CONTROL => return Ok(None),
-            KeyCode::Char(' ') => "Whitespace is not allowed".to_string(),
-            KeyCode::Backspace | KeyCode::Esc if stack.len() <= 1 => "This is the root".to_string(),
-            KeyCode::Backspace | KeyCode::Esc if stack.len() > 1 => {
-                stack.pop();
-                continue;
-            }
-            KeyCode::Char(ch) => {
-                let task = current_group.tasks.iter().find(|t| t.key == ch);
-                if let Some(task) = task {
-                    return Ok(Some(task));
-                }
-                let next_group = current_group.groups.iter().find(|g| g.key == ch);
-                if let Some(next_group) = next_group {
-                    stack.push(next_group);
-                    continue;
-                }
-                format!("No task for key: {}", ch)
-            }
-            _ => "Please enter character key".to_string(),
-        };
-        error = Some(reason)
+        draw_tasks(frame, current_group, focus, state, modes, width)?;
+        if modes.training {
+            draw_slowest_key_paths(frame, state)?;
+        }
+    } else {
+        writeln!(frame, "    {}", "No tasks configured".stylize().bold())?;
+        writeln!(
+            frame,
+            "    Create file {} in the current directory",
+            TTR_CONFIG
+        )?;
+    }
+    writeln!(frame)?;
+    writeln!(frame, "    {} → {:12}", "q".stylize().red(), "quit")?;
+    writeln!(frame, "    {} → {:12}", "!".stylize().red(), "force")?;
+    writeln!(frame, "  {} → {:12}", "<Tab>".stylize().red(), "preview")?;
+    writeln!(frame, "   {} → {:12}", "←↓↑→".stylize().red(), "browse")?;
+    writeln!(frame, "    {} → {:12}", "/".stylize().red(), "search")?;
+    writeln!(frame, "  {} → {:12}", "<C-p>".stylize().red(), "actions")?;
+    if !current_group.has_key('.') {
+        writeln!(frame, "    {} → {:12}", ".".stylize().red(), "repeat last")?;
+    }
+    if !current_group.has_key('h') {
+        writeln!(frame, "    {} → {:12}", "h".stylize().red(), "history")?;
+    }
+    if !current_group.has_key('?') {
+        writeln!(frame, "    {} → {:12}", "?".stylize().red(), "describe")?;
     }
+    if stack.len() > 1 {
+        writeln!(frame, " {} → {:12}", "<BS>".stylize().red(), "up")?;
+    }
+    Ok(())
+}
+
+/// Aggregated status of the group: the worst outcome of the last runs of its tasks. `None` if
+/// none of the tasks was run yet
+fn group_status(group: &Group, last_run: &HashMap<String, RunRecord>) -> Option<Outcome> {
+    let tasks = group.tasks.iter().filter_map(|t| last_run.get(&t.path));
+    let tasks = tasks.map(RunRecord::outcome);
+    let groups = group
+        .groups
+        .iter()
+        .filter_map(|g| group_status(g, last_run));
+    tasks.chain(groups).max()
 }
 
-fn draw_tasks(group: &Group) -> Result<()> {
+/// Draws the tasks and groups of the group. The item with the `focus` key is underlined
+fn draw_tasks(
+    frame: &mut String,
+    group: &Group,
+    focus: Option<char>,
+    state: &ProjectState,
+    modes: Modes,
+    width: u16,
+) -> Result<()> {
+    // Loop mode turns the selector into a dashboard of the last runs of the tasks
+    let dashboard = modes.loop_mode;
+    let theme = theme::current();
     let groups = group.groups.iter().map(DrawItem::Group);
     let tasks = group.tasks.iter().map(DrawItem::Task);
     let draw_items = Vec::from_iter(groups.chain(tasks));
 
-    let (width, _) = crossterm::terminal::size()?;
-    // 4 characters is a padding from screen edge
-    // 20 is width of one task representation
-    let columns_fit = (width as usize - 4) / 20;
-    let rows = (draw_items.len() + columns_fit - 1) / columns_fit;
+    let rows = grid_rows(draw_items.len(), modes, width);
     let columns = draw_items.chunks(rows).collect::<Vec<_>>();
     for i in 0..rows {
-        print!("  ");
+        write!(frame, "  ")?;
         for column in &columns {
             let Some(item) = column.get(i) else {
                 break;
             };
-            let name = if item.name().len() > 12 {
-                format!("{}…", item.name().chars().take(11).collect::<String>())
-            } else {
-                item.name().to_string()
+            let name = format!("{:12}", truncate(item.name(), 12)).stylize();
+            let name = match item {
+                _ if Some(item.key()) == focus => name.underlined(),
+                DrawItem::Task(task) if modes.present && task.dangerous => {
+                    name.dark_grey().crossed_out()
+                }
+                // Training mode leaves the keys to remember them
+                _ if modes.training => name.dark_grey(),
+                _ => name,
             };
             let key = item.key().stylize().bold();
             let key = if let DrawItem::Group(_) = item {
-                key.dark_blue()
+                key.with(theme.group_key())
             } else {
-                key.green()
+                key.with(theme.key())
+            };
+            let badge = match item {
+                DrawItem::Group(group) => match group_status(group, &state.last_run) {
+                    Some(Outcome::Success) => "●".stylize().green(),
+                    Some(Outcome::Warning) => "●".stylize().yellow(),
+                    Some(Outcome::Failure) => "●".stylize().red(),
+                    None => "○".stylize().grey(),
+                },
+                DrawItem::Task(task) if dashboard => match state.last_run.get(&task.path) {
+                    Some(record) => match record.outcome() {
+                        Outcome::Success => "✓".stylize().green(),
+                        Outcome::Warning => "!".stylize().yellow(),
+                        Outcome::Failure => "✗".stylize().red(),
+                    },
+                    None => " ".stylize(),
+                },
+                DrawItem::Task(task) if task.deprecated.is_some() => "†".stylize().yellow(),
+                DrawItem::Task(_) => " ".stylize(),
             };
-            print!(" {key} → {name:12}  ", key = key, name = name);
+            let duration = match item {
+                DrawItem::Task(task) if dashboard => {
+                    let record = state.last_run.get(&task.path);
+                    let duration = record.map(|r| insights::format_duration(r.duration_ms));
+                    format!(" {:>7}", duration.unwrap_or_default())
+                }
+                _ if dashboard => " ".repeat(8),
+                _ => String::new(),
+            };
+            write!(
+                frame,
+                " {key} → {name}{badge}{duration} ",
+                key = key,
+                name = name,
+                badge = badge,
+                duration = duration.stylize().grey()
+            )?;
+        }
+        writeln!(frame)?;
+        // Rows are spaced out for the audience in present mode
+        if modes.present && i + 1 < rows {
+            writeln!(frame)?;
+        }
+    }
+    Ok(())
+}
+
+/// Shortens the text to `width` characters ending it with an ellipsis
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated = text
+        .chars()
+        .take(width.saturating_sub(1))
+        .collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+/// Path of the groups shown after the selector title (` → backend → db`) fitting in `width`
+/// characters. The groups in the middle are replaced with an ellipsis when there are more than
+/// `max_depth` of them or the path is too wide. The first and the current group are always
+/// shown, the latter is truncated if nothing else helps
+fn breadcrumb(names: &[&str], max_depth: Option<usize>, width: usize) -> String {
+    let render = |names: &[&str]| {
+        names
+            .iter()
+            .map(|n| format!(" → {}", n))
+            .collect::<String>()
+    };
+    // Path with `skip` groups after the first one elided
+    let elided = |skip: usize| match skip {
+        0 => names.to_vec(),
+        _ => [&names[..1], &["…"], &names[1 + skip..]].concat(),
+    };
+    let max_skip = names.len().saturating_sub(2);
+    let depth = max_depth.unwrap_or(names.len()).max(2);
+    let mut skip = names.len().saturating_sub(depth).min(max_skip);
+    while skip < max_skip && render(&elided(skip)).chars().count() > width {
+        skip += 1;
+    }
+    let path = elided(skip);
+    match path.split_last() {
+        Some((last, rest)) if render(&path).chars().count() > width => {
+            let rest = render(rest);
+            let room = width.saturating_sub(rest.chars().count() + 3).max(1);
+            format!("{} → {}", rest, truncate(last, room))
         }
-        println!();
+        _ => render(&path),
+    }
+}
+
+/// Number of rows the items are laid out in. Items fill the columns top to bottom
+fn grid_rows(items: usize, modes: Modes, width: u16) -> usize {
+    // 4 characters is a padding from screen edge
+    // 20 is width of one task representation, the dashboard adds the duration of the last run
+    let item_width = if modes.loop_mode { 28 } else { 20 };
+    let columns_fit = ((width as usize).saturating_sub(4) / item_width).max(1);
+    items.div_ceil(columns_fit)
+}
+
+/// Index of the item focused after moving from the item `i` in the direction of the arrow
+fn move_focus(i: usize, direction: KeyCode, rows: usize, items: usize) -> usize {
+    let (row, column) = (i % rows, i / rows);
+    let last_column = (items - 1) / rows;
+    match direction {
+        KeyCode::Up if row > 0 => i - 1,
+        KeyCode::Down if row + 1 < rows && i + 1 < items => i + 1,
+        KeyCode::Left if column > 0 => i - rows,
+        // The last column can be shorter than the others
+        KeyCode::Right if column < last_column => (i + rows).min(items - 1),
+        _ => i,
     }
+}
+
+/// Maximum number of the slowest key paths shown in training mode
+const MAX_SLOWEST: usize = 5;
+
+/// Shows the key paths taking the longest to type (training mode)
+fn draw_slowest_key_paths(frame: &mut String, state: &ProjectState) -> Result<()> {
+    let slowest = state.slowest_key_paths(MAX_SLOWEST);
+    if slowest.is_empty() {
+        return Ok(());
+    }
+    let slowest = slowest
+        .iter()
+        .map(|(path, timing)| {
+            let average = insights::format_duration(timing.average_ms());
+            format!("{} {}", path.stylize().green().bold(), average)
+        })
+        .collect::<Vec<_>>();
+    writeln!(frame)?;
+    writeln!(
+        frame,
+        "  {} {}",
+        "SLOWEST".stylize().grey(),
+        slowest.join(" · ")
+    )?;
     Ok(())
 }
 
@@ -505,6 +4640,82 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn check_breadcrumb() {
+        let names = ["backend", "services", "db", "migrations"];
+        let full = " → backend → services → db → migrations";
+        assert_eq!(full, breadcrumb(&names, None, 80));
+        assert_eq!(
+            " → backend → … → migrations",
+            breadcrumb(&names, Some(2), 80)
+        );
+        assert_eq!(
+            " → backend → … → db → migrations",
+            breadcrumb(&names, None, 35)
+        );
+        assert_eq!(" → backend → … → migrations", breadcrumb(&names, None, 30));
+        assert_eq!(" → backend → … → migr…", breadcrumb(&names, None, 22));
+        assert_eq!(" → migrations", breadcrumb(&names[3..], Some(1), 80));
+        assert_eq!(" → ü → ö", breadcrumb(&["ü", "ö"], None, 8));
+    }
+
+    #[test]
+    fn check_draw_selector() {
+        let yaml = "
+            name: root
+            key: r
+            groups:
+            - name: build
+              key: b
+              tasks:
+              - {name: test, key: t, cmd: cargo test}
+        ";
+        let mut root: Group = serde_yaml::from_str(yaml).unwrap();
+        root.assign_paths("");
+        let state = ProjectState::default();
+        let status = Some("Task test completed".to_string());
+        let mut frame = String::new();
+        let stack = [&root, &root.groups[0]];
+        let modes = Modes::default();
+        draw_selector(&mut frame, &stack, None, &state, &status, modes, 80).unwrap();
+        assert!(frame.contains("Task test completed"), "{}", frame);
+        assert!(frame.contains("SELECT A TASK"), "{}", frame);
+        assert!(frame.contains(" → build"), "{}", frame);
+        assert!(frame.contains(" → test"), "{}", frame);
+        assert!(frame.contains(" → up"), "{}", frame);
+
+        let empty = Group::default();
+        frame.clear();
+        draw_selector(&mut frame, &[&empty], None, &state, &None, modes, 80).unwrap();
+        assert!(frame.contains("No tasks configured"), "{}", frame);
+        assert!(!frame.contains(" → up"), "{}", frame);
+
+        // Rejected keys are reported below the cached layout
+        assert_eq!("No task for key: x", SelectError::NoTask('x').to_string());
+    }
+
+    #[test]
+    fn check_truncate() {
+        assert_eq!("build", truncate("build", 12));
+        assert_eq!("integration…", truncate("integration tests", 12));
+        assert_eq!("ünïcödé nämé", truncate("ünïcödé nämé", 12));
+    }
+
+    #[test]
+    fn check_move_focus() {
+        // a d
+        // b e
+        // c
+        let (rows, items) = (3, 5);
+        assert_eq!(1, move_focus(0, KeyCode::Down, rows, items));
+        assert_eq!(0, move_focus(0, KeyCode::Up, rows, items));
+        assert_eq!(3, move_focus(0, KeyCode::Right, rows, items));
+        assert_eq!(4, move_focus(2, KeyCode::Right, rows, items));
+        assert_eq!(4, move_focus(4, KeyCode::Down, rows, items));
+        assert_eq!(1, move_focus(4, KeyCode::Left, rows, items));
+        assert_eq!(3, move_focus(3, KeyCode::Right, rows, items));
+    }
+
     #[test]
     fn check_yaml_serialization() {
         let yaml = "
@@ -522,6 +4733,168 @@ mod tests {
         assert_eq!(1, group.groups.len());
     }
 
+    #[test]
+    fn check_find_in_path() {
+        let path = env::join_paths(["/no-such-dir", "/bin"]).unwrap();
+        let path = path.to_str().unwrap();
+        assert_eq!(Some(PathBuf::from("/bin/sh")), find_in_path("sh", path));
+        assert_eq!(None, find_in_path("ttr-no-such-command", path));
+    }
+
+    #[test]
+    fn check_shell_args() {
+        assert_eq!(vec!["-c", "exec ls"], shell_args("/bin/zsh", "ls", false));
+        assert_eq!(vec!["-lic", "ls"], shell_args("/bin/zsh", "ls", true));
+        assert_eq!(vec!["/C", "dir"], shell_args("cmd.exe", "dir", false));
+        let powershell = vec!["-NoProfile", "-Command", "ls"];
+        assert_eq!(powershell, shell_args("powershell.exe", "ls", false));
+
+        let script = Path::new("script.sh");
+        assert_eq!(vec!["script.sh"], script_args("/bin/sh", script, false));
+        assert_eq!(
+            vec!["-l", "script.sh"],
+            script_args("/bin/sh", script, true)
+        );
+    }
+
+    #[test]
+    fn check_shell() {
+        let shell = Shell::parse("fish -c");
+        assert_eq!("fish", shell.program);
+        assert_eq!(vec!["-c"], shell.args);
+        let (program, args) = shell.command("ls", true);
+        assert_eq!("fish", program);
+        assert_eq!(vec!["-c", "ls"], args);
+
+        let yaml = "{name: t, key: t, cmd: t, shell: [bash, -lc]}";
+        let task: Task = serde_yaml::from_str(yaml).unwrap();
+        let (program, args) = task.shell.unwrap().command("ll", false);
+        assert_eq!("bash", program);
+        assert_eq!(vec!["-lc", "ll"], args);
+
+        let (_, args) = Shell::parse("/bin/zsh").command("ls", false);
+        assert_eq!(vec!["-c", "exec ls"], args);
+        let yaml = "{name: t, key: t, cmd: t, shell: []}";
+        assert!(serde_yaml::from_str::<Task>(yaml).is_err());
+    }
+
+    #[test]
+    fn check_task_shell() {
+        let opts = Opts::parse_from(["ttr", "--shell", "zsh"]);
+        let task: Task = serde_yaml::from_str("{name: t, key: t, cmd: t}").unwrap();
+        assert_eq!(Shell::program("zsh"), task_shell(&task, &opts));
+
+        let yaml = "{name: t, key: t, cmd: t, shell: [bash, -lc]}";
+        let task: Task = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(Shell::parse("bash -lc"), task_shell(&task, &opts));
+
+        // Shell of the user is not expected in the image
+        let yaml = "{name: t, key: t, cmd: t, image: alpine}";
+        let task: Task = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(Shell::program("sh"), task_shell(&task, &opts));
+    }
+
+    #[test]
+    fn check_task_log() {
+        let dir = env::temp_dir().join(format!("ttr-task-log-{}", std::process::id()));
+        let dir_arg = dir.to_string_lossy();
+        let opts = Opts::parse_from(["ttr", "--log-dir", &dir_arg]);
+        let yaml = "{name: top, key: t, cmd: htop, tui: true}";
+        let task: Task = serde_yaml::from_str(yaml).unwrap();
+        // Output of a full-screen program is never logged
+        assert!(task_log(&task, &opts).unwrap().is_none());
+
+        let task: Task = serde_yaml::from_str("{name: top, key: t, cmd: top -b}").unwrap();
+        assert!(task_log(&task, &opts).unwrap().is_some());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn check_nix_develop() {
+        let args = || vec!["-c".to_string(), "exec cargo build".to_string()];
+        let task: Task = serde_yaml::from_str("{name: build, key: b, cmd: cargo build}").unwrap();
+        assert_eq!(
+            ("sh".to_string(), args()),
+            nix_develop(&task, "sh".into(), args())
+        );
+
+        let task: Task = serde_yaml::from_str("{name: b, key: b, cmd: b, devshell: ci}").unwrap();
+        let (program, wrapped) = nix_develop(&task, "sh".into(), args());
+        assert_eq!("nix", program);
+        let expected = ["develop", ".#ci", "-c", "sh", "-c", "exec cargo build"];
+        assert_eq!(expected.as_slice(), wrapped);
+    }
+
+    #[test]
+    fn check_secrets_are_not_on_command_line() {
+        secrets::set("TTR_TEST_INVOCATION_SECRET", "s3cret".to_string());
+        let yaml = "{name: d, key: d, cmd: ./deploy, env: {A: b}, target: external-terminal, \
+            secrets: [TTR_TEST_INVOCATION_SECRET]}";
+        let task: Task = serde_yaml::from_str(yaml).unwrap();
+        let shell = Shell::parse("sh");
+        let options = RunOptions::default();
+        let invocation = Invocation::new(&task, &shell, options, &[], &HashMap::new()).unwrap();
+        assert_eq!("s3cret", invocation.secrets[0].1);
+        let command_line = invocation.command_line().collect::<Vec<_>>();
+        assert!(command_line.contains(&"A=b".to_string()));
+        assert!(
+            command_line.iter().all(|arg| !arg.contains("s3cret")),
+            "{:?}",
+            command_line
+        );
+        let error = target::launch(&task, &invocation, "xterm -e").unwrap_err();
+        assert!(error.to_string().contains("secrets"), "{}", error);
+
+        let yaml = "{name: d, key: d, cmd: ./deploy, ssh: staging, \
+            secrets: [TTR_TEST_INVOCATION_SECRET]}";
+        let task: Task = serde_yaml::from_str(yaml).unwrap();
+        assert!(Invocation::new(&task, &shell, options, &[], &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn check_first_program() {
+        assert_eq!("cargo", first_program("cargo build --release"));
+        assert_eq!(
+            "make",
+            first_program("#!/bin/sh -e\n\n# Build it\n  make all\n")
+        );
+        assert_eq!("", first_program("# nothing to run"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_failure_hints() {
+        use std::os::unix::process::ExitStatusExt;
+        let not_found = ExitStatus::from_raw(NOT_FOUND_EXIT_CODE << 8);
+        let yaml = "{name: s, key: s, script: \"#!/bin/sh\\nttr-no-such-command --help\"}";
+        let task: Task = serde_yaml::from_str(yaml).unwrap();
+        let hints = failure_hints(&task, not_found);
+        assert!(
+            hints[0].starts_with("`ttr-no-such-command` is not found"),
+            "{:?}",
+            hints
+        );
+
+        let task: Task = serde_yaml::from_str("{name: s, key: s, script: \"sh\\nls\"}").unwrap();
+        let hints = failure_hints(&task, not_found);
+        assert!(
+            hints[0].starts_with("Some command is not found"),
+            "{:?}",
+            hints
+        );
+    }
+
+    #[test]
+    fn check_shebang() {
+        let python = Some(vec!["python3".to_string()]);
+        assert_eq!(python, shebang("#!/usr/bin/env python3\nprint(1)"));
+        assert_eq!(python, shebang("#!/usr/bin/env -S python3"));
+        let bash = Some(vec!["/bin/bash".to_string(), "-e".to_string()]);
+        assert_eq!(bash, shebang("#!/bin/bash -e\nls"));
+        assert_eq!(None, shebang("ls\n#!/bin/sh"));
+        assert_eq!(None, shebang("#!"));
+    }
+
     #[test]
     fn check_iteration() {
         let yaml = "
@@ -545,4 +4918,137 @@ mod tests {
         let names: Vec<_> = group.iter_mut().map(|s| s.name.as_str()).collect();
         assert_eq!(vec!["boo", "bar"], names);
     }
+
+    #[test]
+    fn check_find_task() {
+        let yaml = "
+            name: name
+            key: c
+            groups:
+            - name: foo
+              key: f
+              tasks:
+              - name: bar
+                cmd: --
+                key: b
+        ";
+        let group: Group = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!("bar", group.find_task("fb").unwrap().name);
+        assert!(group.find_task("f").is_none());
+        assert!(group.find_task("fx").is_none());
+    }
+
+    #[test]
+    fn check_find_by_path() {
+        let yaml = "
+            name: name
+            key: c
+            groups:
+            - name: git
+              key: g
+              tasks:
+              - {name: lazygit, key: g, cmd: lazygit}
+        ";
+        let group: Group = serde_yaml::from_str(yaml).unwrap();
+        let path = |parts: &[&str]| parts.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let find = |parts: &[&str]| group.find_by_path(&path(parts)).map(|t| t.name.as_str());
+        assert_eq!(Some("lazygit"), find(&["g", "g"]));
+        assert_eq!(Some("lazygit"), find(&["gg"]));
+        assert_eq!(Some("lazygit"), find(&["git.lazygit"]));
+        assert_eq!(Some("lazygit"), find(&["Git", "LazyGit"]));
+        assert_eq!(None, find(&["git"]));
+        assert_eq!(None, find(&["git.lazygit.x"]));
+    }
+
+    #[test]
+    fn check_dependencies() {
+        let yaml = "
+            name: name
+            key: c
+            tasks:
+            - {name: a, key: a, cmd: a, depends_on: [b, gc]}
+            - {name: b, key: b, cmd: b, depends_on: [c]}
+            - {name: d, key: d, cmd: d, depends_on: [e]}
+            - {name: e, key: e, cmd: e, depends_on: [d]}
+            groups:
+            - name: group
+              key: g
+              tasks:
+              - {name: c, key: c, cmd: c}
+        ";
+        let mut group: Group = serde_yaml::from_str(yaml).unwrap();
+        group.assign_paths("");
+        let a = group.find_task("a").unwrap();
+        let names = group.dependencies(a).unwrap();
+        let names = names.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["c", "b"], names);
+
+        let d = group.find_task("d").unwrap();
+        assert!(group.dependencies(d).is_err());
+    }
+
+    #[test]
+    fn check_composite_tasks() {
+        let yaml = "
+            name: name
+            key: c
+            tasks:
+            - {name: dev, key: d, parallel: [backend, f]}
+            - {name: backend, key: b, cmd: b}
+            - {name: frontend, key: f, cmd: f}
+            - {name: all, key: a, parallel: [dev]}
+        ";
+        let mut group: Group = serde_yaml::from_str(yaml).unwrap();
+        group.assign_paths("");
+        let dev = group.find_task("d").unwrap();
+        let names = group.composite_tasks(dev).unwrap();
+        let names = names.iter().map(|t| t.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["backend", "frontend"], names);
+
+        assert!(group
+            .composite_tasks(group.find_task("a").unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn check_inherit_settings() {
+        let yaml = "
+            name: name
+            key: c
+            groups:
+            - name: backend
+              key: b
+              working_dir: backend
+              env: {RUST_LOG: debug, PROFILE: dev}
+              clear: true
+              groups:
+              - name: db
+                key: d
+                working_dir: db
+                env: {PROFILE: test}
+                tasks:
+                - {name: migrate, key: m, cmd: migrate}
+              tasks:
+              - {name: run, key: r, cmd: run, clear: false, clear_env: true}
+              - {name: deploy, key: D, cmd: deploy, working_dir: /srv}
+        ";
+        let mut group: Group = serde_yaml::from_str(yaml).unwrap();
+        group.assign_paths("");
+        group.inherit_settings();
+
+        let migrate = group.find_task("bdm").unwrap();
+        assert_eq!(Some(PathBuf::from("backend/db")), migrate.working_dir);
+        assert_eq!("debug", migrate.env["RUST_LOG"]);
+        assert_eq!("test", migrate.env["PROFILE"]);
+        assert_eq!(Some(true), migrate.clear);
+        assert_eq!(None, migrate.clear_env);
+
+        let run = group.find_task("br").unwrap();
+        assert_eq!(Some(false), run.clear);
+        assert_eq!(Some(true), run.clear_env);
+        assert_eq!("dev", run.env["PROFILE"]);
+
+        let deploy = group.find_task("bD").unwrap();
+        assert_eq!(Some(PathBuf::from("/srv")), deploy.working_dir);
+    }
 }
@@ -0,0 +1,121 @@
+//! Notifications about completed tasks (`notify_backend: tmux`).
+//!
+//! Long tasks are often left running while working in another window. When ttr runs inside
+//! tmux and its pane is not the one being looked at, the summary of the run is shown in the
+//! tmux status line and the bell is rung, so tmux marks the window. Desktop notifications are
+//! sent with `notify-send` (`osascript` on macOS). Notifications are not sent outside of tmux
+//! unless the backend is configured.
+use crate::Result;
+use anyhow::bail;
+use serde::Deserialize;
+use std::{
+    env,
+    io::{stdout, IsTerminal, Write},
+    process::Command,
+};
+use tracing::debug;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Message in the tmux status line and the bell, if the pane of ttr is not focused
+    Tmux,
+    /// Notification of the desktop environment
+    Desktop,
+    /// Terminal bell
+    Bell,
+    None,
+}
+
+impl Backend {
+    /// Backend used when none is configured: tmux if ttr is running inside it
+    pub fn detect() -> Self {
+        match env::var("TMUX") {
+            Ok(tmux) if !tmux.is_empty() => Backend::Tmux,
+            _ => Backend::None,
+        }
+    }
+}
+
+pub fn send(backend: Backend, summary: &str) -> Result<()> {
+    debug!(?backend, summary, "notifying");
+    match backend {
+        Backend::Tmux => {
+            if is_pane_focused()? {
+                return Ok(());
+            }
+            let message = format!("ttr: {}", summary.replace('#', "##"));
+            run(Command::new("tmux").args(["display-message", "--", &message]))?;
+            ring_bell()
+        }
+        Backend::Desktop if cfg!(target_os = "macos") => {
+            let script = format!(
+                "display notification {} with title \"ttr\"",
+                applescript_string(summary)
+            );
+            run(Command::new("osascript").args(["-e", &script]))
+        }
+        Backend::Desktop => run(Command::new("notify-send").args(["ttr", summary])),
+        Backend::Bell => ring_bell(),
+        Backend::None => Ok(()),
+    }
+}
+
+/// Returns true if the pane ttr is running in is the active one of the active window and
+/// somebody is attached to the session
+fn is_pane_focused() -> Result<bool> {
+    let mut command = Command::new("tmux");
+    command.args(["display-message", "-p"]);
+    if let Ok(pane) = env::var("TMUX_PANE") {
+        command.args(["-t", &pane]);
+    }
+    let output = command
+        .arg("#{pane_active} #{window_active} #{session_attached}")
+        .output()?;
+    if !output.status.success() {
+        bail!("tmux failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    let flags = String::from_utf8_lossy(&output.stdout);
+    Ok(matches!(
+        flags.split_whitespace().collect::<Vec<_>>()[..],
+        ["1", "1", attached] if attached != "0"
+    ))
+}
+
+/// Bell is rung only if the output goes to the terminal
+fn ring_bell() -> Result<()> {
+    let mut stdout = stdout();
+    if !stdout.is_terminal() {
+        return Ok(());
+    }
+    stdout.write_all(b"\x07")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let status = command.status()?;
+    if !status.success() {
+        bail!("{:?} failed: {}", command.get_program(), status);
+    }
+    Ok(())
+}
+
+/// String literal of AppleScript
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_applescript_string() {
+        assert_eq!(
+            r#""Task \"build\" failed""#,
+            applescript_string("Task \"build\" failed")
+        );
+        assert_eq!(r#""a\\b""#, applescript_string(r"a\b"));
+    }
+}
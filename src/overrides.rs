@@ -0,0 +1,149 @@
+//! Per-user overrides of the shared tasks (`.ttr.local.yaml`).
+//!
+//! The file next to a project config changes fields of its tasks found by their key paths
+//! without redefining them, so the shared config stays canonical. Maps (`env`) are merged with
+//! the ones of the task, other fields are replaced, and `append` adds flags to the command:
+//!
+//! ```yaml
+//! overrides:
+//!   bt:
+//!     env: {RUST_LOG: debug}
+//!     append: --release
+//! ```
+use crate::Result;
+use anyhow::{anyhow, bail, Context};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+const LOCAL_CONFIG: &str = ".ttr.local.yaml";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Local {
+    /// Fields of the tasks by their key paths in the config
+    #[serde(default)]
+    overrides: BTreeMap<String, Mapping>,
+}
+
+/// Path of the local config overriding the tasks of the config in `config_dir`
+pub fn local_config(config_dir: &Path) -> PathBuf {
+    config_dir.join(LOCAL_CONFIG)
+}
+
+/// Applies the overrides of the local config in `config_dir` if there is one
+pub fn apply(config: &mut Value, config_dir: &Path) -> Result<()> {
+    let path = local_config(config_dir);
+    if !path.is_file() {
+        return Ok(());
+    }
+    info!(path = %path.display(), "reading local overrides");
+    let content = fs::read_to_string(&path)?;
+    let local: Local = serde_yaml::from_str(&content)
+        .with_context(|| format!("Invalid local config {}", path.display()))?;
+    for (key_path, fields) in local.overrides {
+        let task = find_task(config, &key_path)
+            .ok_or_else(|| anyhow!("No task for key path {} in {}", key_path, path.display()))?;
+        merge(task, fields).with_context(|| format!("Unable to override task {}", key_path))?;
+    }
+    Ok(())
+}
+
+/// Mapping of the task with the key path in the config as written (groups are not merged yet)
+fn find_task<'a>(config: &'a mut Value, key_path: &str) -> Option<&'a mut Mapping> {
+    let mut keys = key_path.chars().peekable();
+    let mut group = config;
+    while let Some(key) = keys.next() {
+        let items = if keys.peek().is_some() {
+            "groups"
+        } else {
+            "tasks"
+        };
+        let item = group
+            .get_mut(items)?
+            .as_sequence_mut()?
+            .iter_mut()
+            .find(|item| has_key(item, key))?;
+        group = item;
+    }
+    group.as_mapping_mut()
+}
+
+/// Keys are usually strings, but digits are parsed as numbers by YAML
+fn has_key(item: &Value, key: char) -> bool {
+    match item.get("key") {
+        Some(Value::String(k)) => k.chars().eq([key]),
+        Some(Value::Number(k)) => k.to_string().chars().eq([key]),
+        _ => false,
+    }
+}
+
+fn merge(task: &mut Mapping, fields: Mapping) -> Result<()> {
+    for (name, value) in fields {
+        if name == "append" {
+            let (Some(cmd), Some(flags)) = (task.get_mut("cmd"), value.as_str()) else {
+                bail!("append requires a task with cmd and a string of flags");
+            };
+            let Value::String(cmd) = cmd else {
+                bail!("cmd should be a string");
+            };
+            cmd.push(' ');
+            cmd.push_str(flags);
+            continue;
+        }
+        match (task.get_mut(&name), value) {
+            (Some(Value::Mapping(existing)), Value::Mapping(value)) => existing.extend(value),
+            (_, value) => {
+                task.insert(name, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_apply() {
+        let dir = std::env::temp_dir().join(format!("ttr-overrides-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut config: Value = serde_yaml::from_str(
+            "
+groups:
+- name: build
+  key: b
+  tasks:
+  - {name: test, key: t, cmd: cargo test, env: {A: '1', B: '2'}}
+  - {name: one, key: 1, cmd: make one}
+",
+        )
+        .unwrap();
+        let local = "
+overrides:
+  bt: {env: {B: '3'}, append: --release, working_dir: app}
+  b1: {cmd: make two}
+";
+        fs::write(dir.join(LOCAL_CONFIG), local).unwrap();
+        apply(&mut config, &dir).unwrap();
+        let task = &config["groups"][0]["tasks"][0];
+        assert_eq!("cargo test --release", task["cmd"].as_str().unwrap());
+        assert_eq!("1", task["env"]["A"].as_str().unwrap());
+        assert_eq!("3", task["env"]["B"].as_str().unwrap());
+        assert_eq!("app", task["working_dir"].as_str().unwrap());
+        assert_eq!(
+            "make two",
+            config["groups"][0]["tasks"][1]["cmd"].as_str().unwrap()
+        );
+
+        fs::write(dir.join(LOCAL_CONFIG), "overrides: {bx: {cmd: ls}}").unwrap();
+        assert!(apply(&mut config, &dir).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
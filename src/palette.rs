@@ -0,0 +1,143 @@
+//! Palette of ttr's own actions searchable by name (`Ctrl-P` in the selector), so the features
+//! don't each need a dedicated key.
+use crate::{search, ui::Ui, Result};
+use crossterm::{
+    event::{KeyCode, KeyModifiers},
+    style::Stylize,
+};
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Search,
+    History,
+    RepeatLast,
+    ToggleForce,
+    TogglePreview,
+    ToggleLoop,
+    ToggleClear,
+    ToggleConfirm,
+    ToggleTraining,
+    ReloadConfig,
+    OpenConfig,
+    Quit,
+}
+
+impl Action {
+    /// Actions in the order they are listed in the palette
+    const ALL: [Action; 12] = [
+        Action::Search,
+        Action::History,
+        Action::RepeatLast,
+        Action::ToggleForce,
+        Action::TogglePreview,
+        Action::ToggleLoop,
+        Action::ToggleClear,
+        Action::ToggleConfirm,
+        Action::ToggleTraining,
+        Action::ReloadConfig,
+        Action::OpenConfig,
+        Action::Quit,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Search => "Search tasks",
+            Action::History => "Recent tasks",
+            Action::RepeatLast => "Repeat the last task",
+            Action::ToggleForce => "Toggle force mode",
+            Action::TogglePreview => "Toggle preview mode",
+            Action::ToggleLoop => "Toggle loop mode",
+            Action::ToggleClear => "Toggle clearing the screen",
+            Action::ToggleConfirm => "Toggle confirm mode",
+            Action::ToggleTraining => "Toggle training mode",
+            Action::ReloadConfig => "Reload config",
+            Action::OpenConfig => "Open config in editor",
+            Action::Quit => "Quit",
+        }
+    }
+
+    /// Key of the selector doing the same
+    fn key(self) -> Option<&'static str> {
+        match self {
+            Action::Search => Some("/"),
+            Action::History => Some("h"),
+            Action::RepeatLast => Some("."),
+            Action::ToggleForce => Some("!"),
+            Action::TogglePreview => Some("<Tab>"),
+            Action::Quit => Some("q"),
+            Action::ToggleLoop
+            | Action::ToggleClear
+            | Action::ToggleConfirm
+            | Action::ToggleTraining
+            | Action::ReloadConfig
+            | Action::OpenConfig => None,
+        }
+    }
+}
+
+/// Shows the palette. Returns the chosen action or `None` if the palette was closed
+pub fn select(ui: &mut Ui) -> Result<Option<Action>> {
+    let mut query = String::new();
+    let mut selected = 0;
+    loop {
+        let matches = search::rank(&query, &Action::ALL, |action| action.name());
+        selected = selected.min(matches.len().saturating_sub(1));
+
+        let mut frame = String::new();
+        writeln!(frame)?;
+        writeln!(frame, "  {} {}▏", "ACTIONS".stylize().grey(), query)?;
+        writeln!(frame)?;
+        for (i, m) in matches.iter().enumerate() {
+            let key = m.item.key().unwrap_or_default();
+            // Highlighting escapes don't take space, so the name is padded beforehand
+            let name = format!("{:24}", m.item.name());
+            let name = search::highlight(&name, &m.positions);
+            match i == selected {
+                true => writeln!(
+                    frame,
+                    "  {} {} {}",
+                    "→".stylize().green(),
+                    name.bold(),
+                    key.stylize().grey()
+                )?,
+                false => writeln!(frame, "    {} {}", name, key.stylize().grey())?,
+            }
+        }
+        if matches.is_empty() {
+            writeln!(frame, "    {}", "No matching actions".stylize().grey())?;
+        }
+        writeln!(frame)?;
+        writeln!(
+            frame,
+            "  {} → run, {} → select, {} → back",
+            "<Enter>".stylize().red(),
+            "<Up>/<Down>".stylize().red(),
+            "<Esc>".stylize().red()
+        )?;
+        ui.draw(&frame)?;
+
+        let event = ui.next_key_event()?;
+        match event.code {
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c' | 'p') if event.modifiers == KeyModifiers::CONTROL => {
+                return Ok(None)
+            }
+            KeyCode::Enter => {
+                if let Some(m) = matches.get(selected) {
+                    return Ok(Some(*m.item));
+                }
+            }
+            KeyCode::Up => selected = selected.saturating_sub(1),
+            KeyCode::Down => selected += 1,
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Char(ch) => {
+                query.push(ch);
+                selected = 0;
+            }
+            _ => {}
+        }
+    }
+}
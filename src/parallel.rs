@@ -0,0 +1,82 @@
+//! Tasks run concurrently with their output multiplexed (`parallel: [backend, frontend]`).
+//!
+//! Each line of the output is prefixed with the colored name of the task it came from.
+use crate::{create_process, timeout::Watchdog, Invocation, Result, Task};
+use crossterm::style::{Color, Stylize};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{Child, ExitStatus},
+    thread::{self, JoinHandle},
+};
+
+const COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Blue,
+    Color::Green,
+    Color::Red,
+];
+
+/// Runs all the tasks at the same time and waits for all of them to exit
+pub fn run<'a>(invocations: &[(&'a Task, Invocation)]) -> Result<Vec<(&'a Task, ExitStatus)>> {
+    let width = invocations
+        .iter()
+        .map(|(task, _)| task.name.chars().count())
+        .max()
+        .unwrap_or_default();
+    let mut children: Vec<(&Task, Child)> = vec![];
+    for (task, invocation) in invocations {
+        // Tasks can't share the terminal, so they stay in the process group of ttr
+        match create_process(task, invocation, true, false) {
+            Ok(child) => children.push((task, child)),
+            Err(e) => {
+                for (_, child) in &mut children {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+                return Err(e);
+            }
+        }
+    }
+    let mut readers = vec![];
+    for (i, (task, child)) in children.iter_mut().enumerate() {
+        let prefix = format!("{:width$} |", task.name, width = width)
+            .with(COLORS[i % COLORS.len()])
+            .to_string();
+        readers.extend(child.stdout.take().map(|out| prefixed(out, prefix.clone())));
+        readers.extend(child.stderr.take().map(|err| prefixed(err, prefix.clone())));
+    }
+    let mut statuses = vec![];
+    let watchdogs = children
+        .iter()
+        .map(|(task, child)| {
+            task.timeout
+                .map(|timeout| Watchdog::start(child.id(), timeout))
+        })
+        .collect::<Vec<_>>();
+    for ((task, mut child), watchdog) in children.into_iter().zip(watchdogs) {
+        statuses.push((task, child.wait()?));
+        if let Some(watchdog) = watchdog {
+            watchdog.stop();
+        }
+    }
+    for reader in readers {
+        let _ = reader.join();
+    }
+    Ok(statuses)
+}
+
+/// Copies the output to stdout line by line prefixing each one
+fn prefixed(output: impl Read + Send + 'static, prefix: String) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut output = BufReader::new(output);
+        let mut line = vec![];
+        while matches!(output.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+            let text = String::from_utf8_lossy(&line);
+            let mut stdout = io::stdout().lock();
+            let _ = writeln!(stdout, "{} {}", prefix, text.trim_end_matches(['\n', '\r']));
+            line.clear();
+        }
+    })
+}
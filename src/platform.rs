@@ -0,0 +1,60 @@
+//! Defaults depending on the operating system.
+//!
+//! There is no `sh` on native Windows, so commands are run by `cmd.exe` unless another shell is
+//! given in `$SHELL` (eg. bash from Git for Windows). Console programs without Windows
+//! counterparts (`less`, `vi`) are replaced with the ones shipped with the system.
+use std::{env, path::Path};
+
+#[cfg(not(windows))]
+mod defaults {
+    pub const SHELL: &str = "sh";
+    pub const PAGER: &str = "less";
+    pub const EDITOR: &str = "vi";
+    pub const TERMINAL: &str = "x-terminal-emulator -e";
+    pub const KEPT_VARIABLES: [&str; 7] =
+        ["PATH", "HOME", "USER", "LOGNAME", "SHELL", "TERM", "TMPDIR"];
+}
+
+#[cfg(windows)]
+mod defaults {
+    pub const SHELL: &str = "cmd.exe";
+    pub const PAGER: &str = "more";
+    pub const EDITOR: &str = "notepad";
+    /// Windows Terminal runs the command line given as arguments in a new window
+    pub const TERMINAL: &str = "wt";
+    /// Programs (including `cmd.exe` itself) don't start without `SYSTEMROOT`
+    pub const KEPT_VARIABLES: [&str; 7] = [
+        "PATH",
+        "PATHEXT",
+        "SYSTEMROOT",
+        "COMSPEC",
+        "USERPROFILE",
+        "TEMP",
+        "TMP",
+    ];
+}
+
+pub use defaults::{EDITOR, PAGER, TERMINAL};
+
+/// Variables kept for the tasks run with the environment cleared (`clear_env: true`), so the
+/// shell and the programs it runs still work
+pub fn kept_variables() -> Vec<(String, String)> {
+    defaults::KEPT_VARIABLES
+        .iter()
+        .filter_map(|name| Some((name.to_string(), env::var(name).ok()?)))
+        .collect()
+}
+
+/// Shell of the user (`$SHELL`). On Windows falls back to `%COMSPEC%`
+pub fn default_shell() -> String {
+    let var = |name| env::var(name).ok().filter(|value| !value.is_empty());
+    let fallback = if cfg!(windows) { var("COMSPEC") } else { None };
+    var("SHELL")
+        .or(fallback)
+        .unwrap_or(defaults::SHELL.to_string())
+}
+
+/// Returns true if the program is given by a path rather than looked up in `PATH`
+pub fn is_path(program: &str) -> bool {
+    program.chars().any(std::path::is_separator) || Path::new(program).is_absolute()
+}
@@ -0,0 +1,113 @@
+//! Built-in tasks offered for the projects without a config.
+//!
+//! Project type is detected by the build files in the current directory or the nearest of its
+//! parents having any (up to the project root). Each detected tool gets a group of its common
+//! tasks. `package.json` and `Makefile` are imported the same way as with `import:`, so the
+//! actual scripts and targets of the project are offered.
+use crate::state;
+use serde_yaml::{Mapping, Value};
+use std::path::{Path, PathBuf};
+
+/// Tasks offered when the build file is found
+enum Preset {
+    /// Group of the tasks written in YAML
+    Group(&'static str),
+    /// Tasks imported from the build file itself (`import: {type: ...}`)
+    Import(&'static str),
+}
+
+const PRESETS: [(&str, Preset); 4] = [
+    ("Cargo.toml", Preset::Group(CARGO)),
+    ("go.mod", Preset::Group(GO)),
+    ("package.json", Preset::Import("npm")),
+    ("Makefile", Preset::Import("make")),
+];
+
+const CARGO: &str = "
+name: cargo
+key: c
+tasks:
+- {name: build, key: b, cmd: cargo build}
+- {name: test, key: t, cmd: cargo test}
+- {name: run, key: r, cmd: cargo run}
+- {name: check, key: c, cmd: cargo check}
+- {name: clippy, key: l, cmd: cargo clippy --all-targets}
+- {name: fmt, key: f, cmd: cargo fmt}
+";
+
+const GO: &str = "
+name: go
+key: g
+tasks:
+- {name: build, key: b, cmd: go build ./...}
+- {name: test, key: t, cmd: go test ./...}
+- {name: run, key: r, cmd: go run .}
+- {name: vet, key: v, cmd: go vet ./...}
+- {name: fmt, key: f, cmd: go fmt ./...}
+";
+
+/// Config with the tasks of the tools detected in `dir` or its parents along with the
+/// directory the tools were found in. `None` if no known build files are found
+pub fn detect(dir: &Path) -> Option<(PathBuf, Value)> {
+    let root = state::project_root(dir);
+    let mut dir = Some(dir);
+    while let Some(d) = dir {
+        let mut groups = vec![];
+        let mut imports = vec![];
+        for (file, preset) in &PRESETS {
+            if !d.join(file).is_file() {
+                continue;
+            }
+            match preset {
+                Preset::Group(group) => {
+                    groups.push(serde_yaml::from_str(group).expect("Invalid preset"))
+                }
+                Preset::Import(import_type) => {
+                    let mut import = Mapping::new();
+                    import.insert("type".into(), (*import_type).into());
+                    imports.push(Value::Mapping(import));
+                }
+            }
+        }
+        if !groups.is_empty() || !imports.is_empty() {
+            let mut config = Mapping::new();
+            config.insert("groups".into(), Value::Sequence(groups));
+            config.insert("import".into(), Value::Sequence(imports));
+            return Some((d.to_path_buf(), Value::Mapping(config)));
+        }
+        if d == root {
+            break;
+        }
+        dir = d.parent();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn check_detect() {
+        let dir = std::env::temp_dir().join(format!("ttr-presets-{}", std::process::id()));
+        let nested = dir.join("src");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        fs::write(dir.join("Makefile"), "").unwrap();
+
+        let (found, config) = detect(&nested).unwrap();
+        assert_eq!(dir, found);
+        let groups = config["groups"].as_sequence().unwrap();
+        assert_eq!(
+            vec!["cargo"],
+            groups
+                .iter()
+                .map(|g| g["name"].as_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("make", config["import"][0]["type"].as_str().unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
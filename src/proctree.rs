@@ -0,0 +1,305 @@
+//! Process tree of the running tasks with CPU and memory usage (`ttr ps`).
+//!
+//! Processes are read from `/proc`, so the tree is only available on Linux.
+use crate::{capture, insights, jobs, state, ui::Ui, Result};
+use anyhow::{bail, Context};
+use crossterm::{
+    event::{KeyCode, KeyModifiers},
+    style::Stylize,
+};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    fs,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+/// Interval between the refreshes of the tree
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Clock ticks per second used in `/proc/<pid>/stat` (`USER_HZ`, 100 on all supported
+/// architectures)
+const CLOCK_TICKS: f64 = 100.0;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Process {
+    pid: u32,
+    ppid: u32,
+    name: String,
+    cmdline: String,
+    /// CPU time consumed in clock ticks (user and system)
+    cpu_ticks: u64,
+    /// Resident set size in bytes
+    rss: u64,
+}
+
+/// Shows the process trees of the running tasks refreshed periodically. Selected process can be
+/// terminated with `t` or killed with `k`
+pub fn run(ui: &mut Ui) -> Result<()> {
+    if !cfg!(target_os = "linux") {
+        bail!("Process tree is only supported on Linux");
+    }
+    let _alt = ui.alternate_screen();
+    let mut selected = 0;
+    let mut previous: Option<(Instant, HashMap<u32, Process>)> = None;
+    let mut message = None;
+    loop {
+        let jobs = jobs::list()?;
+        let processes = snapshot()?;
+        let now = Instant::now();
+        let cpu = |process: &Process| -> Option<f64> {
+            let (at, previous) = previous.as_ref()?;
+            let before = previous.get(&process.pid)?;
+            let elapsed = now.duration_since(*at).as_secs_f64();
+            let ticks = process.cpu_ticks.saturating_sub(before.cpu_ticks) as f64;
+            Some(ticks / CLOCK_TICKS / elapsed * 100.0)
+        };
+
+        let mut frame = String::new();
+        let mut pids = vec![];
+        writeln!(frame)?;
+        writeln!(frame, "  {}", "RUNNING TASKS".stylize().grey())?;
+        writeln!(frame)?;
+        if jobs.is_empty() {
+            writeln!(frame, "    {}", "No tasks are running".stylize().bold())?;
+        }
+        for job in &jobs {
+            let running = state::now().saturating_sub(job.started_at) * 1000;
+            writeln!(
+                frame,
+                "  {} ({}) in {}, {}",
+                job.name.as_str().stylize().bold(),
+                job.path,
+                job.project.display(),
+                insights::format_duration(running)
+            )?;
+            for (depth, process) in tree(job.pid, &processes) {
+                let marker = match pids.len() == selected {
+                    true => "→".stylize().green(),
+                    false => " ".stylize(),
+                };
+                let cpu = cpu(process)
+                    .map(|cpu| format!("{:5.1}%", cpu))
+                    .unwrap_or("     -".to_string());
+                writeln!(
+                    frame,
+                    "  {} {:>7} {} {:>6} {}{}",
+                    marker,
+                    process.pid,
+                    cpu,
+                    capture::format_size(process.rss),
+                    "  ".repeat(depth),
+                    process.cmdline
+                )?;
+                pids.push(process.pid);
+            }
+            writeln!(frame)?;
+        }
+        if let Some(message) = message.take() {
+            writeln!(frame, "  {}", message)?;
+            writeln!(frame)?;
+        }
+        writeln!(
+            frame,
+            "  {} → select, {} → terminate, {} → kill, {} → quit",
+            "<Up>/<Down>".stylize().red(),
+            "t".stylize().red(),
+            "k".stylize().red(),
+            "q".stylize().red()
+        )?;
+        ui.draw(&frame)?;
+        previous = Some((now, processes));
+
+        let Some(event) = ui.poll_key_event(REFRESH_INTERVAL)? else {
+            continue;
+        };
+        match event.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if event.modifiers == KeyModifiers::CONTROL => return Ok(()),
+            KeyCode::Up => selected = selected.saturating_sub(1),
+            KeyCode::Down => selected = (selected + 1).min(pids.len().saturating_sub(1)),
+            KeyCode::Char(key @ ('t' | 'k')) => {
+                if let Some(pid) = pids.get(selected) {
+                    let signal = if key == 't' { "TERM" } else { "KILL" };
+                    message = Some(match signal_process(*pid, signal) {
+                        Ok(()) => format!("Sent SIG{} to {}", signal, pid),
+                        Err(e) => format!("{}", format!("{:#}", e).red()),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn signal_process(pid: u32, signal: &str) -> Result<()> {
+    let status = Command::new("kill")
+        .arg(format!("-{}", signal))
+        .arg(pid.to_string())
+        .status()
+        .context("Unable to run kill")?;
+    if !status.success() {
+        bail!("Unable to send SIG{} to {}", signal, pid);
+    }
+    Ok(())
+}
+
+/// Processes of the system by pid. Processes exited while reading are skipped
+fn snapshot() -> Result<HashMap<u32, Process>> {
+    let mut processes = HashMap::new();
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse().ok()) else {
+            continue;
+        };
+        let dir = entry.path();
+        let (Ok(stat), Ok(status), Ok(cmdline)) = (
+            fs::read_to_string(dir.join("stat")),
+            fs::read_to_string(dir.join("status")),
+            fs::read(dir.join("cmdline")),
+        ) else {
+            continue;
+        };
+        if let Some(process) = parse(pid, &stat, &status, &cmdline) {
+            processes.insert(pid, process);
+        }
+    }
+    Ok(processes)
+}
+
+fn parse(pid: u32, stat: &str, status: &str, cmdline: &[u8]) -> Option<Process> {
+    let (name, fields) = stat_fields(stat)?;
+    let name = name.to_string();
+    let ppid = fields.get(1)?.parse().ok()?;
+    let utime = fields.get(11)?.parse::<u64>().ok()?;
+    let stime = fields.get(12)?.parse::<u64>().ok()?;
+    let rss_kb = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))
+        .and_then(|v| v.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    let cmdline = String::from_utf8_lossy(cmdline)
+        .split('\0')
+        .filter(|a| !a.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(Process {
+        pid,
+        ppid,
+        cmdline: if cmdline.is_empty() {
+            format!("[{}]", name)
+        } else {
+            cmdline
+        },
+        name,
+        cpu_ticks: utime + stime,
+        rss: rss_kb * 1024,
+    })
+}
+
+/// Number of the running descendants of the process
+pub fn count_descendants(pid: u32) -> Result<usize> {
+    Ok(descendants(pid)?.len())
+}
+
+/// Pids of the running descendants of the process
+pub fn descendants(pid: u32) -> Result<Vec<u32>> {
+    let mut parents: Vec<(u32, u32)> = vec![];
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let Some(child) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        if let Some(ppid) = stat_fields(&stat).and_then(|(_, f)| f.get(1)?.parse().ok()) {
+            parents.push((child, ppid));
+        }
+    }
+    let mut descendants = vec![];
+    let mut stack = vec![pid];
+    while let Some(parent) = stack.pop() {
+        let children = parents
+            .iter()
+            .filter(|(_, p)| *p == parent)
+            .map(|(c, _)| *c);
+        let before = stack.len();
+        stack.extend(children);
+        descendants.extend_from_slice(&stack[before..]);
+    }
+    Ok(descendants)
+}
+
+/// Name of the process and the fields of `/proc/<pid>/stat` after it (starting from the 3rd
+/// one, state)
+fn stat_fields(stat: &str) -> Option<(&str, Vec<&str>)> {
+    // Name of the process is in parentheses and can contain spaces and parentheses itself
+    let name_start = stat.find('(')?;
+    let name_end = stat.rfind(')')?;
+    let name = &stat[name_start + 1..name_end];
+    Some((name, stat[name_end + 1..].split_whitespace().collect()))
+}
+
+/// Process with the given pid and all its descendants with their depth in the tree
+fn tree(pid: u32, processes: &HashMap<u32, Process>) -> Vec<(usize, &Process)> {
+    let mut result = vec![];
+    let Some(root) = processes.get(&pid) else {
+        return result;
+    };
+    let mut stack = vec![(0, root)];
+    while let Some((depth, process)) = stack.pop() {
+        result.push((depth, process));
+        let mut children = processes
+            .values()
+            .filter(|p| p.ppid == process.pid)
+            .collect::<Vec<_>>();
+        // Children are popped from the stack in the reverse order
+        children.sort_by_key(|p| std::cmp::Reverse(p.pid));
+        stack.extend(children.into_iter().map(|c| (depth + 1, c)));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_parse() {
+        let stat = "42 (cargo (build)) S 1 42 42 0 -1 4194560 100 0 0 0 250 50 0 0 20 0 1 0";
+        let status = "Name:\tcargo\nVmRSS:\t    2048 kB\n";
+        let process = parse(42, stat, status, b"cargo\0build\0").unwrap();
+        assert_eq!("cargo (build)", process.name);
+        assert_eq!(1, process.ppid);
+        assert_eq!(300, process.cpu_ticks);
+        assert_eq!(2 * 1024 * 1024, process.rss);
+        assert_eq!("cargo build", process.cmdline);
+    }
+
+    #[test]
+    fn check_tree() {
+        let process = |pid, ppid| Process {
+            pid,
+            ppid,
+            name: String::new(),
+            cmdline: String::new(),
+            cpu_ticks: 0,
+            rss: 0,
+        };
+        let processes = [process(1, 0), process(2, 1), process(3, 2), process(4, 1)]
+            .into_iter()
+            .map(|p| (p.pid, p))
+            .collect::<HashMap<_, _>>();
+        let tree = tree(1, &processes)
+            .into_iter()
+            .map(|(depth, p)| (depth, p.pid))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(0, 1), (1, 2), (2, 3), (1, 4)], tree);
+    }
+}
@@ -0,0 +1,87 @@
+//! Running the task on a remote host over SSH (`ssh: deploy@staging`).
+//!
+//! The host is passed to `ssh` as is, so the aliases of `~/.ssh/config` (`host: staging`) work
+//! as well. The command is run by `sh` on the host in the remote directory of the task with the
+//! environment variables of the task. When ttr runs in the terminal, a pseudo-terminal is
+//! allocated on the host, so the output is streamed as it is produced and Ctrl-C interrupts the
+//! remote command rather than just the connection.
+use crate::template::quote;
+use std::io::{stdin, stdout, IsTerminal};
+
+/// Host the task is run on
+pub struct Remote<'a> {
+    pub host: &'a str,
+    /// Directory of the command on the host, the home directory if not given
+    pub dir: Option<&'a str>,
+    /// Environment variables set for the command
+    pub env: &'a [(String, String)],
+}
+
+impl Remote<'_> {
+    /// `ssh` invocation running the shell command on the host
+    pub fn wrap(&self, cmd: &str) -> (String, Vec<String>) {
+        let tty = stdin().is_terminal() && stdout().is_terminal();
+        ("ssh".to_string(), self.ssh_args(tty, cmd))
+    }
+
+    fn ssh_args(&self, tty: bool, cmd: &str) -> Vec<String> {
+        let mut args = vec![];
+        if tty {
+            args.push("-t".to_string());
+        }
+        args.push("--".to_string());
+        args.push(self.host.to_string());
+        args.push(self.remote_command(cmd));
+        args
+    }
+
+    /// Command line interpreted by the login shell of the user on the host
+    fn remote_command(&self, cmd: &str) -> String {
+        let mut words = vec![];
+        if let Some(dir) = self.dir {
+            // Home directory is expanded by the remote shell
+            let dir = match dir.strip_prefix("~/") {
+                Some(rest) => format!("~/{}", quote(rest)),
+                None => quote(dir),
+            };
+            words.extend(["cd".to_string(), dir, "&&".to_string()]);
+        }
+        words.push("exec".to_string());
+        if !self.env.is_empty() {
+            words.push("env".to_string());
+            let vars = self.env.iter().map(|(k, v)| quote(&format!("{}={}", k, v)));
+            words.extend(vars);
+        }
+        words.extend(["sh".to_string(), "-c".to_string(), quote(cmd)]);
+        words.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_ssh_args() {
+        let env = [("GREETING".to_string(), "hello world".to_string())];
+        let remote = Remote {
+            host: "deploy@staging",
+            dir: Some("~/app current"),
+            env: &env,
+        };
+        let args = remote.ssh_args(true, "systemctl restart 'app'");
+        assert_eq!(["-t", "--", "deploy@staging"], args[..3]);
+        assert_eq!(
+            r#"cd ~/'app current' && exec env 'GREETING=hello world' sh -c 'systemctl restart '\''app'\'''"#,
+            args[3]
+        );
+
+        let remote = Remote {
+            host: "staging",
+            dir: None,
+            env: &[],
+        };
+        let args = remote.ssh_args(false, "uptime");
+        assert_eq!(["--", "staging", "exec sh -c uptime"], args[..]);
+    }
+}
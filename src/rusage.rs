@@ -0,0 +1,160 @@
+//! Resources used by the task: peak memory and CPU time of its processes (`wait4`) and the
+//! maximum number of processes it spawned.
+use crate::capture;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, io,
+    process::{Child, ExitStatus},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    /// Peak resident set size of the largest process in bytes
+    pub max_rss: u64,
+    pub user_ms: u64,
+    pub system_ms: u64,
+    /// Maximum number of descendant processes running at the same time. Only counted on Linux
+    #[serde(default)]
+    pub max_children: Option<usize>,
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} peak memory, {:.1}s user, {:.1}s system",
+            capture::format_size(self.max_rss),
+            self.user_ms as f64 / 1000.0,
+            self.system_ms as f64 / 1000.0
+        )?;
+        match self.max_children {
+            Some(1) => write!(f, ", 1 child process"),
+            Some(n) => write!(f, ", {} child processes", n),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Waits for the task process to exit. Resource usage is only reported on Unix
+pub fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<Usage>)> {
+    #[cfg(unix)]
+    {
+        let children = children::Counter::start(child.id());
+        let (status, mut usage) = wait4(child)?;
+        usage.max_children = children.stop();
+        Ok((status, Some(usage)))
+    }
+    #[cfg(not(unix))]
+    {
+        Ok((child.wait()?, None))
+    }
+}
+
+#[cfg(unix)]
+fn wait4(child: &Child) -> io::Result<(ExitStatus, Usage)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    // SAFETY: rusage is a plain C struct, all zeroes is a valid value
+    let mut rusage = unsafe { std::mem::zeroed::<libc::rusage>() };
+    loop {
+        // SAFETY: pointers are valid for the duration of the call
+        let pid = unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, 0, &mut rusage) };
+        if pid >= 0 {
+            break;
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+    // ru_maxrss is in bytes on macOS and in kilobytes elsewhere
+    let rss_unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+    let millis = |t: libc::timeval| t.tv_sec as u64 * 1000 + t.tv_usec as u64 / 1000;
+    let usage = Usage {
+        max_rss: rusage.ru_maxrss as u64 * rss_unit,
+        user_ms: millis(rusage.ru_utime),
+        system_ms: millis(rusage.ru_stime),
+        max_children: None,
+    };
+    Ok((ExitStatus::from_raw(status), usage))
+}
+
+#[cfg(unix)]
+mod children {
+    use crate::proctree;
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+    /// Periodically counts the descendants of the process in the background
+    pub struct Counter {
+        max: Arc<AtomicUsize>,
+        stopped: Arc<AtomicBool>,
+    }
+
+    impl Counter {
+        pub fn start(pid: u32) -> Self {
+            let max = Arc::new(AtomicUsize::new(0));
+            let stopped = Arc::new(AtomicBool::new(false));
+            if cfg!(target_os = "linux") {
+                let (max, stopped) = (max.clone(), stopped.clone());
+                thread::spawn(move || {
+                    while !stopped.load(Ordering::Relaxed) {
+                        if let Ok(count) = proctree::count_descendants(pid) {
+                            max.fetch_max(count, Ordering::Relaxed);
+                        }
+                        thread::sleep(SAMPLE_INTERVAL);
+                    }
+                });
+            }
+            Self { max, stopped }
+        }
+
+        /// Stops counting. Returns the maximum number of the descendants seen
+        pub fn stop(self) -> Option<usize> {
+            self.stopped.store(true, Ordering::Relaxed);
+            cfg!(target_os = "linux").then(|| self.max.load(Ordering::Relaxed))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_display() {
+        let usage = Usage {
+            max_rss: 8 * 1024 * 1024 * 1024,
+            user_ms: 12345,
+            system_ms: 500,
+            max_children: Some(3),
+        };
+        assert_eq!(
+            "8.0G peak memory, 12.3s user, 0.5s system, 3 child processes",
+            usage.to_string()
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn check_wait() {
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "sleep 0.5 & sleep 0.5; wait"])
+            .spawn()
+            .unwrap();
+        let (status, usage) = wait(&mut child).unwrap();
+        assert!(status.success());
+        let usage = usage.unwrap();
+        assert!(usage.max_rss > 0);
+        assert_eq!(Some(2), usage.max_children);
+    }
+}
@@ -0,0 +1,267 @@
+//! Isolation of the task from the network (`network: none`) and from the working directory
+//! (`sandbox_dir: temp|overlay`).
+//!
+//! On Linux the task without network is run in a new network namespace with only the loopback
+//! interface using `unshare` (requires unprivileged user namespaces). On macOS the network is
+//! denied by `sandbox-exec`. Tasks without network are refused on other platforms rather than
+//! being run with the network enabled.
+//!
+//! Sandboxed working directory is either a temporary copy of it (`temp`) or, on Linux, an
+//! overlay mounted over it in a separate mount namespace (`overlay`), so only the changed files
+//! are written to a temporary directory. Changes made in the sandbox are reported and discarded.
+use crate::{changes, changes::Change, temp, Invocation, Result};
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    /// Network of the host
+    #[default]
+    Host,
+    /// No network access
+    None,
+}
+
+/// Sandbox profile of `sandbox-exec` denying all network access
+const MACOS_PROFILE: &str = "(version 1) (allow default) (deny network*)";
+
+/// Wraps the program, so it is run with the given network access
+pub fn wrap(network: Network, program: String, args: Vec<String>) -> Result<(String, Vec<String>)> {
+    if network == Network::Host {
+        return Ok((program, args));
+    }
+    let sandbox: &[&str] = if cfg!(target_os = "linux") {
+        &["unshare", "--map-current-user", "--net", "--"]
+    } else if cfg!(target_os = "macos") {
+        &["sandbox-exec", "-p", MACOS_PROFILE]
+    } else {
+        bail!("Running tasks without network is not supported on this platform");
+    };
+    let wrapped = sandbox[1..]
+        .iter()
+        .map(|arg| arg.to_string())
+        .chain(std::iter::once(program))
+        .chain(args)
+        .collect();
+    Ok((sandbox[0].to_string(), wrapped))
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxDir {
+    /// Temporary copy of the working directory
+    Temp,
+    /// Overlay over the working directory keeping the changes in a temporary directory
+    Overlay,
+}
+
+/// Sandboxed working directory of a single task run. Temporary files are removed when dropped
+pub struct Sandbox {
+    kind: SandboxDir,
+    /// Temporary directory with all the files of the sandbox
+    root: PathBuf,
+    /// Directory the task is run in
+    dir: PathBuf,
+    /// Working directory of the task
+    original: PathBuf,
+    /// Files of the copied working directory before the task is run (`temp` sandbox)
+    before: Option<changes::Snapshot>,
+}
+
+impl Sandbox {
+    pub fn create(kind: SandboxDir, working_dir: &Path) -> Result<Self> {
+        // The copy can contain secrets (eg. `.env`), so it is kept in a private directory
+        let root = temp::create_dir("sandbox").context("Unable to create sandbox directory")?;
+        let mut sandbox = Self {
+            kind,
+            root: root.clone(),
+            dir: working_dir.to_path_buf(),
+            original: working_dir.to_path_buf(),
+            before: None,
+        };
+        match kind {
+            SandboxDir::Temp => {
+                sandbox.dir = root.join("copy");
+                copy_dir(working_dir, &sandbox.dir)
+                    .with_context(|| format!("Unable to copy {}", working_dir.display()))?;
+                sandbox.before = Some(changes::Snapshot::take_dir(&sandbox.dir)?);
+            }
+            SandboxDir::Overlay => {
+                if !cfg!(target_os = "linux") {
+                    bail!("Overlay sandbox is only supported on Linux");
+                }
+                fs::create_dir_all(root.join("upper"))?;
+                fs::create_dir_all(root.join("work"))?;
+            }
+        }
+        Ok(sandbox)
+    }
+
+    /// Directory the task is run in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Wraps the invocation, so the overlay is mounted before the task is run
+    pub fn wrap(&self, mut invocation: Invocation) -> Invocation {
+        if self.kind == SandboxDir::Temp {
+            return invocation;
+        }
+        // Mounting requires root in the user namespace. The task is run in the overlay mounted
+        // over the working directory, so the paths stay the same
+        const SCRIPT: &str = r#"mount -t overlay overlay -o "lowerdir=$1,upperdir=$2,workdir=$3" "$1" && cd "$1" && shift 3 && exec "$@""#;
+        let mut args = ["--map-root-user", "--mount", "--", "sh", "-c", SCRIPT, "sh"]
+            .into_iter()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        for dir in [&self.dir, &self.root.join("upper"), &self.root.join("work")] {
+            args.push(dir.to_string_lossy().to_string());
+        }
+        args.push(invocation.program);
+        args.append(&mut invocation.args);
+        invocation.program = "unshare".to_string();
+        invocation.args = args;
+        invocation
+    }
+
+    /// Changes made by the task with the paths in the original working directory
+    pub fn changes(&self) -> Result<Vec<Change>> {
+        let mut changes = match &self.before {
+            Some(before) => before.diff(&changes::Snapshot::take_dir(&self.dir)?),
+            None => overlay_changes(&self.root.join("upper"), &self.original)?,
+        };
+        for change in &mut changes {
+            let (Change::Created(path) | Change::Modified(path) | Change::Removed(path)) = change;
+            if let Ok(relative) = path.strip_prefix(&self.dir) {
+                *path = self.original.join(relative);
+            }
+        }
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        Ok(changes)
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        // Overlay leaves the directory without permissions in the work dir
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let work = self.root.join("work").join("work");
+            let _ = fs::set_permissions(work, fs::Permissions::from_mode(0o700));
+        }
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, &target)?;
+            #[cfg(not(unix))]
+            fs::copy(entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Changes recorded in the upper directory of the overlay. Removed files are marked with
+/// whiteouts (character devices with 0/0 device number)
+fn overlay_changes(upper: &Path, lower: &Path) -> Result<Vec<Change>> {
+    let mut changes = vec![];
+    for entry in fs::read_dir(upper)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let original = lower.join(entry.file_name());
+        if file_type.is_dir() {
+            changes.extend(overlay_changes(&entry.path(), &original)?);
+        } else if is_whiteout(&entry)? {
+            changes.push(Change::Removed(original));
+        } else if original.exists() {
+            changes.push(Change::Modified(original));
+        } else {
+            changes.push(Change::Created(original));
+        }
+    }
+    Ok(changes)
+}
+
+#[cfg(unix)]
+fn is_whiteout(entry: &fs::DirEntry) -> Result<bool> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    let metadata = entry.metadata()?;
+    Ok(metadata.file_type().is_char_device() && metadata.rdev() == 0)
+}
+
+#[cfg(not(unix))]
+fn is_whiteout(_: &fs::DirEntry) -> Result<bool> {
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn check_wrap() {
+        let args = vec!["-c".to_string(), "make".to_string()];
+        let (program, wrapped) = wrap(Network::Host, "sh".to_string(), args.clone()).unwrap();
+        assert_eq!(("sh", &args), (program.as_str(), &wrapped));
+
+        #[cfg(target_os = "linux")]
+        {
+            let (program, wrapped) = wrap(Network::None, "sh".to_string(), args).unwrap();
+            assert_eq!("unshare", program);
+            assert_eq!(
+                vec!["--map-current-user", "--net", "--", "sh", "-c", "make"],
+                wrapped
+            );
+        }
+    }
+
+    #[test]
+    fn check_temp_sandbox() {
+        let dir = env::temp_dir().join(format!("ttr-sandbox-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.c"), "int main;").unwrap();
+        fs::write(dir.join("build.log"), "old").unwrap();
+
+        let sandbox = Sandbox::create(SandboxDir::Temp, &dir).unwrap();
+        fs::remove_file(sandbox.dir().join("build.log")).unwrap();
+        fs::write(sandbox.dir().join("src/main.o"), "").unwrap();
+        let changes = sandbox.changes().unwrap();
+        assert_eq!(
+            vec![
+                Change::Removed(dir.join("build.log")),
+                Change::Created(dir.join("src/main.o"))
+            ],
+            changes
+        );
+        let root = sandbox.root.clone();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&root).unwrap().permissions().mode();
+            assert_eq!(0o700, mode & 0o777);
+        }
+        drop(sandbox);
+        assert!(!root.exists());
+        assert!(dir.join("build.log").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+//! Incremental search of tasks across all the groups (`/` in the selector).
+//!
+//! Tasks are ranked by how well their labels fuzzy match the query, the matched characters are
+//! highlighted.
+use crate::{ui::Ui, Group, Result, Task};
+use crossterm::{
+    event::{KeyCode, KeyModifiers},
+    style::Stylize,
+};
+use nucleo_matcher::{
+    pattern::{CaseMatching, Normalization, Pattern},
+    Config, Matcher, Utf32Str,
+};
+use std::{cmp::Reverse, fmt::Write};
+
+/// Task with the names of the groups it is nested in (`cargo → test`)
+pub struct Candidate<'a> {
+    pub task: &'a Task,
+    pub label: String,
+}
+
+/// Shows the search screen. Returns the chosen task or `None` if the search was cancelled
+pub fn select<'a>(ui: &mut Ui, root: &'a Group) -> Result<Option<&'a Task>> {
+    let candidates = candidates(root);
+    let mut query = String::new();
+    let mut selected = 0;
+    loop {
+        let matches = rank(&query, &candidates, |c| &c.label);
+        selected = selected.min(matches.len().saturating_sub(1));
+
+        let (_, height) = ui.size()?;
+        // Header, query and the help take 6 lines
+        let visible = (height as usize).saturating_sub(6).max(1);
+        let first = selected.saturating_sub(visible - 1);
+        let mut frame = String::new();
+        writeln!(frame)?;
+        writeln!(frame, "  {} {}▏", "SEARCH".stylize().grey(), query)?;
+        writeln!(frame)?;
+        for (i, m) in matches.iter().enumerate().skip(first).take(visible) {
+            let path = format!("{:6}", m.item.task.path);
+            let label = highlight(&m.item.label, &m.positions);
+            match i == selected {
+                true => writeln!(
+                    frame,
+                    "  {} {} {}",
+                    "→".stylize().green(),
+                    path.stylize().green(),
+                    label.bold()
+                )?,
+                false => writeln!(frame, "    {} {}", path.stylize().green(), label)?,
+            }
+        }
+        if matches.is_empty() {
+            writeln!(frame, "    {}", "No matching tasks".stylize().grey())?;
+        }
+        writeln!(frame)?;
+        writeln!(
+            frame,
+            "  {} → run, {} → select, {} → back",
+            "<Enter>".stylize().red(),
+            "<Up>/<Down>".stylize().red(),
+            "<Esc>".stylize().red()
+        )?;
+        ui.draw(&frame)?;
+
+        let event = ui.next_key_event()?;
+        match event.code {
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if event.modifiers == KeyModifiers::CONTROL => return Ok(None),
+            KeyCode::Enter => {
+                if let Some(m) = matches.get(selected) {
+                    return Ok(Some(m.item.task));
+                }
+            }
+            KeyCode::Up => selected = selected.saturating_sub(1),
+            KeyCode::Down => selected += 1,
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Char(ch) => {
+                query.push(ch);
+                selected = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// All the tasks of the tree labeled with the names of their groups. Shared by the selectors
+pub fn candidates(root: &Group) -> Vec<Candidate<'_>> {
+    let mut candidates = vec![];
+    collect(root, "", &mut candidates);
+    candidates
+}
+
+fn collect<'a>(group: &'a Group, prefix: &str, candidates: &mut Vec<Candidate<'a>>) {
+    for task in &group.tasks {
+        let label = format!("{}{}", prefix, task.name);
+        candidates.push(Candidate { task, label });
+    }
+    for child in &group.groups {
+        collect(child, &format!("{}{} → ", prefix, child.name), candidates);
+    }
+}
+
+/// Item matching the query with the positions of the matched characters in its text
+pub struct Match<'a, T> {
+    pub item: &'a T,
+    pub positions: Vec<usize>,
+}
+
+/// Items matching the query, the best matches first. Items matching equally well keep their
+/// order, so all of them are listed in order for an empty query.
+///
+/// Characters of each word of the query are found in the text in the same order, ignoring
+/// case (`ct` matches `cargo → test`). Words prefixed with `'` are matched exactly, with `^`
+/// as a prefix and with `!` excluded
+pub fn rank<'a, T>(query: &str, items: &'a [T], text: impl Fn(&T) -> &str) -> Vec<Match<'a, T>> {
+    let pattern = Pattern::parse(query, CaseMatching::Ignore, Normalization::Smart);
+    let mut matcher = Matcher::new(Config::DEFAULT);
+    let mut buf = vec![];
+    let mut matches = items
+        .iter()
+        .filter_map(|item| {
+            let mut positions = vec![];
+            let text = Utf32Str::new(text(item), &mut buf);
+            let score = pattern.indices(text, &mut matcher, &mut positions)?;
+            positions.sort_unstable();
+            positions.dedup();
+            let positions = positions.into_iter().map(|p| p as usize).collect();
+            Some((score, Match { item, positions }))
+        })
+        .collect::<Vec<_>>();
+    matches.sort_by_key(|(score, _)| Reverse(*score));
+    matches.into_iter().map(|(_, m)| m).collect()
+}
+
+/// Text with the matched characters highlighted
+pub fn highlight(text: &str, positions: &[usize]) -> String {
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| match positions.binary_search(&i) {
+            Ok(_) => c.stylize().yellow().to_string(),
+            Err(_) => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_rank() {
+        let items = ["cargo → integration tests", "cargo → test", "git → status"];
+        let rank = |query: &str| {
+            let matches = rank(query, &items, |item| item);
+            matches.iter().map(|m| *m.item).collect::<Vec<_>>()
+        };
+        assert_eq!(items.to_vec(), rank(""));
+        assert_eq!(
+            vec!["cargo → test", "cargo → integration tests"],
+            rank("ct")
+        );
+        assert_eq!(vec!["cargo → test"], rank("Car test !integration"));
+        assert_eq!(Vec::<&str>::new(), rank("tc"));
+
+        let matches = super::rank("gs", &items, |item| item);
+        assert_eq!(vec![0, 6], matches[0].positions);
+    }
+}
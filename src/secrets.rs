@@ -0,0 +1,131 @@
+//! Secrets asked before the task is run (`secrets: [API_TOKEN]`).
+//!
+//! Credentials don't belong in the config or in the shell history, so the values are typed in
+//! with masked input and passed to the task as environment variables. They are kept in memory
+//! for the rest of the session, so the task can be run again without retyping them, and are
+//! never written to the state of ttr. Secrets already set in the environment of ttr are not
+//! asked for.
+//!
+//! Values are passed only through the environment of the process and never on the command line,
+//! where they are visible to other users. Tasks run over SSH or launched in multiplexers and
+//! other terminals can't have secrets, containers get them by name from the engine environment.
+use crate::Result;
+use anyhow::bail;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal,
+};
+use std::{
+    collections::BTreeMap,
+    env,
+    io::{stderr, stdin, IsTerminal, Write},
+    sync::Mutex,
+};
+
+/// Values typed in during the session
+static VALUES: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+fn value(name: &str) -> Option<String> {
+    VALUES
+        .lock()
+        .unwrap()
+        .get(name)
+        .cloned()
+        .or_else(|| env::var(name).ok().filter(|v| !v.is_empty()))
+}
+
+/// Remembers the value typed in for the rest of the session
+pub fn set(name: &str, value: String) {
+    VALUES.lock().unwrap().insert(name.to_string(), value);
+}
+
+/// Secrets neither typed in nor set in the environment
+pub fn missing(names: &[String]) -> Vec<&str> {
+    names
+        .iter()
+        .map(String::as_str)
+        .filter(|name| value(name).is_none())
+        .collect()
+}
+
+/// Environment variables of the secrets known so far
+pub fn env(names: &[String]) -> Vec<(String, String)> {
+    names
+        .iter()
+        .filter_map(|name| Some((name.clone(), value(name)?)))
+        .collect()
+}
+
+/// Asks for the missing secrets in the terminal without the selector. Returns false if the user
+/// cancelled
+pub fn ask(task: &str, names: &[String]) -> Result<bool> {
+    let missing = missing(names);
+    if missing.is_empty() {
+        return Ok(true);
+    }
+    if !stdin().is_terminal() {
+        bail!(
+            "Task {} needs secret {}, set it in the environment",
+            task,
+            missing.join(", ")
+        );
+    }
+    for name in missing {
+        eprint!("{}: ", name);
+        stderr().flush()?;
+        terminal::enable_raw_mode()?;
+        let value = read_hidden();
+        terminal::disable_raw_mode()?;
+        eprintln!();
+        match value? {
+            Some(value) => set(name, value),
+            None => return Ok(false),
+        }
+    }
+    Ok(true)
+}
+
+/// Reads the line without echoing it. `None` if it is cancelled with Esc or Ctrl-C
+fn read_hidden() -> Result<Option<String>> {
+    let mut input = String::new();
+    loop {
+        let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event::read()?
+        else {
+            continue;
+        };
+        match code {
+            KeyCode::Enter => return Ok(Some(input)),
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Char(ch) => input.push(ch),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_values() {
+        let names = vec!["TTR_TEST_SECRET".to_string(), "PATH".to_string()];
+        assert_eq!(vec!["TTR_TEST_SECRET"], missing(&names));
+        set("TTR_TEST_SECRET", "s3cret".to_string());
+        assert!(missing(&names).is_empty());
+        let env = env(&names);
+        assert_eq!(
+            ("TTR_TEST_SECRET".to_string(), "s3cret".to_string()),
+            env[0]
+        );
+        assert_eq!("PATH", env[1].0);
+    }
+}
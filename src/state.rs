@@ -0,0 +1,543 @@
+//! Persistent state shared between ttr runs (history, pins, prompt answers etc.)
+//!
+//! State is scoped by project root (see [`project_root()`]), so history of one repository
+//! doesn't leak into another. State is kept in a single JSON file with a version header. When
+//! the file was created by an older version of ttr it is migrated on load. Writes are atomic:
+//! the state is written to a temporary file which is renamed over the original one. Updates are
+//! serialized between concurrently running instances using a lock file next to the state file.
+use crate::{audit, rusage::Usage, temp, Result, TTR_CONFIG};
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+/// Current version of the state schema
+const VERSION: u64 = 2;
+
+/// Migrations of the state schema. Migration `i` converts state of version `i + 1` to version
+/// `i + 2`. Version 1 is the first one written
+const MIGRATIONS: [fn(&mut Value); VERSION as usize - 1] = [migrate_v1];
+
+/// Maximum number of history entries kept in the state
+const HISTORY_LIMIT: usize = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct State {
+    version: u64,
+    /// State of the projects by project root
+    #[serde(default)]
+    pub projects: HashMap<PathBuf, ProjectState>,
+    /// Fingerprints of the trusted project configs by config path
+    #[serde(default)]
+    pub trusted: HashMap<PathBuf, String>,
+}
+
+impl State {
+    /// Removes the state of the project or all the projects if `project` is `None`. Trusted
+    /// configs are kept
+    fn clear(&mut self, project: Option<&Path>) {
+        match project {
+            Some(project) => {
+                self.projects.remove(project);
+            }
+            None => self.projects.clear(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct ProjectState {
+    /// Runs of the tasks, the most recent last
+    #[serde(default)]
+    pub history: Vec<RunRecord>,
+    /// Key paths of pinned tasks
+    #[serde(default)]
+    pub pins: Vec<String>,
+    /// Last run of each task by key path
+    #[serde(default)]
+    pub last_run: HashMap<String, RunRecord>,
+    /// Last answers given to task prompts by key path and prompt name
+    #[serde(default)]
+    pub prompts: HashMap<String, HashMap<String, String>>,
+    /// Hash of the task inputs at the last successful run by key path
+    #[serde(default)]
+    pub inputs_hashes: HashMap<String, String>,
+    /// Key path of the group the selector was left in (`remember_position: true`)
+    #[serde(default)]
+    pub position: Option<String>,
+    /// Time it takes to type the key path of each task in the selector
+    #[serde(default)]
+    pub key_timings: HashMap<String, KeyTiming>,
+}
+
+/// How fast the key path of the task is typed in the selector (training statistics)
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct KeyTiming {
+    pub count: u32,
+    pub total_ms: u64,
+    pub best_ms: u64,
+}
+
+impl KeyTiming {
+    pub fn average_ms(&self) -> u64 {
+        self.total_ms / u64::from(self.count.max(1))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunRecord {
+    /// Key path of the task from the root group (eg. `gd`)
+    pub path: String,
+    pub name: String,
+    /// Unix timestamp of the task start
+    pub started_at: u64,
+    pub duration_ms: u64,
+    /// Exit code of the process. `None` if process was terminated by a signal
+    pub exit_code: Option<i32>,
+    /// Outcome according to the exit codes mapping of the task. Missing in the records made
+    /// by older versions
+    #[serde(default)]
+    pub outcome: Option<Outcome>,
+    /// Resources used by the task. Missing in the records made by older versions
+    #[serde(default)]
+    pub usage: Option<Usage>,
+    /// Task was terminated because it ran longer than its timeout
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+}
+
+/// Result of the task run. Outcomes are ordered from the best to the worst
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    /// Task succeeded with findings (eg. linter warnings)
+    Warning,
+    Failure,
+}
+
+impl RunRecord {
+    pub fn outcome(&self) -> Outcome {
+        self.outcome.unwrap_or(match self.exit_code {
+            Some(0) => Outcome::Success,
+            _ => Outcome::Failure,
+        })
+    }
+}
+
+impl ProjectState {
+    pub fn record_keys(&mut self, path: &str, ms: u64) {
+        let timing = self.key_timings.entry(path.to_string()).or_default();
+        timing.best_ms = if timing.count == 0 {
+            ms
+        } else {
+            timing.best_ms.min(ms)
+        };
+        timing.count += 1;
+        timing.total_ms += ms;
+    }
+
+    /// Key paths taking the longest to type on average, the slowest first
+    pub fn slowest_key_paths(&self, limit: usize) -> Vec<(&str, KeyTiming)> {
+        let mut timings = self
+            .key_timings
+            .iter()
+            .map(|(path, timing)| (path.as_str(), *timing))
+            .collect::<Vec<_>>();
+        timings.sort_by(|(a_path, a), (b_path, b)| {
+            b.average_ms().cmp(&a.average_ms()).then(a_path.cmp(b_path))
+        });
+        timings.truncate(limit);
+        timings
+    }
+
+    pub fn record_run(&mut self, record: RunRecord) {
+        self.last_run.insert(record.path.clone(), record.clone());
+        self.history.push(record);
+        if self.history.len() > HISTORY_LIMIT {
+            let excess = self.history.len() - HISTORY_LIMIT;
+            self.history.drain(..excess);
+        }
+    }
+}
+
+/// Location of the state file along with the reason it is not in the state directory
+struct Location {
+    file: PathBuf,
+    fallback: Option<String>,
+}
+
+fn location() -> &'static Location {
+    static LOCATION: OnceLock<Location> = OnceLock::new();
+    LOCATION.get_or_init(|| {
+        let dir = dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|dir| dir.join("ttr"));
+        if let Some(dir) = dir.filter(|dir| fs::create_dir_all(dir).is_ok()) {
+            return Location {
+                file: dir.join("state.json"),
+                fallback: None,
+            };
+        }
+        let shared = match audit::user() {
+            user if user.is_empty() => env::temp_dir().join("ttr-state"),
+            user => env::temp_dir().join(format!("ttr-state-{}", user)),
+        };
+        let (dir, fallback) = match fallback_dir(&shared) {
+            Ok(dir) if dir == shared => {
+                let reason = format!("state is kept in {} until reboot", dir.display());
+                (dir, reason)
+            }
+            Ok(dir) => {
+                let reason = format!(
+                    "{} is not private, state is kept in {} for the session",
+                    shared.display(),
+                    dir.display()
+                );
+                (dir, reason)
+            }
+            Err(e) => (shared, format!("state can't be kept: {}", e)),
+        };
+        warn!(dir = %dir.display(), fallback, "state directory is unavailable");
+        Location {
+            file: dir.join("state.json"),
+            fallback: Some(fallback),
+        }
+    })
+}
+
+/// Default location of the state file.
+///
+/// When the state directory can't be determined or created (eg. `$HOME` is not set in a
+/// container) the state is kept in the temporary directory, so it only lives until reboot
+pub fn state_file() -> PathBuf {
+    location().file.clone()
+}
+
+/// Why the state is kept in the temporary directory, if it is
+pub fn fallback() -> Option<&'static str> {
+    location().fallback.as_deref()
+}
+
+/// Directory of the state in the shared temporary directory.
+///
+/// The state keeps fingerprints of the trusted configs, so the directory is used only if it is
+/// private to the user. Otherwise it could have been created beforehand by someone else to
+/// trust their configs, and a new directory is created for the session instead
+fn fallback_dir(dir: &Path) -> io::Result<PathBuf> {
+    match temp::private_dir(dir) {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        result => return result.map(|_| dir.to_path_buf()),
+    }
+    if is_private(dir)? {
+        Ok(dir.to_path_buf())
+    } else {
+        temp::create_dir("state")
+    }
+}
+
+/// Returns true if the directory (not a symlink) is owned and accessible only by the user
+fn is_private(dir: &Path) -> io::Result<bool> {
+    let metadata = fs::symlink_metadata(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // SAFETY: getuid is always successful
+        let uid = unsafe { libc::getuid() };
+        Ok(metadata.is_dir() && metadata.uid() == uid && metadata.mode() & 0o077 == 0)
+    }
+    // Temporary directory is not shared between the users on Windows
+    #[cfg(not(unix))]
+    Ok(metadata.is_dir())
+}
+
+/// Finds root of the project `dir` belongs to.
+///
+/// It is the root of a git repository if `dir` is inside one, otherwise the nearest directory
+/// with ttr config. If none found `dir` itself is the project root.
+pub fn project_root(dir: &Path) -> PathBuf {
+    let git_root = dir.ancestors().find(|d| d.join(".git").exists());
+    let config_root = || dir.ancestors().find(|d| d.join(TTR_CONFIG).is_file());
+    git_root.or_else(config_root).unwrap_or(dir).to_path_buf()
+}
+
+/// Reads the state, applies `f` to the state of the project and atomically writes it back
+pub fn update<T>(project: &Path, f: impl FnOnce(&mut ProjectState) -> T) -> Result<T> {
+    update_all(|state| f(state.projects.entry(project.to_path_buf()).or_default()))
+}
+
+/// Reads the state of the project without modifying the state file
+pub fn read(project: &Path) -> Result<ProjectState> {
+    Ok(read_all()?.projects.remove(project).unwrap_or_default())
+}
+
+/// Reads the state of all the projects without modifying the state file
+pub fn read_all() -> Result<State> {
+    load(&state_file())
+}
+
+/// Removes the state of the project or all the projects if `project` is `None`
+pub fn clear(project: Option<&Path>) -> Result<()> {
+    update_all(|state| state.clear(project))
+}
+
+/// Reads the state, applies `f` to it and atomically writes it back
+pub fn update_all<T>(f: impl FnOnce(&mut State) -> T) -> Result<T> {
+    update_file(&state_file(), f)
+}
+
+/// Read-modify-write cycle is done under the exclusive lock, so concurrent ttr instances (eg. in
+/// different panes of the same project) don't lose each other's changes
+fn update_file<T>(path: &Path, f: impl FnOnce(&mut State) -> T) -> Result<T> {
+    let _lock = lock(path)?;
+    let mut state = load(path)?;
+    let result = f(&mut state);
+    save(path, &state)?;
+    Ok(result)
+}
+
+/// Takes the exclusive lock of the state file. Lock is released when returned file is closed.
+///
+/// Separate lock file is used, because the state file itself is replaced on every write
+fn lock(path: &Path) -> Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let lock_path = path.with_extension("lock");
+    let file = File::create(&lock_path)
+        .with_context(|| format!("Unable to create lock file: {}", lock_path.display()))?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Reads the state file. Returns empty state if the file doesn't exist yet
+pub fn load(path: &Path) -> Result<State> {
+    if !path.exists() {
+        return Ok(State {
+            version: VERSION,
+            ..State::default()
+        });
+    }
+    let context = || format!("Unable to read state file: {}", path.display());
+    let content = fs::read_to_string(path).with_context(context)?;
+    let value = serde_json::from_str(&content).with_context(context)?;
+    serde_json::from_value(migrate(value)?).with_context(context)
+}
+
+/// Atomically writes the state to the file
+pub fn save(path: &Path, state: &State) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension(format!("tmp.{}", std::process::id()));
+    let mut file = File::create(&tmp_path)?;
+    serde_json::to_writer_pretty(&mut file, state)?;
+    file.write_all(b"\n")?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn migrate(mut value: Value) -> Result<Value> {
+    let Some(version) = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .filter(|v| *v > 0)
+    else {
+        bail!("State file has no version");
+    };
+    if version > VERSION {
+        bail!(
+            "State file version {} is not supported. Please upgrade ttr",
+            version
+        );
+    }
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(&mut value);
+    }
+    value["version"] = json!(VERSION);
+    Ok(value)
+}
+
+/// Project scoping was introduced in version 2. Unscoped state is moved to the project ttr is
+/// run in, it is dropped if the current directory is unavailable
+fn migrate_v1(value: &mut Value) {
+    let mut project = value.take();
+    if let Some(project) = project.as_object_mut() {
+        project.remove("version");
+    }
+    *value = match env::current_dir() {
+        Ok(dir) => json!({ "projects": { project_root(&dir).to_string_lossy(): project } }),
+        Err(e) => {
+            warn!("dropping unscoped state: {}", e);
+            json!({})
+        }
+    };
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(path: &str) -> RunRecord {
+        RunRecord {
+            path: path.to_string(),
+            name: "test".to_string(),
+            started_at: 1,
+            duration_ms: 2,
+            exit_code: Some(0),
+            outcome: Some(Outcome::Success),
+            usage: None,
+            timed_out: false,
+        }
+    }
+
+    #[test]
+    fn check_save_and_load() {
+        let path = std::env::temp_dir()
+            .join(format!("ttr-state-{}", std::process::id()))
+            .join("state.json");
+        let mut state = load(&path).unwrap();
+        let project = PathBuf::from("/project");
+        let project_state = state.projects.entry(project.clone()).or_default();
+        project_state.record_run(record("gd"));
+        save(&path, &state).unwrap();
+
+        let loaded = load(&path).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(state, loaded);
+        let last_run = loaded.projects[&project].last_run.get("gd");
+        assert_eq!(Some(&record("gd")), last_run);
+    }
+
+    #[test]
+    fn check_concurrent_updates() {
+        let path = std::env::temp_dir()
+            .join(format!("ttr-state-concurrent-{}", std::process::id()))
+            .join("state.json");
+        let project = Path::new("/project");
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let path = &path;
+                scope.spawn(move || {
+                    update_file(path, |state| {
+                        let project_state = state.projects.entry(project.into()).or_default();
+                        project_state.record_run(record(&i.to_string()));
+                    })
+                    .unwrap()
+                });
+            }
+        });
+        let state = load(&path).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(8, state.projects[project].history.len());
+    }
+
+    #[test]
+    fn check_fallback_dir() {
+        let dir = std::env::temp_dir().join(format!("ttr-state-fallback-{}", std::process::id()));
+        assert_eq!(dir, fallback_dir(&dir).unwrap());
+        assert!(is_private(&dir).unwrap());
+        // Directory left from the previous run is reused
+        assert_eq!(dir, fallback_dir(&dir).unwrap());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&dir, fs::Permissions::from_mode(0o777)).unwrap();
+            let session_dir = fallback_dir(&dir).unwrap();
+            assert_ne!(dir, session_dir);
+            assert!(is_private(&session_dir).unwrap());
+            fs::remove_dir(session_dir).unwrap();
+
+            let link = dir.with_extension("link");
+            std::os::unix::fs::symlink(&dir, &link).unwrap();
+            let session_dir = fallback_dir(&link).unwrap();
+            assert_ne!(link, session_dir);
+            fs::remove_dir(session_dir).unwrap();
+            fs::remove_file(link).unwrap();
+        }
+        fs::remove_dir(dir).unwrap();
+    }
+
+    #[test]
+    fn check_project_root() {
+        let dir = std::env::temp_dir().join(format!("ttr-project-root-{}", std::process::id()));
+        let (repo, config) = (dir.join("repo"), dir.join("config"));
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("app/src")).unwrap();
+        // Config nested in the repository doesn't split it into projects
+        fs::write(repo.join("app").join(TTR_CONFIG), "").unwrap();
+        fs::create_dir_all(config.join("src")).unwrap();
+        fs::write(config.join(TTR_CONFIG), "").unwrap();
+        let plain = dir.join("plain");
+        fs::create_dir_all(&plain).unwrap();
+
+        assert_eq!(repo, project_root(&repo.join("app/src")));
+        assert_eq!(config, project_root(&config.join("src")));
+        assert_eq!(plain, project_root(&plain));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn check_clear() {
+        let (a, b) = (Path::new("/a"), Path::new("/b"));
+        let mut state = State::default();
+        state
+            .trusted
+            .insert("/a/.ttr.yaml".into(), "fingerprint".into());
+        for project in [a, b] {
+            let project_state = state.projects.entry(project.into()).or_default();
+            project_state.record_run(record("gd"));
+        }
+        state.clear(Some(a));
+        assert!(!state.projects.contains_key(a));
+        assert_eq!(1, state.projects[b].history.len());
+
+        state.clear(None);
+        assert!(state.projects.is_empty());
+        assert_eq!(1, state.trusted.len());
+    }
+
+    #[test]
+    fn check_migration() {
+        let v1 = json!({ "version": 1, "history": [record("a")] });
+        let state: State = serde_json::from_value(migrate(v1).unwrap()).unwrap();
+        assert_eq!(VERSION, state.version);
+        let project = project_root(&env::current_dir().unwrap());
+        assert_eq!(vec![record("a")], state.projects[&project].history);
+
+        assert!(migrate(json!({ "version": VERSION + 1 })).is_err());
+        assert!(migrate(json!([record("a")])).is_err());
+    }
+
+    #[test]
+    fn check_key_timings() {
+        let mut state = ProjectState::default();
+        state.record_keys("gd", 1200);
+        state.record_keys("gd", 800);
+        state.record_keys("ct", 400);
+        state.record_keys("b", 3000);
+        let slowest = state.slowest_key_paths(2);
+        let paths = slowest.iter().map(|(path, _)| *path).collect::<Vec<_>>();
+        assert_eq!(vec!["b", "gd"], paths);
+        let gd = state.key_timings["gd"];
+        assert_eq!((2, 1000, 800), (gd.count, gd.average_ms(), gd.best_ms));
+    }
+}
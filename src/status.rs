@@ -0,0 +1,97 @@
+//! Status file for prompts and status bars (`--status-file`).
+//!
+//! The file is a single JSON object describing the task running at the moment, the result of
+//! the last run and the number of tasks queued after the running one (remaining steps of a
+//! serial task or dependencies). It is rewritten atomically on every change, so readers never
+//! see a partially written file.
+use crate::{state::Outcome, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Status {
+    pub running: Option<Running>,
+    pub last: Option<Last>,
+    /// Number of tasks waiting for the running one to complete
+    #[serde(default)]
+    pub queued: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Running {
+    pub name: String,
+    /// Key path of the task
+    pub path: String,
+    pub pid: u32,
+    /// Unix timestamp of the task start
+    pub started_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Last {
+    pub name: String,
+    /// Key path of the task
+    pub path: String,
+    pub outcome: Outcome,
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+}
+
+/// Reads the status file, applies the changes and writes it back. Missing or invalid file is
+/// treated as empty status
+pub fn update(path: &Path, f: impl FnOnce(&mut Status)) -> Result<()> {
+    let mut status = fs::read(path)
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default();
+    f(&mut status);
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension(format!("tmp.{}", std::process::id()));
+    let mut file = File::create(&tmp_path)?;
+    serde_json::to_writer(&mut file, &status)?;
+    file.write_all(b"\n")?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_update() {
+        let dir = std::env::temp_dir().join(format!("ttr-status-{}", std::process::id()));
+        let path = dir.join("status.json");
+        let running = Running {
+            name: "build".to_string(),
+            path: "cb".to_string(),
+            pid: 1,
+            started_at: 0,
+        };
+        update(&path, |status| status.running = Some(running)).unwrap();
+        update(&path, |status| status.queued = 2).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let status: Status = serde_json::from_str(&content).unwrap();
+        assert_eq!(Some("build"), status.running.map(|r| r.name).as_deref());
+        assert_eq!(2, status.queued);
+        assert_eq!(
+            vec![path.clone()],
+            fs::read_dir(&dir)
+                .unwrap()
+                .map(|e| e.unwrap().path())
+                .collect::<Vec<_>>()
+        );
+
+        fs::write(&path, "garbage").unwrap();
+        update(&path, |_| {}).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(Status::default(), serde_json::from_str(&content).unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
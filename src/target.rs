@@ -0,0 +1,321 @@
+//! Execution targets. Task can be run in the terminal ttr is running in or launched
+//! somewhere else (a new terminal window, multiplexer pane etc.) without blocking the selector.
+//!
+//! Every target translates task [`Invocation`] into a command line of a launcher (eg. `zellij
+//! run`), which is run by ttr.
+//!
+//! Custom targets are implemented as plugins: executables in the `ttr/plugins` config directory.
+//! Plugin is given the [`PluginSpec`] of the task as JSON on stdin and should exit with zero
+//! status when the task is launched.
+use crate::{temp, Invocation, Result, Task};
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use tracing::info;
+
+/// Version of the plugin contract
+const PLUGIN_API_VERSION: u32 = 1;
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(from = "String")]
+pub enum Target {
+    /// Task is run in the current terminal
+    #[default]
+    Local,
+    /// Task is run in a new terminal emulator window
+    ExternalTerminal,
+    /// Task is run in a new pane of the current zellij session
+    ZellijPane,
+    /// Task is run in a new tab of the current zellij session
+    ZellijTab,
+    /// Task is run in a new window of the current GNU screen session
+    ScreenWindow,
+    /// Task is run in a new pane (split) of the current tmux window
+    TmuxPane,
+    /// Task is run in a new window of the current tmux session
+    TmuxWindow,
+    /// Task is launched by the plugin with a given name
+    Plugin(String),
+}
+
+impl From<String> for Target {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "local" => Target::Local,
+            "external-terminal" => Target::ExternalTerminal,
+            "zellij-pane" => Target::ZellijPane,
+            "zellij-tab" => Target::ZellijTab,
+            "screen-window" => Target::ScreenWindow,
+            "tmux-pane" => Target::TmuxPane,
+            "tmux-window" => Target::TmuxWindow,
+            _ => Target::Plugin(name),
+        }
+    }
+}
+
+impl Target {
+    /// Name of the target in the config
+    pub fn name(&self) -> &str {
+        match self {
+            Target::Local => "local",
+            Target::ExternalTerminal => "external-terminal",
+            Target::ZellijPane => "zellij-pane",
+            Target::ZellijTab => "zellij-tab",
+            Target::ScreenWindow => "screen-window",
+            Target::TmuxPane => "tmux-pane",
+            Target::TmuxWindow => "tmux-window",
+            Target::Plugin(name) => name,
+        }
+    }
+}
+
+/// Description of the task passed to a plugin
+#[derive(Serialize)]
+struct PluginSpec<'a> {
+    version: u32,
+    name: &'a str,
+    /// Key path of the task from the root group
+    path: &'a str,
+    /// Task command (or script) as written in the config
+    cmd: &'a str,
+    /// Program and arguments to be run
+    command_line: Vec<String>,
+    working_dir: &'a Path,
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Local => write!(f, "current terminal"),
+            Target::ExternalTerminal => write!(f, "external terminal"),
+            Target::ZellijPane => write!(f, "zellij pane"),
+            Target::ZellijTab => write!(f, "zellij tab"),
+            Target::ScreenWindow => write!(f, "screen window"),
+            Target::TmuxPane => write!(f, "tmux pane"),
+            Target::TmuxWindow => write!(f, "tmux window"),
+            Target::Plugin(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Launches the task invocation on the target of the task without waiting for it to complete
+pub fn launch(task: &Task, invocation: &Invocation, terminal: &str) -> Result<()> {
+    let target = &task.target;
+    if !invocation.secrets.is_empty() {
+        bail!(
+            "Task with secrets can not be launched in {}, they would be exposed on the command line",
+            target
+        );
+    }
+    let name = task.name.as_str();
+    let mut layout_path = None;
+    let args = match target {
+        Target::Local => bail!("Local tasks can not be launched in background"),
+        Target::ExternalTerminal => external_terminal(invocation, terminal),
+        Target::ZellijPane => zellij_pane(name, invocation)?,
+        Target::ZellijTab => {
+            let path = layout_path.insert(temp::path("layout")?.with_extension("kdl"));
+            temp::create(path)?.write_all(zellij_layout(invocation).as_bytes())?;
+            zellij_tab(name, path)?
+        }
+        Target::ScreenWindow => screen_window(name, invocation)?,
+        Target::TmuxPane => tmux(Target::TmuxPane, &["split-window"], invocation)?,
+        Target::TmuxWindow => tmux(Target::TmuxWindow, &["new-window", "-n", name], invocation)?,
+        Target::Plugin(plugin) => return run_plugin(plugin, task, invocation),
+    };
+    info!(%target, ?args, "launching task");
+    let result = run_launcher(target, &args, &invocation.working_dir);
+    if let Some(path) = layout_path {
+        let _ = fs::remove_file(path);
+    }
+    result
+}
+
+fn run_launcher(target: &Target, args: &[String], working_dir: &Path) -> Result<()> {
+    let (program, args) = args.split_first().context("Launcher command is empty")?;
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .current_dir(working_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null());
+    let context = || format!("Unable to launch task in {}", target);
+    // Terminal emulator is running as long as the task is, other launchers exit immediately
+    if *target == Target::ExternalTerminal {
+        command
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(context)?;
+    } else {
+        let output = command.output().with_context(context)?;
+        if !output.status.success() {
+            bail!(
+                "Unable to launch task in {}: {}",
+                target,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn external_terminal(invocation: &Invocation, terminal: &str) -> Vec<String> {
+    let mut args = terminal
+        .split_whitespace()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    args.extend(invocation.command_line());
+    args
+}
+
+fn zellij_pane(name: &str, invocation: &Invocation) -> Result<Vec<String>> {
+    ensure_env("ZELLIJ", Target::ZellijPane)?;
+    let mut args = strings(["zellij", "run", "--name", name, "--cwd"]);
+    args.push(invocation.working_dir.to_string_lossy().to_string());
+    args.push("--".to_string());
+    args.extend(invocation.command_line());
+    Ok(args)
+}
+
+/// zellij can't run a command in a new tab directly, so a layout with a single command pane
+/// is used
+fn zellij_tab(name: &str, layout_path: &Path) -> Result<Vec<String>> {
+    ensure_env("ZELLIJ", Target::ZellijTab)?;
+    let mut args = strings(["zellij", "action", "new-tab", "--name", name, "--layout"]);
+    args.push(layout_path.to_string_lossy().to_string());
+    Ok(args)
+}
+
+fn zellij_layout(invocation: &Invocation) -> String {
+    let quote = |s: &str| format!("{:?}", s);
+    let mut command_line = invocation.command_line();
+    let program = command_line.next().unwrap_or_default();
+    let args = command_line
+        .map(|a| quote(&a))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "layout {{\n  pane command={} cwd={} {{\n    args {}\n  }}\n}}\n",
+        quote(&program),
+        quote(&invocation.working_dir.to_string_lossy()),
+        args
+    )
+}
+
+fn screen_window(name: &str, invocation: &Invocation) -> Result<Vec<String>> {
+    ensure_env("STY", Target::ScreenWindow)?;
+    let mut args = strings(["screen", "-X", "screen", "-t", name]);
+    // New window is started in the working directory of the screen session
+    args.extend(strings(["sh", "-c", "cd \"$0\" && exec \"$@\""]));
+    args.push(invocation.working_dir.to_string_lossy().to_string());
+    args.extend(invocation.command_line());
+    Ok(args)
+}
+
+/// `-d` keeps the focus on the pane ttr is running in
+fn tmux(target: Target, command: &[&str], invocation: &Invocation) -> Result<Vec<String>> {
+    ensure_env("TMUX", target)?;
+    let mut args = vec!["tmux".to_string()];
+    args.extend(command.iter().map(|a| a.to_string()));
+    args.extend(strings(["-d", "-c"]));
+    args.push(invocation.working_dir.to_string_lossy().to_string());
+    args.push("--".to_string());
+    args.extend(invocation.command_line());
+    Ok(args)
+}
+
+/// Directory plugins are discovered from (`~/.config/ttr/plugins` on Linux)
+pub fn plugins_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("ttr").join("plugins"))
+}
+
+fn run_plugin(plugin: &str, task: &Task, invocation: &Invocation) -> Result<()> {
+    if plugin.contains(std::path::is_separator) {
+        bail!("Invalid plugin name: {}", plugin);
+    }
+    let Some(path) = plugins_dir().map(|dir| dir.join(plugin)) else {
+        bail!("Unable to determine plugins directory");
+    };
+    if !path.is_file() {
+        bail!(
+            "Unknown target: {} (no plugin at {})",
+            plugin,
+            path.display()
+        );
+    }
+    let spec = PluginSpec {
+        version: PLUGIN_API_VERSION,
+        name: &task.name,
+        path: &task.path,
+        cmd: task.command(),
+        command_line: invocation.command_line().collect(),
+        working_dir: &invocation.working_dir,
+    };
+    info!(plugin = %path.display(), "launching task using plugin");
+    let mut child = Command::new(&path)
+        .current_dir(&invocation.working_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Unable to run plugin {}", path.display()))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        serde_json::to_writer(&mut stdin, &spec)?;
+        stdin.write_all(b"\n")?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "Plugin {} failed ({}): {}",
+            plugin,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn ensure_env(variable: &str, target: Target) -> Result<()> {
+    if env::var_os(variable).is_none_or(|v| v.is_empty()) {
+        bail!(
+            "Task can be run in {} only inside of it (${} is not set)",
+            target,
+            variable
+        );
+    }
+    Ok(())
+}
+
+fn strings<const N: usize>(args: [&str; N]) -> Vec<String> {
+    args.into_iter().map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_zellij_layout() {
+        let invocation = Invocation {
+            program: "sh".to_string(),
+            args: strings(["-c", "exec echo \"hello\""]),
+            working_dir: "/tmp".into(),
+            env: vec![],
+            clear_env: false,
+            secrets: vec![],
+            scripts: temp::Files::default(),
+        };
+        let expected = r#"layout {
+  pane command="sh" cwd="/tmp" {
+    args "-c" "exec echo \"hello\""
+  }
+}
+"#;
+        assert_eq!(expected, zellij_layout(&invocation));
+    }
+}
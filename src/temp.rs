@@ -0,0 +1,136 @@
+//! Private temporary files.
+//!
+//! The temporary directory is shared by all users of the system, so a predictable name in it can
+//! be taken beforehand by someone else (eg. a symlink to a file of the user). Temporary files of
+//! ttr (scripts, spilled output, layouts) are created in a directory only the user can access.
+//! The directory is created anew for every session and files are never reused.
+//! Scripts of the tasks are removed once the task exits, unless it is launched on an external
+//! target and may outlive ttr.
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Creates a new directory accessible only by the user in the temporary directory. Fails
+/// instead of reusing a directory which already exists
+pub fn create_dir(prefix: &str) -> io::Result<PathBuf> {
+    loop {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let name = format!("ttr-{}-{}-{:x}{}", prefix, std::process::id(), nanos, n);
+        let dir = std::env::temp_dir().join(name);
+        match private_dir(&dir) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            result => return result.map(|_| dir),
+        }
+    }
+}
+
+/// Creates the directory accessible only by the user. Fails if the directory (or a symlink)
+/// already exists
+pub fn private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)
+}
+
+/// Returns unique path of a temporary file in the private directory of the session
+pub fn path(prefix: &str) -> io::Result<PathBuf> {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    let dir = match DIR.get() {
+        Some(dir) => dir,
+        None => {
+            let dir = create_dir("session")?;
+            DIR.get_or_init(|| dir)
+        }
+    };
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    Ok(dir.join(format!("ttr-{}-{}", prefix, n)))
+}
+
+/// Creates the file readable only by the user. Fails if the file already exists
+pub fn create(path: &Path) -> io::Result<File> {
+    let mut options = File::options();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+/// Temporary files removed when dropped
+#[derive(Default)]
+pub struct Files(Vec<PathBuf>);
+
+impl Files {
+    pub fn push(&mut self, path: PathBuf) {
+        self.0.push(path);
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.0
+    }
+
+    /// Leaves the files in place (eg. for a process which outlives ttr)
+    pub fn keep(&mut self) {
+        self.0.clear();
+    }
+}
+
+impl Drop for Files {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_private() {
+        let path = path("test").unwrap();
+        assert_ne!(path, super::path("test").unwrap());
+        create(&path).unwrap();
+        assert!(create(&path).is_err());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(0o600, mode(&path));
+            assert_eq!(0o700, mode(path.parent().unwrap()));
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn check_files() {
+        let mut files = Files::default();
+        let (removed, kept) = (path("test").unwrap(), path("test").unwrap());
+        create(&removed).unwrap();
+        files.push(removed.clone());
+        drop(files);
+        assert!(!removed.exists());
+
+        let mut files = Files::default();
+        create(&kept).unwrap();
+        files.push(kept.clone());
+        files.keep();
+        drop(files);
+        assert!(kept.exists());
+        fs::remove_file(&kept).unwrap();
+    }
+}
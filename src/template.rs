@@ -0,0 +1,152 @@
+//! Substitution of `{{ }}` placeholders in task commands.
+//!
+//! Placeholder is either a variable name (`{{cwd}}`) or a helper applied to a variable
+//! (`{{wslpath cwd}}`). Placeholders not looking like identifiers (eg. `{{.Id}}` in
+//! `docker inspect` format strings) and the ones with unknown variables or helpers (eg. in the
+//! templates of other tools) are left as is.
+//!
+//! Values coming from the user (eg. command line arguments) are quoted using [`quote()`], so
+//! they can't inject shell code into the command.
+use crate::Result;
+use anyhow::{bail, Context as _};
+use std::{collections::HashMap, path::Path, process::Command};
+use tracing::debug;
+
+/// Variables available for the substitution
+#[derive(Default)]
+pub struct Context {
+    vars: HashMap<String, String>,
+}
+
+impl Context {
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.vars.insert(name.into(), value.into());
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(String::as_str)
+    }
+}
+
+pub fn render(template: &str, context: &Context) -> Result<String> {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let Some(end) = placeholder.find("}}") else {
+            result.push_str(placeholder);
+            return Ok(result);
+        };
+        let expression = placeholder[2..end].trim();
+        let value = match is_expression(expression) {
+            true => evaluate(expression, context)?,
+            false => None,
+        };
+        match value {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&placeholder[..end + 2]),
+        }
+        rest = &placeholder[end + 2..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Expression is one or two identifiers (variable name optionally prefixed with a helper name)
+fn is_expression(expression: &str) -> bool {
+    let is_identifier = |s: &str| {
+        s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    let tokens = expression.split_whitespace().collect::<Vec<_>>();
+    (1..=2).contains(&tokens.len()) && tokens.iter().all(|t| is_identifier(t))
+}
+
+/// Value of the expression. `None` if the variable or the helper is unknown
+fn evaluate(expression: &str, context: &Context) -> Result<Option<String>> {
+    let tokens = expression.split_whitespace().collect::<Vec<_>>();
+    let (helper, name) = match tokens[..] {
+        [name] => (None, name),
+        [helper, name] => (Some(helper), name),
+        _ => return Ok(None),
+    };
+    let Some(value) = context.get(name) else {
+        debug!(name, "unknown template variable is left as is");
+        return Ok(None);
+    };
+    match helper {
+        None => Ok(Some(value.to_string())),
+        Some("wslpath") => wslpath(value).map(Some),
+        Some("quote") => Ok(Some(quote(value))),
+        Some(helper) => {
+            debug!(helper, "unknown template helper is left as is");
+            Ok(None)
+        }
+    }
+}
+
+/// Quotes the string for POSIX shell, so it is passed to the command as a single argument
+pub fn quote(value: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c);
+    if !value.is_empty() && value.chars().all(is_safe) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Returns true if ttr is running inside Windows Subsystem for Linux
+pub fn is_wsl() -> bool {
+    std::env::var_os("WSL_DISTRO_NAME").is_some()
+        || Path::new("/proc/sys/fs/binfmt_misc/WSLInterop").exists()
+}
+
+/// Converts the path to the Windows form using `wslpath -w`. Outside of WSL path is
+/// returned unchanged, so the same config can be used on both sides.
+fn wslpath(path: &str) -> Result<String> {
+    if !is_wsl() {
+        return Ok(path.to_string());
+    }
+    let output = Command::new("wslpath")
+        .args(["-w", path])
+        .output()
+        .context("Unable to run wslpath")?;
+    if !output.status.success() {
+        bail!(
+            "wslpath failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_render() {
+        let mut context = Context::default();
+        context.set("cwd", "/home/user");
+        let render = |template| render(template, &context);
+
+        assert_eq!("ls /home/user", render("ls {{cwd}}").unwrap());
+        assert_eq!("ls /home/user", render("ls {{ cwd }}").unwrap());
+        assert_eq!(
+            "docker inspect -f '{{.Id}}'",
+            render("docker inspect -f '{{.Id}}'").unwrap()
+        );
+        assert_eq!("echo {{", render("echo {{").unwrap());
+        assert_eq!("{{unknown}}", render("{{unknown}}").unwrap());
+        assert_eq!("{{ unknown cwd }}", render("{{ unknown cwd }}").unwrap());
+    }
+
+    #[test]
+    fn check_quote() {
+        assert_eq!("--release", quote("--release"));
+        assert_eq!("''", quote(""));
+        assert_eq!("'hello world'", quote("hello world"));
+        assert_eq!(r"'$(rm -rf ~)'", quote("$(rm -rf ~)"));
+        assert_eq!(r"'it'\''s'", quote("it's"));
+    }
+}
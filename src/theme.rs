@@ -0,0 +1,240 @@
+//! Colors of the selector (`theme: {key: yellow}` in the config or `TTR_THEME=key=yellow`).
+//!
+//! Colors are the names known to crossterm (`dark_blue`, `grey`), `#rrggbb` or an ANSI color
+//! number (`0`-`255`). Colors of `TTR_THEME` take precedence over the ones of the config. Colors
+//! are disabled altogether with `no_color: true`, `TTR_THEME=no-color` or the `NO_COLOR`
+//! variable (<https://no-color.org>): they are stripped from everything ttr prints, keeping
+//! the other attributes (bold, underline).
+use crate::Result;
+use anyhow::{anyhow, bail, Context};
+use crossterm::style::Color;
+use serde::{Deserialize, Deserializer};
+use std::{env, sync::RwLock};
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    /// Keys of the tasks
+    #[serde(default, deserialize_with = "deserialize_color")]
+    key: Option<Color>,
+    /// Keys of the groups
+    #[serde(default, deserialize_with = "deserialize_color")]
+    group_key: Option<Color>,
+    /// Status line of the selector (result of the last run)
+    #[serde(default, deserialize_with = "deserialize_color")]
+    status: Option<Color>,
+    /// Error messages
+    #[serde(default, deserialize_with = "deserialize_color")]
+    error: Option<Color>,
+    /// Title of the selector and the path of the groups
+    #[serde(default, deserialize_with = "deserialize_color")]
+    breadcrumb: Option<Color>,
+    no_color: Option<bool>,
+}
+
+impl Theme {
+    pub fn key(&self) -> Color {
+        self.key.unwrap_or(Color::Green)
+    }
+
+    pub fn group_key(&self) -> Color {
+        self.group_key.unwrap_or(Color::DarkBlue)
+    }
+
+    /// Status line is printed in the default color of the terminal unless configured
+    pub fn status(&self) -> Option<Color> {
+        self.status
+    }
+
+    pub fn error(&self) -> Color {
+        self.error.unwrap_or(Color::Red)
+    }
+
+    pub fn breadcrumb(&self) -> Color {
+        self.breadcrumb.unwrap_or(Color::Grey)
+    }
+
+    pub fn no_color(&self) -> bool {
+        self.no_color.unwrap_or(false)
+    }
+
+    /// Colors set in `self` take precedence over the ones of `other`
+    pub fn or(self, other: Theme) -> Theme {
+        Theme {
+            key: self.key.or(other.key),
+            group_key: self.group_key.or(other.group_key),
+            status: self.status.or(other.status),
+            error: self.error.or(other.error),
+            breadcrumb: self.breadcrumb.or(other.breadcrumb),
+            no_color: self.no_color.or(other.no_color),
+        }
+    }
+
+    /// Parses `TTR_THEME` value: comma separated `name=color` pairs and `no-color`
+    fn parse(spec: &str) -> Result<Theme> {
+        let mut theme = Theme::default();
+        for item in spec.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            if item == "no-color" {
+                theme.no_color = Some(true);
+                continue;
+            }
+            let (name, color) = item
+                .split_once('=')
+                .ok_or_else(|| anyhow!("name=color expected: {}", item))?;
+            let color = Some(parse_color(color.trim())?);
+            match name.trim() {
+                "key" => theme.key = color,
+                "group_key" => theme.group_key = color,
+                "status" => theme.status = color,
+                "error" => theme.error = color,
+                "breadcrumb" => theme.breadcrumb = color,
+                name => bail!("Unknown theme color: {}", name),
+            }
+        }
+        Ok(theme)
+    }
+}
+
+static THEME: RwLock<Theme> = RwLock::new(Theme {
+    key: None,
+    group_key: None,
+    status: None,
+    error: None,
+    breadcrumb: None,
+    no_color: None,
+});
+
+/// Theme in effect
+pub fn current() -> Theme {
+    *THEME.read().unwrap()
+}
+
+/// Sets the theme of the config. `TTR_THEME` and `NO_COLOR` take precedence over it
+pub fn init(config: Theme) -> Result<()> {
+    let spec = env::var("TTR_THEME").unwrap_or_default();
+    let mut theme = Theme::parse(&spec).context("Invalid TTR_THEME")?.or(config);
+    if env::var("NO_COLOR").is_ok_and(|v| !v.is_empty()) {
+        theme.no_color = Some(true);
+    }
+    *THEME.write().unwrap() = theme;
+    Ok(())
+}
+
+/// Text to print: the colors are stripped in no-color mode
+pub fn plain(text: impl ToString) -> String {
+    let text = text.to_string();
+    match current().no_color() {
+        true => strip_colors(&text),
+        false => text,
+    }
+}
+
+fn parse_color(color: &str) -> Result<Color> {
+    if let Some(hex) = color.strip_prefix('#') {
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+        };
+        if let (6, Some(r), Some(g), Some(b)) = (hex.len(), channel(0), channel(2), channel(4)) {
+            return Ok(Color::Rgb { r, g, b });
+        }
+    } else if let Ok(value) = color.parse::<u8>() {
+        return Ok(Color::AnsiValue(value));
+    } else if color == "default" {
+        return Ok(Color::Reset);
+    } else if let Ok(color) = Color::try_from(color) {
+        return Ok(color);
+    }
+    bail!("Unknown color: {}", color)
+}
+
+fn deserialize_color<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Color>, D::Error> {
+    let color = String::deserialize(deserializer)?;
+    parse_color(&color)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Removes the color parameters of SGR sequences (`ESC [ ... m`) leaving the other attributes
+pub fn strip_colors(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("\x1b[") {
+        result.push_str(&rest[..start]);
+        let sequence = &rest[start + 2..];
+        let Some(end) = sequence.find(|c: char| ('@'..='~').contains(&c)) else {
+            result.push_str(&rest[start..]);
+            return result;
+        };
+        rest = &sequence[end + 1..];
+        if !sequence[end..].starts_with('m') {
+            result.push_str(&format!("\x1b[{}", &sequence[..=end]));
+            continue;
+        }
+        let mut params = sequence[..end].split(';');
+        let mut kept = vec![];
+        while let Some(param) = params.next() {
+            match param.parse::<u8>().unwrap_or(0) {
+                // Extended colors: `38;5;n` or `38;2;r;g;b`
+                38 | 48 | 58 => {
+                    let skip = if params.next() == Some("2") { 3 } else { 1 };
+                    params.by_ref().take(skip).for_each(drop);
+                }
+                30..=37 | 39..=47 | 49 | 59 | 90..=97 | 100..=107 => {}
+                _ => kept.push(param),
+            }
+        }
+        if !kept.is_empty() {
+            result.push_str(&format!("\x1b[{}m", kept.join(";")));
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::style::Stylize;
+
+    #[test]
+    fn check_parse() {
+        let theme = Theme::parse("key=yellow, group_key=#ff8000,error=196,no-color").unwrap();
+        assert_eq!(Color::Yellow, theme.key());
+        assert_eq!(
+            Color::Rgb {
+                r: 255,
+                g: 128,
+                b: 0
+            },
+            theme.group_key()
+        );
+        assert_eq!(Color::AnsiValue(196), theme.error());
+        assert_eq!(Color::Grey, theme.breadcrumb());
+        assert!(theme.no_color());
+        assert!(Theme::parse("key=purple").is_err());
+        assert!(Theme::parse("title=red").is_err());
+        assert!(Theme::parse("#ff").is_err());
+
+        let config: Theme = serde_yaml::from_str("{key: cyan, status: dark_grey}").unwrap();
+        let theme = Theme::parse("key=red").unwrap().or(config);
+        assert_eq!(Color::Red, theme.key());
+        assert_eq!(Some(Color::DarkGrey), theme.status());
+    }
+
+    #[test]
+    fn check_strip_colors() {
+        let text = format!(
+            "{} {} {}",
+            "a".green().bold(),
+            "b".on_red().underlined(),
+            "c".with(Color::Rgb { r: 1, g: 2, b: 3 })
+        );
+        assert_eq!("a b c", crate::ui::strip_ansi(&strip_colors(&text)));
+        assert!(!strip_colors(&text).contains("38;"));
+        assert!(strip_colors(&text).contains("\x1b[1m"));
+        assert_eq!("\x1b[2Kplain", strip_colors("\x1b[2Kplain"));
+    }
+}
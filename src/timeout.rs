@@ -0,0 +1,178 @@
+//! Killing the tasks running longer than allowed (`timeout: 30s`).
+//!
+//! Task is asked to terminate with SIGTERM first and killed with SIGKILL if it is still running
+//! after a grace period. The signals are sent to the process group of the task and all the
+//! descendants of the task process (which could have left the group), so the commands started
+//! by the shell don't outlive it.
+use crate::Result;
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use std::{
+    fmt,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tracing::warn;
+
+/// Time given to the task to exit after SIGTERM before it is killed
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// Maximum duration of the task run, written with units (`90s`, `5m`, `1h30m`)
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String")]
+pub struct Timeout(Duration);
+
+impl Timeout {
+    pub fn duration(self) -> Duration {
+        self.0
+    }
+}
+
+impl TryFrom<String> for Timeout {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        parse(&text)
+            .map(Self)
+            .with_context(|| format!("Invalid timeout: {}", text))
+    }
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        match (secs / 3600, secs % 3600 / 60, secs % 60) {
+            (0, 0, 0) => write!(f, "{}ms", self.0.as_millis()),
+            (0, 0, s) => write!(f, "{}s", s),
+            (0, m, 0) => write!(f, "{}m", m),
+            (0, m, s) => write!(f, "{}m{}s", m, s),
+            (h, 0, _) => write!(f, "{}h", h),
+            (h, m, _) => write!(f, "{}h{}m", h, m),
+        }
+    }
+}
+
+fn parse(text: &str) -> Result<Duration> {
+    let mut total = Duration::ZERO;
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        bail!("Timeout is empty");
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let number: u64 = rest[..digits].parse().context("Number expected")?;
+        rest = &rest[digits..];
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        total += match &rest[..unit] {
+            "ms" => Duration::from_millis(number),
+            "s" => Duration::from_secs(number),
+            "m" => Duration::from_secs(number * 60),
+            "h" => Duration::from_secs(number * 3600),
+            "" => bail!("Unit expected (ms, s, m or h)"),
+            unit => bail!("Unknown unit: {}", unit),
+        };
+        rest = &rest[unit..];
+    }
+    if total.is_zero() {
+        bail!("Timeout must be positive");
+    }
+    Ok(total)
+}
+
+/// Terminates the process and its descendants when the timeout expires, unless stopped before
+pub struct Watchdog {
+    stop: Sender<()>,
+    thread: JoinHandle<bool>,
+}
+
+impl Watchdog {
+    pub fn start(pid: u32, timeout: Timeout) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            if stopped.recv_timeout(timeout.duration()) != Err(RecvTimeoutError::Timeout) {
+                return false;
+            }
+            warn!(pid, %timeout, "task timed out, terminating");
+            terminate(pid, false);
+            if stopped.recv_timeout(KILL_GRACE) == Err(RecvTimeoutError::Timeout) {
+                warn!(pid, "task is still running, killing");
+                terminate(pid, true);
+            }
+            true
+        });
+        Self { stop, thread }
+    }
+
+    /// Stops the watchdog after the process has exited. Returns true if the process was
+    /// terminated because of the timeout
+    pub fn stop(self) -> bool {
+        let _ = self.stop.send(());
+        self.thread.join().unwrap_or(false)
+    }
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32, kill: bool) {
+    let signal = if kill { libc::SIGKILL } else { libc::SIGTERM };
+    // Descendants are found before the process is signalled, they are reparented once it exits
+    let descendants = crate::proctree::descendants(pid).unwrap_or_default();
+    // SAFETY: getpgid and kill have no memory safety requirements
+    unsafe {
+        let group = pid as libc::pid_t;
+        if libc::getpgid(group) == group {
+            libc::kill(-group, signal);
+        }
+        for pid in std::iter::once(pid).chain(descendants) {
+            libc::kill(pid as libc::pid_t, signal);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn terminate(pid: u32, _kill: bool) {
+    // There are no signals on Windows, the whole process tree is killed at once
+    let _ = std::process::Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .output();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_parse() {
+        assert_eq!(Duration::from_secs(30), parse("30s").unwrap());
+        assert_eq!(Duration::from_secs(90), parse("1m30s").unwrap());
+        assert_eq!(Duration::from_secs(7200), parse("2h").unwrap());
+        assert_eq!(Duration::from_millis(500), parse("500ms").unwrap());
+        assert!(parse("30").is_err());
+        assert!(parse("5d").is_err());
+        assert!(parse("0s").is_err());
+        assert!(parse("").is_err());
+        assert_eq!("1m30s", Timeout(Duration::from_secs(90)).to_string());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_watchdog() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let watchdog = Watchdog::start(child.id(), Timeout(Duration::from_millis(100)));
+        let status = child.wait().unwrap();
+        assert!(watchdog.stop());
+        assert!(!status.success());
+
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let watchdog = Watchdog::start(child.id(), Timeout(Duration::from_secs(10)));
+        child.wait().unwrap();
+        assert!(!watchdog.stop());
+    }
+}
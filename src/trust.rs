@@ -0,0 +1,89 @@
+//! Trust of project configs.
+//!
+//! Config found in the current directory or its parents could come from a freshly cloned
+//! repository, so its tasks are not available until the user trusts it (like `direnv allow`).
+//! SHA-256 fingerprint of the trusted config is kept in the state. Any change to the config or
+//! to its local overrides (`.ttr.local.yaml`) revokes the trust. Configs in the home and config
+//! directories are always trusted.
+use crate::{overrides, state, Result};
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+/// Hex-encoded SHA-256 of the config content.
+///
+/// Local overrides can replace commands of the tasks, so when there are some the fingerprint
+/// is a hash of the digests of both files
+pub fn fingerprint(config: &Path) -> Result<String> {
+    let read =
+        |path: &Path| fs::read(path).with_context(|| format!("Unable to read {}", path.display()));
+    let digest = Sha256::digest(read(config)?);
+    let local = overrides::local_config(config.parent().unwrap_or(Path::new("")));
+    if !local.is_file() {
+        return Ok(format!("{:x}", digest));
+    }
+    let local_digest = Sha256::digest(read(&local)?);
+    let digest = Sha256::new()
+        .chain_update(digest)
+        .chain_update(local_digest)
+        .finalize();
+    Ok(format!("{:x}", digest))
+}
+
+/// Returns true if the config wasn't changed since it was trusted
+pub fn is_trusted(config: &Path) -> Result<bool> {
+    let config = canonical(config)?;
+    let fingerprint = fingerprint(&config)?;
+    Ok(state::read_all()?.trusted.get(&config) == Some(&fingerprint))
+}
+
+pub fn trust(config: &Path) -> Result<()> {
+    let config = canonical(config)?;
+    let fingerprint = fingerprint(&config)?;
+    info!(config = %config.display(), fingerprint, "trusting config");
+    state::update_all(|state| state.trusted.insert(config, fingerprint))?;
+    Ok(())
+}
+
+/// Revokes the trust. Returns false if the config wasn't trusted
+pub fn untrust(config: &Path) -> Result<bool> {
+    let config = canonical(config)?;
+    info!(config = %config.display(), "revoking trust");
+    let removed = state::update_all(|state| state.trusted.remove(&config))?;
+    Ok(removed.is_some())
+}
+
+fn canonical(config: &Path) -> Result<PathBuf> {
+    config
+        .canonicalize()
+        .with_context(|| format!("Unable to find {}", config.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_fingerprint_covers_local_overrides() {
+        let dir = std::env::temp_dir().join(format!("ttr-trust-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join(crate::TTR_CONFIG);
+        fs::write(&config, "tasks: []").unwrap();
+        let without_local = fingerprint(&config).unwrap();
+        let expected = "0e26e7bf94566bd7ac6b07d7c0719b2aa3ead85d8aaf20db6fa9b852811b39dc";
+        assert_eq!(expected, without_local);
+
+        let local = overrides::local_config(&dir);
+        fs::write(&local, "overrides: {}").unwrap();
+        let with_local = fingerprint(&config).unwrap();
+        fs::write(&local, "overrides: {a: {cmd: ls}}").unwrap();
+        let changed_local = fingerprint(&config).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_ne!(without_local, with_local);
+        assert_ne!(with_local, changed_local);
+    }
+}
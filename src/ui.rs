@@ -0,0 +1,542 @@
+use crate::{theme, Result};
+use anyhow::{anyhow, bail, Context};
+use crossterm::{
+    cursor,
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers, PopKeyboardEnhancementFlags,
+    },
+    execute, queue,
+    terminal::{
+        disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+        LeaveAlternateScreen, SetSize,
+    },
+};
+use std::{
+    fs::{self, File},
+    io::{self, stdout, IsTerminal, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Terminal size reported in headless mode
+const HEADLESS_SIZE: (u16, u16) = (80, 24);
+
+/// Escape sequence making the line double-width (DECDWL). Terminals not supporting it ignore it
+const DOUBLE_WIDTH: &str = "\x1b#6";
+
+/// Escape sequence making the line single-width again (DECSWL)
+const SINGLE_WIDTH: &str = "\x1b#5";
+
+/// Makes all the lines of the frame double-width, so it is readable on a shared screen. The
+/// frame should be laid out for the half of the terminal width
+pub fn enlarge(frame: &str) -> String {
+    frame
+        .lines()
+        .map(|line| format!("{}{}\n", DOUBLE_WIDTH, line))
+        .collect()
+}
+
+/// Terminal event the screens react to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    Key(KeyEvent),
+    /// Terminal was resized to the given columns and rows
+    Resize(u16, u16),
+}
+
+/// User interface backend.
+///
+/// Either a real terminal or a headless session where key events are read from a script
+/// and rendered frames are written to a file. Headless mode is used for end-to-end testing
+/// and automation.
+pub struct Ui {
+    backend: Backend,
+}
+
+enum Backend {
+    Terminal {
+        /// Lines of the last frame drawn on the screen. Used to redraw only changed lines
+        screen: Vec<String>,
+        size: (u16, u16),
+    },
+    Headless {
+        inputs: std::vec::IntoIter<Input>,
+        frames: Box<dyn Write>,
+        frame_no: usize,
+        /// Terminal size reported, changed by `<Resize:COLSxROWS>` in the script
+        size: (u16, u16),
+    },
+}
+
+impl Ui {
+    pub fn terminal() -> Self {
+        Self {
+            backend: Backend::Terminal {
+                screen: vec![],
+                size: (0, 0),
+            },
+        }
+    }
+
+    /// Creates headless UI reading keys from `input` (`-` means stdin) and writing frames
+    /// to `output` (stdout if not provided)
+    pub fn headless(input: &Path, output: Option<&Path>) -> Result<Self> {
+        let script = if input == Path::new("-") {
+            let mut script = String::new();
+            io::stdin().read_to_string(&mut script)?;
+            script
+        } else {
+            fs::read_to_string(input)
+                .with_context(|| format!("Unable to read input script: {}", input.display()))?
+        };
+        let inputs = parse_key_script(&script)?;
+        let frames: Box<dyn Write> = match output {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(stdout()),
+        };
+        Ok(Self {
+            backend: Backend::Headless {
+                inputs: inputs.into_iter(),
+                frames,
+                frame_no: 0,
+                size: HEADLESS_SIZE,
+            },
+        })
+    }
+
+    /// Enters alternate screen. Screen is restored when returned guard is dropped
+    pub fn alternate_screen(&mut self) -> Option<AlternateScreen> {
+        match &mut self.backend {
+            Backend::Terminal { screen, .. } => {
+                screen.clear();
+                Some(AlternateScreen::enter())
+            }
+            Backend::Headless { .. } => None,
+        }
+    }
+
+    pub fn size(&self) -> Result<(u16, u16)> {
+        match self.backend {
+            Backend::Terminal { .. } => Ok(crossterm::terminal::size()?),
+            Backend::Headless { size, .. } => Ok(size),
+        }
+    }
+
+    /// Restores the terminal after a full-screen program (`tui: true`), which could have left it
+    /// in its own state. The next frame is redrawn completely
+    pub fn restore(&mut self) -> Result<()> {
+        if let Backend::Terminal { screen, .. } = &mut self.backend {
+            screen.clear();
+            restore_terminal()?;
+        }
+        Ok(())
+    }
+
+    /// Asks the terminal to resize its window (`CSI 8 t`). Terminals not supporting it ignore
+    /// the request
+    pub fn resize(&mut self, cols: u16, rows: u16) -> Result<()> {
+        if let Backend::Terminal { .. } = self.backend {
+            execute!(stdout(), SetSize(cols, rows))?;
+            // Window is resized asynchronously
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        Ok(())
+    }
+
+    /// Draws full-screen frame.
+    ///
+    /// Only the lines changed since the previous frame are written to the terminal, so
+    /// redraws don't flicker on slow connections.
+    pub fn draw(&mut self, frame: &str) -> Result<()> {
+        let Backend::Terminal { screen, size } = &mut self.backend else {
+            return self.print(frame);
+        };
+        let frame = theme::plain(frame);
+        let mut stdout = stdout().lock();
+        let new_size = crossterm::terminal::size()?;
+        if *size != new_size {
+            // Terminal could reflow the content after resize, so everything is redrawn
+            queue!(stdout, Clear(ClearType::All))?;
+            screen.clear();
+            *size = new_size;
+        }
+        let lines = frame.lines().collect::<Vec<_>>();
+        for (row, line) in changed_lines(screen, &lines) {
+            queue!(stdout, cursor::MoveTo(0, row as u16))?;
+            // Width of the line is kept by the terminal until it is changed explicitly
+            if !line.starts_with(DOUBLE_WIDTH) {
+                write!(stdout, "{}", SINGLE_WIDTH)?;
+            }
+            write!(stdout, "{}", line)?;
+            queue!(stdout, Clear(ClearType::UntilNewLine))?;
+        }
+        if lines.len() < screen.len() {
+            queue!(
+                stdout,
+                cursor::MoveTo(0, lines.len() as u16),
+                Clear(ClearType::FromCursorDown)
+            )?;
+        }
+        stdout.flush()?;
+        *screen = lines.into_iter().map(str::to_string).collect();
+        Ok(())
+    }
+
+    /// Prints the frame below the current terminal content
+    pub fn print(&mut self, frame: &str) -> Result<()> {
+        match &mut self.backend {
+            Backend::Terminal { .. } => {
+                let mut stdout = stdout().lock();
+                stdout.write_all(theme::plain(frame).as_bytes())?;
+                stdout.flush()?;
+            }
+            Backend::Headless {
+                frames, frame_no, ..
+            } => {
+                *frame_no += 1;
+                writeln!(frames, "--- frame {} ---", frame_no)?;
+                frames.write_all(strip_ansi(frame).as_bytes())?;
+                frames.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Clears the screen before running a task
+    pub fn clear(&mut self) -> Result<()> {
+        if let Backend::Terminal { screen, .. } = &mut self.backend {
+            screen.clear();
+            execute!(stdout(), Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+        }
+        Ok(())
+    }
+
+    /// Copies text to the system clipboard using OSC 52 escape sequence. Works over SSH and
+    /// in terminal multiplexers supporting it
+    pub fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        if let Backend::Terminal { .. } = self.backend {
+            let mut stdout = stdout().lock();
+            write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+            stdout.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn next_key_event(&mut self) -> Result<KeyEvent> {
+        loop {
+            if let Input::Key(key) = self.next_input()? {
+                return Ok(key);
+            }
+        }
+    }
+
+    /// Waits for a key press or a resize of the terminal, so the screen can be laid out again
+    pub fn next_input(&mut self) -> Result<Input> {
+        match &mut self.backend {
+            Backend::Terminal { .. } => Ok(next_input()),
+            Backend::Headless { inputs, size, .. } => {
+                let input = inputs.next().ok_or(anyhow!("Input script exhausted"))?;
+                if let Input::Resize(cols, rows) = input {
+                    *size = (cols, rows);
+                }
+                Ok(input)
+            }
+        }
+    }
+
+    /// Waits for a key press no longer than `timeout`. In headless mode the time is not
+    /// waited, `None` is returned once the input script is exhausted
+    pub fn poll_key_event(&mut self, timeout: Duration) -> Result<Option<KeyEvent>> {
+        match &mut self.backend {
+            Backend::Terminal { .. } => poll_key_event(timeout),
+            Backend::Headless { inputs, size, .. } => {
+                for input in inputs {
+                    match input {
+                        Input::Key(key) => return Ok(Some(key)),
+                        Input::Resize(cols, rows) => *size = (cols, rows),
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+pub struct AlternateScreen;
+
+impl AlternateScreen {
+    fn enter() -> Self {
+        execute!(stdout(), EnterAlternateScreen, cursor::Hide)
+            .expect("Unable to enter alternative screen");
+        Self
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        // No need to unpack Result. We can't do anything about it anyway
+        let _ = execute!(stdout(), LeaveAlternateScreen, cursor::Show);
+    }
+}
+
+/// Resets the terminal modes full-screen programs use: leaves the alternate screen, disables raw
+/// mode, mouse reporting, bracketed paste and keyboard enhancements, and shows the cursor. The
+/// program could have crashed or been killed before restoring them
+pub fn restore_terminal() -> Result<()> {
+    let mut stdout = stdout();
+    if !stdout.is_terminal() {
+        return Ok(());
+    }
+    disable_raw_mode()?;
+    execute!(
+        stdout,
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste,
+        cursor::Show,
+    )?;
+    // Popping keyboard enhancement flags not pushed before is ignored by the terminals
+    let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+    Ok(())
+}
+
+struct RawMode;
+
+impl RawMode {
+    fn enter() -> Self {
+        enable_raw_mode().expect("Unable to enable raw mode");
+        Self
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // No need to unpack Result. We can't do anything about it anyway
+        let _ = disable_raw_mode();
+    }
+}
+
+fn next_input() -> Input {
+    let _raw = RawMode::enter();
+    loop {
+        let Ok(true) = event::poll(Duration::from_secs(60)) else {
+            continue;
+        };
+        if let Some(input) = to_input(event::read().expect("Unable to read event")) {
+            break input;
+        }
+    }
+}
+
+/// Terminal event as the input of the screens. Other events are ignored
+fn to_input(event: Event) -> Option<Input> {
+    match event {
+        // Windows reports both press and release of the key
+        Event::Key(e) if e.kind != KeyEventKind::Release => Some(Input::Key(e)),
+        Event::Resize(cols, rows) => Some(Input::Resize(cols, rows)),
+        _ => None,
+    }
+}
+
+fn poll_key_event(timeout: Duration) -> Result<Option<KeyEvent>> {
+    let _raw = RawMode::enter();
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if !event::poll(remaining)? {
+            return Ok(None);
+        }
+        if let Some(Input::Key(e)) = to_input(event::read()?) {
+            return Ok(Some(e));
+        }
+    }
+}
+
+/// Returns lines of the `next` frame differing from the lines currently on the `screen`
+/// with their row numbers
+fn changed_lines<'a>(
+    screen: &'a [String],
+    next: &'a [&'a str],
+) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+    next.iter()
+        .enumerate()
+        .filter(|(row, line)| screen.get(*row).map(String::as_str) != Some(**line))
+        .map(|(row, line)| (row, *line))
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// Parses key script used in headless mode.
+///
+/// Script is a whitespace separated list of tokens. Every character of a token is a separate
+/// key press, special keys are written in vim notation: `<Enter>`, `<Esc>`, `<BS>`, `<Space>`,
+/// `<Up>`, `<Down>`, `<Left>`, `<Right>`, `<Tab>`, `<C-x>`. `<Resize:60x20>` resizes the
+/// terminal. Lines starting with `#` are ignored.
+fn parse_key_script(script: &str) -> Result<Vec<Input>> {
+    let mut keys = vec![];
+    let tokens = script
+        .lines()
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .flat_map(str::split_whitespace);
+    for token in tokens {
+        if token.len() > 2 && token.starts_with('<') && token.ends_with('>') {
+            keys.push(parse_special_key(&token[1..token.len() - 1])?);
+        } else {
+            let key = |ch| Input::Key(KeyEvent::from(KeyCode::Char(ch)));
+            keys.extend(token.chars().map(key));
+        }
+    }
+    Ok(keys)
+}
+
+fn parse_special_key(name: &str) -> Result<Input> {
+    if let Some(size) = name.strip_prefix("Resize:") {
+        let parsed = size
+            .split_once('x')
+            .and_then(|(cols, rows)| Some((cols.parse().ok()?, rows.parse().ok()?)));
+        let Some((cols, rows)) = parsed else {
+            bail!("Invalid size in input script: <{}>", name);
+        };
+        return Ok(Input::Resize(cols, rows));
+    }
+    let code = match name.to_lowercase().as_str() {
+        "enter" | "cr" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "bs" => KeyCode::Backspace,
+        "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "lt" => KeyCode::Char('<'),
+        _ => {
+            let Some(ch) = name.strip_prefix("C-").and_then(|c| c.chars().next()) else {
+                bail!("Unknown key in input script: <{}>", name);
+            };
+            let key = KeyEvent::new(KeyCode::Char(ch), KeyModifiers::CONTROL);
+            return Ok(Input::Key(key));
+        }
+    };
+    Ok(Input::Key(KeyEvent::from(code)))
+}
+
+/// Removes ANSI escape sequences from a string
+pub fn strip_ansi(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut chars = input.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\x1b' {
+            result.push(ch);
+            continue;
+        }
+        match chars.next() {
+            // CSI sequence is terminated by a character in range @..~
+            Some('[') => {
+                for ch in chars.by_ref() {
+                    if ('@'..='~').contains(&ch) {
+                        break;
+                    }
+                }
+            }
+            // Line width (`ESC # 6`) takes one more character
+            Some('#') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::style::Stylize;
+
+    #[test]
+    fn check_key_releases_are_ignored() {
+        let press = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE);
+        let release = KeyEvent::new_with_kind(
+            KeyCode::Char('a'),
+            KeyModifiers::NONE,
+            KeyEventKind::Release,
+        );
+        assert_eq!(Some(Input::Key(press)), to_input(Event::Key(press)));
+        assert_eq!(None, to_input(Event::Key(release)));
+        assert_eq!(Some(Input::Resize(80, 24)), to_input(Event::Resize(80, 24)));
+        assert_eq!(None, to_input(Event::FocusGained));
+    }
+
+    #[test]
+    fn check_key_script_parsing() {
+        let inputs = parse_key_script("# comment\ngr <Enter>\n<C-c> <BS>").unwrap();
+        let keys = inputs
+            .iter()
+            .map(|i| match i {
+                Input::Key(key) => *key,
+                Input::Resize(..) => panic!("Unexpected resize"),
+            })
+            .collect::<Vec<_>>();
+        let codes = keys.iter().map(|k| k.code).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                KeyCode::Char('g'),
+                KeyCode::Char('r'),
+                KeyCode::Enter,
+                KeyCode::Char('c'),
+                KeyCode::Backspace
+            ],
+            codes
+        );
+        assert_eq!(KeyModifiers::CONTROL, keys[3].modifiers);
+        assert!(parse_key_script("<Foo>").is_err());
+        assert_eq!(
+            vec![Input::Resize(60, 20)],
+            parse_key_script("<Resize:60x20>").unwrap()
+        );
+        assert!(parse_key_script("<Resize:60>").is_err());
+    }
+
+    #[test]
+    fn check_changed_lines() {
+        let screen = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let changed = changed_lines(&screen, &["a", "x", "c", "d"]).collect::<Vec<_>>();
+        assert_eq!(vec![(1, "x"), (3, "d")], changed);
+    }
+
+    #[test]
+    fn check_base64() {
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9vYmFy", base64(b"foobar"));
+    }
+
+    #[test]
+    fn check_strip_ansi() {
+        let styled = format!("Task {}", "failed".stylize().red().bold());
+        assert_eq!("Task failed", strip_ansi(&styled));
+        assert_eq!("  a → b\n", strip_ansi(&enlarge("  a → b")));
+    }
+}
@@ -0,0 +1,98 @@
+//! Unix specific process handling.
+//!
+//! Tasks are started in their own process group which is made the foreground group of the
+//! terminal (if ttr owns it), so Ctrl-C is delivered to the task and everything it started
+//! (eg. the dev server started by `npm run dev`) while ttr itself survives and shows the task
+//! status. Signals received by ttr while a task is running are forwarded to the whole group of
+//! the task.
+use std::{
+    io,
+    os::unix::process::CommandExt,
+    process::Command,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Once,
+    },
+};
+
+/// Process group id of the running task or 0 if there is no running task
+static TASK_GROUP: AtomicI32 = AtomicI32::new(0);
+
+/// Foreground process group of the terminal before it was handed to the task or 0 if the
+/// terminal wasn't handed over
+static PREVIOUS_GROUP: AtomicI32 = AtomicI32::new(0);
+
+/// Signals forwarded to the process group of the task
+const FORWARDED: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+/// Configures the command to be started in a new process group owning the terminal. The
+/// terminal is handed over only if ttr owns it, so ttr run in the background (`ttr run s &`)
+/// doesn't take it from the shell
+pub fn configure(command: &mut Command) {
+    // SAFETY: the calls have no memory safety requirements
+    let group = unsafe { libc::getpgrp() };
+    let owns_terminal = unsafe {
+        libc::isatty(libc::STDIN_FILENO) == 1 && libc::tcgetpgrp(libc::STDIN_FILENO) == group
+    };
+    PREVIOUS_GROUP.store(if owns_terminal { group } else { 0 }, Ordering::SeqCst);
+    // SAFETY: only async-signal-safe functions are called between fork and exec
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setpgid(0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // Process of a background group changing the foreground one gets SIGTTOU
+            if owns_terminal {
+                libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+                libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpid());
+                libc::signal(libc::SIGTTOU, libc::SIG_DFL);
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Forwards signals to the process group of the task until [`task_exited()`]
+pub fn task_started(pid: u32) {
+    static HANDLER: Once = Once::new();
+    HANDLER.call_once(|| {
+        for signal in FORWARDED {
+            // SAFETY: the handler only calls async-signal-safe functions
+            unsafe { libc::signal(signal, forward as *const () as libc::sighandler_t) };
+        }
+    });
+    // Set by the parent as well, so the group exists as soon as the process is spawned
+    // SAFETY: setpgid has no memory safety requirements
+    unsafe { libc::setpgid(pid as libc::pid_t, pid as libc::pid_t) };
+    TASK_GROUP.store(pid as i32, Ordering::SeqCst);
+}
+
+/// Gives the terminal back to the group which owned it before the task
+pub fn task_exited() {
+    TASK_GROUP.store(0, Ordering::SeqCst);
+    let previous = PREVIOUS_GROUP.swap(0, Ordering::SeqCst);
+    if previous == 0 {
+        return;
+    }
+    // SAFETY: the calls have no memory safety requirements
+    unsafe {
+        let handler = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        libc::tcsetpgrp(libc::STDIN_FILENO, previous);
+        libc::signal(libc::SIGTTOU, handler);
+    }
+}
+
+/// Sends the signal to the whole process group of the task. Without a running task the default
+/// action is taken (ttr is terminated)
+extern "C" fn forward(signal: libc::c_int) {
+    let group = TASK_GROUP.load(Ordering::SeqCst);
+    // SAFETY: kill, signal and raise are async-signal-safe
+    unsafe {
+        if group == 0 {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        } else {
+            libc::kill(-group, signal);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! Minimum ttr version required by the config or a task (`requires_ttr: ">=0.6"`).
+//!
+//! Tasks requiring a newer version are loaded with the name and key only, so fields added in
+//! later versions don't break the config. Running such a task reports that ttr is to be
+//! upgraded.
+use crate::Result;
+use anyhow::{bail, Context};
+use serde_yaml::Value;
+
+pub const CURRENT: &str = env!("CARGO_PKG_VERSION");
+
+/// Fields kept for the tasks requiring newer version of ttr
+const KEPT_FIELDS: [&str; 4] = ["name", "key", "description", "requires_ttr"];
+
+/// Checks that the current version satisfies the requirement
+pub fn require(requirement: Option<&str>) -> Result<()> {
+    let Some(requirement) = requirement else {
+        return Ok(());
+    };
+    if !matches(requirement, CURRENT)? {
+        bail!(
+            "Task requires ttr {} (this is {}). Upgrade ttr to use this task",
+            requirement,
+            CURRENT
+        );
+    }
+    Ok(())
+}
+
+/// Strips the tasks of the config requiring newer version of ttr down to [`KEPT_FIELDS`].
+/// Requirement of the config is inherited by its tasks
+pub fn strip_unsupported(config: &mut Value) -> Result<()> {
+    let requirement = config
+        .get("requires_ttr")
+        .map(requirement)
+        .transpose()?
+        .map(str::to_string);
+    strip_group(config, requirement.as_deref())
+}
+
+fn strip_group(group: &mut Value, inherited: Option<&str>) -> Result<()> {
+    if let Some(tasks) = group.get_mut("tasks").and_then(Value::as_sequence_mut) {
+        for task in tasks {
+            let Some(task) = task.as_mapping_mut() else {
+                continue;
+            };
+            let requirement = match task.get("requires_ttr") {
+                Some(value) => requirement(value)?.to_string(),
+                None => match inherited {
+                    Some(inherited) => inherited.to_string(),
+                    None => continue,
+                },
+            };
+            if !matches(&requirement, CURRENT)? {
+                task.retain(|key, _| KEPT_FIELDS.iter().any(|f| key == *f));
+                task.insert("requires_ttr".into(), requirement.into());
+            }
+        }
+    }
+    if let Some(groups) = group.get_mut("groups").and_then(Value::as_sequence_mut) {
+        for group in groups {
+            strip_group(group, inherited)?;
+        }
+    }
+    Ok(())
+}
+
+fn requirement(value: &Value) -> Result<&str> {
+    value.as_str().context("requires_ttr should be a string")
+}
+
+/// Checks the version against the requirement: comma separated comparisons (`>=0.6, <1`).
+/// Bare version is the minimum version
+fn matches(requirement: &str, version: &str) -> Result<bool> {
+    let version = parse(version)?;
+    for comparison in requirement.split(',') {
+        let comparison = comparison.trim();
+        let split = comparison
+            .find(|c: char| c.is_ascii_digit())
+            .with_context(|| format!("Invalid version requirement: {}", requirement))?;
+        let (op, required) = comparison.split_at(split);
+        let required = parse(required)?;
+        let satisfied = match op.trim() {
+            ">=" | "" => version >= required,
+            ">" => version > required,
+            "<=" => version <= required,
+            "<" => version < required,
+            "=" => version == required,
+            op => bail!("Invalid version comparison: {}", op),
+        };
+        if !satisfied {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Parses version into major, minor and patch numbers. Missing numbers are zeroes
+fn parse(version: &str) -> Result<[u64; 3]> {
+    let mut numbers = [0; 3];
+    let parts = version.trim().split('.').collect::<Vec<_>>();
+    if parts.len() > 3 {
+        bail!("Invalid version: {}", version);
+    }
+    for (number, part) in numbers.iter_mut().zip(parts) {
+        *number = part
+            .parse()
+            .with_context(|| format!("Invalid version: {}", version))?;
+    }
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_matches() {
+        assert!(matches(">=0.6", "0.6.0").unwrap());
+        assert!(matches("0.6", "0.7.1").unwrap());
+        assert!(!matches(">=0.6", "0.5.9").unwrap());
+        assert!(matches(">=0.3, <1", "0.3.0").unwrap());
+        assert!(!matches(">0.3", "0.3.0").unwrap());
+        assert!(!matches("=1.2.3", "1.2.4").unwrap());
+        assert!(matches("~1", "1.0.0").is_err());
+        assert!(matches(">=x", "1.0.0").is_err());
+    }
+
+    #[test]
+    fn check_strip_unsupported() {
+        let mut config: Value = serde_yaml::from_str(
+            "
+            requires_ttr: '>=0.1'
+            tasks:
+            - name: future
+              key: f
+              requires_ttr: '>=99'
+              cmd: future
+              future_field: [1, 2]
+            groups:
+            - name: group
+              key: g
+              tasks:
+              - name: current
+                key: c
+                cmd: current
+            ",
+        )
+        .unwrap();
+        strip_unsupported(&mut config).unwrap();
+        let future = &config["tasks"][0];
+        assert_eq!(3, future.as_mapping().unwrap().len());
+        assert_eq!(Some("future"), future["name"].as_str());
+        assert_eq!(
+            Some("current"),
+            config["groups"][0]["tasks"][0]["cmd"].as_str()
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Rerunning the task when the files it depends on change (`watch: ["src/**/*.rs"]`).
+use crate::{ui::Ui, Result};
+use anyhow::Context;
+use crossterm::{
+    event::{KeyCode, KeyModifiers},
+    style::Stylize,
+};
+use glob::{MatchOptions, Pattern};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+/// Changes made within this interval after the first one are considered a single change (eg.
+/// editor saving several files)
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Waits for the files matching the globs (relative to `dir`) to change. Returns false if the
+/// user stopped watching
+pub fn wait(ui: &mut Ui, dir: &Path, globs: &[String]) -> Result<bool> {
+    let patterns = globs
+        .iter()
+        .map(|glob| {
+            let pattern = dir.join(glob);
+            Pattern::new(&pattern.to_string_lossy())
+                .with_context(|| format!("Invalid glob: {}", glob))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    let mut roots: Vec<PathBuf> = globs.iter().map(|glob| dir.join(base_dir(glob))).collect();
+    roots.sort();
+    roots.dedup();
+    for root in roots.iter().filter(|root| root.is_dir()) {
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("Unable to watch {}", root.display()))?;
+    }
+    ui.print(&format!(
+        "{} {} for changes. Press {} to rerun or {} to stop\n",
+        "Watching".stylize().bold(),
+        globs.join(", "),
+        "r".stylize().yellow().bold(),
+        "q".stylize().yellow().bold(),
+    ))?;
+
+    let is_relevant = |event: notify::Result<notify::Event>| match event {
+        Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+            event.paths.iter().any(|path| {
+                patterns
+                    .iter()
+                    .any(|p| p.matches_path_with(path, MATCH_OPTIONS))
+            })
+        }
+        _ => false,
+    };
+    loop {
+        if let Some(event) = ui.poll_key_event(Duration::from_millis(100))? {
+            match event.code {
+                KeyCode::Char('r') => return Ok(true),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                KeyCode::Char('c') if event.modifiers == KeyModifiers::CONTROL => return Ok(false),
+                _ => {}
+            }
+        }
+        if events.try_iter().any(is_relevant) {
+            std::thread::sleep(DEBOUNCE);
+            events.try_iter().for_each(drop);
+            return Ok(true);
+        }
+    }
+}
+
+/// Directory part of the glob before the first component with wildcards
+fn base_dir(glob: &str) -> PathBuf {
+    Path::new(glob)
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .take_while(|c| match c {
+            Component::Normal(name) => !name.to_string_lossy().contains(['*', '?', '[']),
+            _ => true,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_base_dir() {
+        assert_eq!(PathBuf::from("src"), base_dir("src/**/*.rs"));
+        assert_eq!(PathBuf::from("src/bin"), base_dir("src/bin/*.rs"));
+        assert_eq!(PathBuf::new(), base_dir("*.toml"));
+        assert_eq!(PathBuf::new(), base_dir("Cargo.toml"));
+        assert_eq!(PathBuf::from("web"), base_dir("web/*/index.ts"));
+    }
+
+    #[test]
+    fn check_patterns() {
+        let pattern = Pattern::new("/p/src/**/*.rs").unwrap();
+        assert!(pattern.matches_path_with(Path::new("/p/src/main.rs"), MATCH_OPTIONS));
+        assert!(pattern.matches_path_with(Path::new("/p/src/a/b.rs"), MATCH_OPTIONS));
+        let pattern = Pattern::new("/p/*.toml").unwrap();
+        assert!(!pattern.matches_path_with(Path::new("/p/a/Cargo.toml"), MATCH_OPTIONS));
+    }
+}
@@ -0,0 +1,84 @@
+//! Windows console specific process handling.
+//!
+//! Tasks are started in a separate process group. Ctrl-C pressed while a task is running is
+//! forwarded to the whole group of the task as `CTRL_BREAK_EVENT` (the only event which can be
+//! sent to a process group), while ttr itself survives and shows the task status.
+use std::{
+    os::windows::process::CommandExt,
+    path::Path,
+    process::Command,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Once,
+    },
+};
+use windows_sys::Win32::{
+    Foundation::{BOOL, FALSE, TRUE},
+    System::{
+        Console::{
+            GenerateConsoleCtrlEvent, SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_C_EVENT,
+        },
+        Threading::CREATE_NEW_PROCESS_GROUP,
+    },
+};
+
+/// Process group id of the running task or 0 if there is no running task
+static TASK_GROUP: AtomicU32 = AtomicU32::new(0);
+
+/// Configures the command to be started in a new process group
+pub fn configure(command: &mut Command) {
+    command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+}
+
+/// Adds arguments to the command. `cmd.exe` doesn't follow the quoting rules of the C runtime
+/// used by [`Command::arg()`], so the command line is passed to it as is
+pub fn add_args(command: &mut Command, program: &str, args: &[String]) {
+    let is_cmd = Path::new(program)
+        .file_stem()
+        .is_some_and(|stem| stem.eq_ignore_ascii_case("cmd"));
+    for arg in args {
+        if is_cmd {
+            command.raw_arg(arg);
+        } else {
+            command.arg(arg);
+        }
+    }
+}
+
+/// Forwards console control events to the process group of the task until [`task_exited()`]
+pub fn task_started(pid: u32) {
+    static HANDLER: Once = Once::new();
+    HANDLER.call_once(|| unsafe {
+        SetConsoleCtrlHandler(Some(ctrl_handler), TRUE);
+    });
+    TASK_GROUP.store(pid, Ordering::SeqCst);
+}
+
+pub fn task_exited() {
+    TASK_GROUP.store(0, Ordering::SeqCst);
+}
+
+unsafe extern "system" fn ctrl_handler(ctrl_type: u32) -> BOOL {
+    let group = TASK_GROUP.load(Ordering::SeqCst);
+    if group == 0 || !matches!(ctrl_type, CTRL_C_EVENT | CTRL_BREAK_EVENT) {
+        // Default handler terminates ttr
+        return FALSE;
+    }
+    GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, group);
+    TRUE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_task_group() {
+        task_started(42);
+        assert_eq!(42, TASK_GROUP.load(Ordering::SeqCst));
+        task_exited();
+        assert_eq!(0, TASK_GROUP.load(Ordering::SeqCst));
+        // Ctrl-C is left to the default handler terminating ttr when no task is running
+        assert_eq!(FALSE, unsafe { ctrl_handler(CTRL_C_EVENT) });
+    }
+}
@@ -0,0 +1,1738 @@
+#![cfg(unix)]
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+/// Headless run of ttr in a temporary directory with a given config and key script. Optional
+/// settings are given by the builder methods
+struct Headless<'a> {
+    name: &'a str,
+    config: &'a str,
+    keys: &'a str,
+    args: &'a [&'a str],
+    files: &'a [(&'a str, &'a str)],
+    env: &'a [(&'a str, &'a str)],
+    trusted: bool,
+    changed: &'a [(&'a str, &'a str)],
+}
+
+impl<'a> Headless<'a> {
+    fn new(name: &'a str, config: &'a str, keys: &'a str) -> Self {
+        Self {
+            name,
+            config,
+            keys,
+            args: &[],
+            files: &[],
+            env: &[],
+            trusted: true,
+            changed: &[],
+        }
+    }
+
+    fn args(mut self, args: &'a [&'a str]) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Executable files created in the temporary directory before running ttr
+    fn files(mut self, files: &'a [(&'a str, &'a str)]) -> Self {
+        self.files = files;
+        self
+    }
+
+    /// Additional environment variables of ttr
+    fn env(mut self, env: &'a [(&'a str, &'a str)]) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Config is not trusted beforehand (using `ttr trust`)
+    fn untrusted(mut self) -> Self {
+        self.trusted = false;
+        self
+    }
+
+    /// Files written after the config is trusted (eg. to check that the change revokes the trust)
+    fn changed(mut self, files: &'a [(&'a str, &'a str)]) -> Self {
+        self.changed = files;
+        self
+    }
+
+    /// Returns process output and the rendered frames
+    fn run(self) -> (Output, String) {
+        let dir = std::env::temp_dir().join(format!("ttr-{}-{}", self.name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // Empty config means the project has none
+        if !self.config.is_empty() {
+            fs::write(dir.join(".ttr.yaml"), self.config).unwrap();
+        }
+        fs::write(dir.join("keys"), self.keys).unwrap();
+        for (path, content) in self.files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let frames_path: PathBuf = dir.join("frames");
+        // Configs of the developer are not used. The project is not looked up past the home
+        let home = dir.join("home");
+        fs::create_dir_all(&home).unwrap();
+
+        let ttr = |args: &[&str]| {
+            Command::new(env!("CARGO_BIN_EXE_ttr"))
+                .args(args)
+                .current_dir(&dir)
+                .env("HOME", &home)
+                .env("XDG_STATE_HOME", &dir)
+                .env("XDG_CONFIG_HOME", &dir)
+                // Tests could be run inside tmux, which would be notified about the runs
+                .env_remove("TMUX")
+                .envs(self.env.iter().copied())
+                .output()
+                .unwrap()
+        };
+        if self.trusted {
+            assert!(ttr(&["trust"]).status.success());
+        }
+        for (path, content) in self.changed {
+            fs::write(dir.join(path), content).unwrap();
+        }
+        let headless_args = ["--input-from", "keys", "--output-to", "frames"];
+        let output = ttr(&[&headless_args[..], self.args].concat());
+        let frames = fs::read_to_string(frames_path).unwrap_or_default();
+        fs::remove_dir_all(&dir).unwrap();
+        (output, frames)
+    }
+}
+
+const CONFIG: &str = "
+groups:
+- name: greetings
+  key: g
+  tasks:
+  - name: hello
+    key: h
+    cmd: echo hello
+    confirm: true
+tasks:
+- name: fail
+  key: f
+  cmd: 'false'
+";
+
+#[test]
+fn check_group_navigation_and_confirm() {
+    let (output, frames) = Headless::new("navigation", CONFIG, "x g <BS> g h <Enter>").run();
+    assert!(output.status.success());
+    assert_eq!("hello\n", String::from_utf8_lossy(&output.stdout));
+    assert!(frames.contains("No task for key: x"));
+    assert!(frames.contains("SELECT A TASK → greetings"));
+    assert!(frames.contains("Task completed"));
+}
+
+#[test]
+fn check_failed_task_repeat() {
+    let (output, frames) = Headless::new("repeat", CONFIG, "f r q").run();
+    assert!(output.status.success());
+    assert_eq!(2, frames.matches("Task failed").count());
+}
+
+#[test]
+fn check_exhausted_script_is_an_error() {
+    let (output, _) = Headless::new("exhausted", CONFIG, "g").run();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn check_output_capture() {
+    let config = "
+tasks:
+- name: hello
+  key: h
+  cmd: echo hello
+  capture: true
+  confirm: true
+";
+    let (output, frames) = Headless::new("capture", config, "h <Enter>").run();
+    assert!(output.status.success());
+    assert_eq!("hello\n", String::from_utf8_lossy(&output.stdout));
+    assert!(frames.contains("to view the output (6 bytes)"));
+}
+
+#[test]
+fn check_error_screen() {
+    let config = "
+tasks:
+- name: broken
+  key: b
+  cmd: echo hello
+  working_dir: no-such-dir
+";
+    let (output, frames) = Headless::new("error", config, "b <Enter> q").run();
+    assert!(output.status.success());
+    assert!(frames.contains("Working directory doesn't exist"));
+    assert!(frames.contains("Task broken failed to start"));
+}
+
+#[test]
+fn check_not_found_hints() {
+    let config = "
+tasks:
+- name: missing
+  key: m
+  cmd: ttr-no-such-command --help
+";
+    let (output, frames) = Headless::new("hints", config, "m q").run();
+    assert!(output.status.success());
+    assert!(frames.contains("`ttr-no-such-command` is not found in PATH"));
+    assert!(frames.contains("to retry in a login shell"));
+}
+
+#[test]
+fn check_external_terminal_target() {
+    let config = "
+tasks:
+- name: server
+  key: s
+  cmd: 'true'
+  target: external-terminal
+";
+    let args = ["--loop", "--terminal", "env"];
+    let (output, frames) = Headless::new("external", config, "s q").args(&args).run();
+    assert!(output.status.success());
+    assert!(frames.contains("Task server started in external terminal"));
+}
+
+#[test]
+fn check_target_plugin() {
+    let config = "
+tasks:
+- name: plugged
+  key: p
+  cmd: echo hello
+  target: echo-plugin
+";
+    // Plugin fails reporting the spec it was given
+    let plugin = "#!/bin/sh\ncat >&2\nexit 1\n";
+    let files = [("ttr/plugins/echo-plugin", plugin)];
+    let (output, frames) = Headless::new("plugin", config, "p q").files(&files).run();
+    assert!(output.status.success());
+    assert!(frames.contains("Plugin echo-plugin failed"));
+    assert!(frames.contains(r#""cmd":"echo hello""#));
+}
+
+#[test]
+fn check_tmux_pane() {
+    let config = "
+tasks:
+- name: server
+  key: s
+  cmd: echo serving
+  run_in: tmux-pane
+";
+    // Fake tmux accepting only a detached split in the working directory of the task
+    let tmux = "#!/bin/sh\n[ \"$1 $2 $3\" = \"split-window -d -c\" ] && [ \"$5\" = -- ]\n";
+    let path = format!("bin:{}", std::env::var("PATH").unwrap());
+    let env = [
+        ("PATH", path.as_str()),
+        ("TMUX", "/tmp/tmux-1000/default,1,0"),
+    ];
+    let files = [("bin/tmux", tmux)];
+    let args = ["--loop"];
+    let (output, frames) = Headless::new("tmux", config, "s q")
+        .args(&args)
+        .files(&files)
+        .env(&env)
+        .run();
+    assert!(output.status.success());
+    assert!(
+        frames.contains("Task server started in tmux pane"),
+        "{}",
+        frames
+    );
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("serving"));
+
+    let env = [("PATH", path.as_str()), ("TMUX", "")];
+    let (_, frames) = Headless::new("tmux-outside", config, "s q")
+        .args(&args)
+        .files(&files)
+        .env(&env)
+        .run();
+    assert!(frames.contains("only inside of it"), "{}", frames);
+}
+
+#[test]
+fn check_verbose_rerun() {
+    let config = "
+tasks:
+- name: build
+  key: b
+  cmd: sh -c 'echo build{{verbose_flags}}; exit 1'
+  verbose_flags: ' -vv'
+";
+    let (output, frames) = Headless::new("verbose", config, "b V q").run();
+    assert!(output.status.success());
+    assert_eq!(
+        "build\nbuild -vv\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert!(frames.contains("to rerun with extra verbosity"));
+}
+
+#[test]
+fn check_up_to_date_task_is_skipped() {
+    let config = "
+tasks:
+- name: build
+  key: b
+  cmd: cp input output
+  inputs: [input]
+  outputs: [output]
+";
+    let files = [("input", "")];
+    let keys = "b <Enter> b ! b <Enter> q";
+    let (output, frames) = Headless::new("cache", config, keys)
+        .args(&["--loop"])
+        .files(&files)
+        .run();
+    assert!(output.status.success());
+    assert!(frames.contains("Task build is up to date"));
+    assert!(frames.contains("FORCE"));
+    assert!(frames.contains("1 → output (0B)"));
+}
+
+#[test]
+fn check_track_changes() {
+    let config = "
+tasks:
+- name: setup
+  key: s
+  cmd: sh -c 'touch new && rm old'
+  track_changes: true
+";
+    let files = [("old", "")];
+    let (output, frames) = Headless::new("changes", config, "s q").files(&files).run();
+    assert!(output.status.success());
+    assert!(frames.contains("+ new"));
+    assert!(frames.contains("- old"));
+
+    // Failure to take the snapshot doesn't end the session
+    let config = "
+tasks:
+- {name: setup, key: s, cmd: 'true', working_dir: old, track_changes: true}
+";
+    let (output, frames) = Headless::new("changes-failed", config, "s q")
+        .files(&files)
+        .run();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("Unable to track changes"), "{}", stderr);
+    assert!(frames.contains("Unable to start task setup"), "{}", frames);
+}
+
+#[test]
+fn check_untrusted_config() {
+    let (output, frames) = Headless::new("untrusted", CONFIG, "n q").untrusted().run();
+    assert!(output.status.success());
+    assert!(frames.contains(".ttr.yaml is not trusted"));
+    assert!(frames.contains("No tasks configured"));
+
+    let (output, frames) = Headless::new("trusted", CONFIG, "y f q").untrusted().run();
+    assert!(output.status.success());
+    assert!(frames.contains("Task failed"));
+}
+
+#[test]
+fn check_args_are_quoted() {
+    let config = "
+tasks:
+- name: print
+  key: p
+  cmd: printf '%s\\n' {{args}}
+- name: raw
+  key: r
+  cmd: printf '%s\\n' {{args}}
+  raw: true
+";
+    let args = ["--", "hello world", "$HOME"];
+    let (output, _) = Headless::new("args", config, "p").args(&args).run();
+    assert!(output.status.success());
+    assert_eq!(
+        "hello world\n$HOME\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let (output, _) = Headless::new("raw-args", config, "r")
+        .args(&["--", "a b"])
+        .run();
+    assert_eq!("a\nb\n", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn check_locale_and_tz() {
+    let config = "
+tasks:
+- name: date
+  key: d
+  cmd: echo $LANG $LC_ALL $TZ
+  locale: C
+  tz: Asia/Tokyo
+";
+    let (output, _) = Headless::new("locale", config, "d").run();
+    assert!(output.status.success());
+    assert_eq!("C C Asia/Tokyo\n", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn check_preview() {
+    let (output, frames) =
+        Headless::new("preview", CONFIG, "<Tab> g h <Esc> h <Enter> <Enter>").run();
+    assert!(output.status.success());
+    assert!(frames.contains("SELECT A TASK → greetings PREVIEW"));
+    assert!(frames.contains("  echo hello\n"));
+    assert_eq!("hello\n", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn check_script() {
+    let config = "
+tasks:
+- name: script
+  key: s
+  shell: sh
+  script: |
+    echo one
+    echo {{cwd}} | grep -q ttr-script
+    echo two
+";
+    let (output, _) = Headless::new("script", config, "s").run();
+    assert!(output.status.success());
+    assert_eq!("one\ntwo\n", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn check_script_shebang() {
+    let config = "
+tasks:
+- name: awk
+  key: a
+  script: |
+    #!/usr/bin/env awk -f
+    BEGIN { print \"hello from awk\" }
+";
+    let (output, _) = Headless::new("shebang", config, "a").run();
+    assert!(output.status.success());
+    assert_eq!("hello from awk\n", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn check_exit_code_mapping() {
+    let config = "
+tasks:
+- name: grep
+  key: g
+  cmd: grep -q needle keys
+  allow_exit_codes: [0, 1]
+- name: lint
+  key: l
+  cmd: sh -c 'exit 2'
+  warn_exit_codes: [2]
+";
+    let (output, frames) = Headless::new("exit-codes", config, "g l <Enter> q")
+        .args(&["--loop"])
+        .run();
+    assert!(output.status.success());
+    assert!(frames.contains("Task grep completed"));
+    assert!(frames.contains("Task completed with warnings (exit status: 2)"));
+    assert!(frames.contains("Task lint completed with warnings"));
+}
+
+#[test]
+fn check_quiet() {
+    let config = "
+tasks:
+- name: ok
+  key: o
+  cmd: echo hidden
+- name: fail
+  key: f
+  cmd: sh -c 'echo shown; exit 1'
+";
+    let args = ["--quiet", "--loop"];
+    let (output, _) = Headless::new("quiet", config, "o f <Enter> q")
+        .args(&args)
+        .run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("(7B of output hidden)\nshown\n"));
+    assert!(!stdout.contains("hidden\n"));
+}
+
+#[test]
+fn check_group_badges() {
+    let args = ["--loop"];
+    let (output, frames) = Headless::new("badges", CONFIG, "g h <Enter> q")
+        .args(&args)
+        .run();
+    assert!(output.status.success());
+    assert!(frames.contains("g → greetings   ○"));
+    assert!(frames.contains("g → greetings   ●"));
+}
+
+#[test]
+fn check_requires_newer_ttr() {
+    let config = "
+tasks:
+- name: future
+  key: f
+  requires_ttr: '>=99'
+  cmd: echo future
+  future_field: {}
+";
+    let (output, frames) = Headless::new("requires", config, "f q").run();
+    assert!(output.status.success());
+    assert!(frames.contains("Task requires ttr >=99"));
+    assert!(frames.contains("Upgrade ttr to use this task"));
+}
+
+#[test]
+fn check_args_prompts() {
+    let config = "
+tasks:
+- name: checkout
+  key: c
+  cmd: printf '%s\\n' {{branch}} {{mode}}
+  args:
+  - name: branch
+    prompt: Branch
+  - name: mode
+    default: debug
+    choices: [debug, release]
+";
+    let keys = "c my <Space> branch <Enter> 2 c <Enter> <Enter> q";
+    let (output, frames) = Headless::new("prompts", config, keys)
+        .args(&["--loop"])
+        .run();
+    assert!(output.status.success());
+    assert_eq!(
+        "my branch\nrelease\nmy branch\nrelease\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert!(frames.contains("2 → release"));
+    assert!(frames.contains("Branch [my branch]:"));
+}
+
+#[test]
+fn check_availability() {
+    let config = "
+groups:
+- name: deploy
+  key: d
+  available:
+    hours: 00:00-00:00
+  tasks:
+  - name: production
+    key: p
+    cmd: echo deployed
+";
+    let (output, _) = Headless::new("available", config, "d p").run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("is not available now"));
+    assert!(stdout.contains("(00:00-00:00), use --override"));
+
+    let (output, _) = Headless::new("override", config, "d p")
+        .args(&["--override"])
+        .run();
+    assert_eq!("deployed\n", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn check_dependencies() {
+    let config = "
+tasks:
+- name: generate
+  key: g
+  cmd: echo generate
+- name: build
+  key: b
+  cmd: echo build
+  depends_on: [g]
+- name: deploy
+  key: d
+  cmd: echo deploy
+  depends_on: [build, fail]
+- name: fail
+  key: f
+  cmd: 'false'
+";
+    let (output, _) = Headless::new("depends", config, "b d q")
+        .args(&["--loop"])
+        .run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(2, stdout.matches(" generate\ngenerate\n").count());
+    assert!(stdout.contains(" build\nbuild\n"));
+    assert!(!stdout.contains("deploy\n"));
+}
+
+#[test]
+fn check_approval() {
+    let config = "
+tasks:
+- name: deploy
+  key: d
+  cmd: echo deployed
+  approval_cmd: sh -c 'test -f approved || (echo $TTR_TASK needs approval; exit 1)'
+";
+    let (output, frames) = Headless::new("approval", config, "d q").run();
+    assert!(output.status.success());
+    assert!(frames.contains("Task is not approved: deploy needs approval"));
+
+    let files = [("approved", "")];
+    let (output, _) = Headless::new("approved", config, "d").files(&files).run();
+    assert_eq!("deployed\n", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn check_search() {
+    let (output, frames) = Headless::new("search", CONFIG, "/ l <Down> <Enter> <Enter>").run();
+    assert!(output.status.success());
+    assert_eq!("hello\n", String::from_utf8_lossy(&output.stdout));
+    assert!(frames.contains("SEARCH l▏"));
+    assert!(frames.contains("→ gh     greetings → hello"));
+}
+
+#[test]
+fn check_countdown() {
+    let config = "
+tasks:
+- name: destroy
+  key: d
+  cmd: echo destroyed
+  dangerous: true
+";
+    let (output, frames) = Headless::new("countdown", config, "d").run();
+    assert!(output.status.success());
+    assert_eq!("destroyed\n", String::from_utf8_lossy(&output.stdout));
+    assert!(frames.contains("in 1… (press any key to cancel)"));
+
+    let (output, frames) = Headless::new("countdown-cancel", config, "d x").run();
+    assert!(output.status.success());
+    assert!(!frames.contains("in 4…"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("cancelled"));
+}
+
+#[test]
+fn check_make_import() {
+    let config = "
+import:
+  type: make
+  path: Makefile
+";
+    let makefile = "build: ## Build it\n\t@echo building\n";
+    let files = [("Makefile", makefile)];
+    let (output, frames) = Headless::new("make", config, "m b").files(&files).run();
+    assert!(output.status.success());
+    assert!(frames.contains("m → make"));
+    assert_eq!("building\n", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn check_parallel() {
+    let config = "
+tasks:
+- name: dev
+  key: d
+  parallel: [backend, frontend]
+- name: backend
+  key: b
+  cmd: echo api
+- name: frontend
+  key: f
+  cmd: sh -c 'echo assets; exit 3'
+";
+    let (output, _) = Headless::new("parallel", config, "d").run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(" api\n"));
+    assert!(stdout.contains(" assets\n"));
+    assert!(stdout.contains("(frontend: exit status: 3)"));
+}
+
+#[test]
+fn check_serial() {
+    let config = "
+tasks:
+- name: release
+  key: r
+  serial: [build, test, publish]
+- name: build
+  key: b
+  cmd: echo building
+- name: test
+  key: t
+  cmd: 'false'
+- name: publish
+  key: p
+  cmd: echo publishing
+";
+    let (output, frames) = Headless::new("serial", config, "r <Enter>").run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("building\n"));
+    assert!(!stdout.contains("publishing\n"));
+    assert!(frames.contains("failed (exit status: 1)"));
+    assert!(frames.contains("publish  not run"));
+}
+
+#[test]
+fn check_sandbox_dir() {
+    let config = "
+tasks:
+- name: clean build
+  key: c
+  sandbox_dir: temp
+  cmd: sh -c 'rm input.txt && echo built > output.txt'
+- name: list
+  key: l
+  cmd: ls
+";
+    let files = [("input.txt", "source")];
+    let (output, frames) = Headless::new("sandbox", config, "c <Enter> l <Enter> q")
+        .args(&["--loop"])
+        .files(&files)
+        .run();
+    assert!(output.status.success());
+    assert!(frames.contains("Changes made in the sandbox are discarded"));
+    assert!(frames.contains("input.txt"));
+    assert!(frames.contains("output.txt"));
+    // The working directory itself is left intact
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("input.txt"), "{}", stdout);
+    assert!(!stdout.contains("output.txt"), "{}", stdout);
+}
+
+#[test]
+fn check_watch() {
+    let config = "
+tasks:
+- name: test
+  key: t
+  cmd: echo testing
+  watch: ['src/**/*.rs']
+";
+    let (output, frames) = Headless::new("watch", config, "t r q").run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(2, stdout.matches("testing\n").count());
+    assert!(frames.contains("src/**/*.rs for changes"));
+}
+
+#[test]
+fn check_run_without_selector() {
+    let config = "
+groups:
+- name: build
+  key: b
+  tasks:
+  - name: generate
+    key: g
+    cmd: echo generate
+  - name: release
+    key: r
+    cmd: echo {{target}}
+    depends_on: [generate]
+    args:
+    - name: target
+      default: linux
+tasks:
+- name: fail
+  key: f
+  cmd: sh -c 'exit 3'
+";
+    let (output, _) = Headless::new("run", config, "")
+        .args(&["run", "b", "r"])
+        .run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("generate\n"));
+    assert!(stdout.contains("linux\n"));
+
+    let args = ["run", "build.release", "--arg", "target=macos"];
+    let (output, _) = Headless::new("run-names", config, "").args(&args).run();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("macos\n"));
+
+    let (output, _) = Headless::new("run-fail", config, "")
+        .args(&["run", "f"])
+        .run();
+    assert_eq!(Some(3), output.status.code());
+}
+
+#[test]
+fn check_min_size() {
+    let config = "
+tasks:
+- name: k9s
+  key: k
+  cmd: echo k9s
+  min_size: {cols: 120, rows: 30}
+";
+    let (output, frames) = Headless::new("min-size", config, "k <Esc> k r <Enter> q")
+        .args(&["--loop"])
+        .run();
+    assert!(output.status.success());
+    assert!(frames.contains("Terminal is 80x24, the task needs at least 120x30"));
+    assert_eq!(
+        1,
+        String::from_utf8_lossy(&output.stdout)
+            .matches("k9s\n")
+            .count()
+    );
+}
+
+#[test]
+fn check_history() {
+    let config = "
+tasks:
+- name: echo
+  key: e
+  cmd: echo hi
+";
+    let keys = ". e . h 1 q";
+    let (output, frames) = Headless::new("history", config, keys)
+        .args(&["--loop"])
+        .run();
+    assert!(output.status.success());
+    assert!(frames.contains("No tasks were run yet"));
+    assert!(frames.contains("RECENT TASKS"));
+    assert_eq!(
+        3,
+        String::from_utf8_lossy(&output.stdout)
+            .matches("hi\n")
+            .count()
+    );
+}
+
+#[test]
+fn check_tui_focus() {
+    let config = "
+groups:
+- name: tools
+  key: t
+  tasks:
+  - {name: top, key: p, cmd: echo top, tui: true}
+  - {name: echo, key: e, cmd: echo hi}
+";
+    let (output, frames) = Headless::new("tui-focus", config, "t p e q")
+        .args(&["--loop"])
+        .run();
+    assert!(output.status.success());
+    // Selector returns to the group after the full-screen task, but not after the regular one
+    assert_eq!(2, frames.matches("SELECT A TASK → tools").count());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("hi\n"));
+}
+
+#[test]
+fn check_timeout() {
+    let config = "
+tasks:
+- name: hang
+  key: h
+  cmd: sleep 10
+  timeout: 200ms
+";
+    let (output, _) = Headless::new("timeout", config, "h q")
+        .args(&["--loop"])
+        .run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("timed out"));
+    assert!(stdout.contains("after 200ms"));
+}
+
+#[test]
+fn check_remember_position() {
+    let config = "
+remember_position: true
+groups:
+- name: tools
+  key: t
+  tasks:
+  - {name: echo, key: e, cmd: echo hi}
+";
+    let (output, frames) = Headless::new("position", config, "t e e q")
+        .args(&["--loop"])
+        .run();
+    assert!(output.status.success());
+    assert_eq!(3, frames.matches("SELECT A TASK → tools").count());
+    assert_eq!(
+        2,
+        String::from_utf8_lossy(&output.stdout)
+            .matches("hi\n")
+            .count()
+    );
+}
+
+#[test]
+fn check_palette() {
+    let config = "
+tasks:
+- {name: echo, key: e, cmd: echo hi}
+";
+    let keys = "<C-p> reload <Enter> <C-p> loop <Enter> e e q";
+    let (output, frames) = Headless::new("palette", config, keys).run();
+    assert!(output.status.success());
+    assert!(frames.contains("Toggle loop mode"));
+    assert!(frames.contains("Config reloaded"));
+    assert!(frames.contains("Loop mode is on"));
+    assert_eq!(
+        2,
+        String::from_utf8_lossy(&output.stdout)
+            .matches("hi\n")
+            .count()
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn check_process_group() {
+    let config = "
+tasks:
+- name: stat
+  key: s
+  cmd: cut -d' ' -f1,5 /proc/self/stat
+";
+    let (output, _) = Headless::new("process-group", config, "s").run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout
+        .lines()
+        .find(|l| l.split(' ').count() == 2 && l.chars().all(|c| c.is_ascii_digit() || c == ' '))
+        .unwrap();
+    // Task is the leader of its own process group
+    let (pid, group) = line.split_once(' ').unwrap();
+    assert_eq!(pid, group);
+}
+
+#[test]
+fn check_confirm_before() {
+    let config = "
+tasks:
+- name: destroy
+  key: d
+  cmd: echo destroyed
+  confirm_before: Destroy the staging environment?
+";
+    let (output, frames) = Headless::new("confirm-before", config, "d n d y q")
+        .args(&["--loop"])
+        .run();
+    assert!(output.status.success());
+    assert!(frames.contains("Destroy the staging environment? [y/N]"));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(1, stdout.matches("destroyed\n").count());
+
+    let args = ["run", "d"];
+    let (output, _) = Headless::new("confirm-before-run", config, "")
+        .args(&args)
+        .run();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn check_runtime_modes() {
+    let config = "
+tasks:
+- {name: echo, key: e, cmd: echo hi}
+";
+    let keys = "<C-p> loop <Enter> <C-p> confirm <Enter> e <Enter> q";
+    let (output, frames) = Headless::new("runtime-modes", config, keys).run();
+    assert!(output.status.success());
+    assert!(frames.contains("Confirm mode is on"));
+    assert!(frames.contains("LOOP CONFIRM"));
+    // Task completion is confirmed with Enter before returning to the selector
+    assert!(frames.contains("Task completed"));
+}
+
+#[test]
+fn check_extra_tasks() {
+    let config = "
+tasks:
+- {name: build, key: b, cmd: make}
+- {name: test, key: t, cmd: make test}
+";
+    let extra = "tasks: [{name: deploy review app, key: b, cmd: echo deploying}]";
+    let env = [("TTR_EXTRA_TASKS", extra)];
+    let (output, _) = Headless::new("extra-tasks", config, "")
+        .args(&["list"])
+        .env(&env)
+        .run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("deploy review app"));
+    assert!(stdout.contains("test"));
+    assert!(!stdout.contains("build"));
+}
+
+#[test]
+fn check_env_expansion() {
+    let config = r#"
+tasks:
+- name: greet
+  key: g
+  cmd: echo "$GREETING ${TTR_TEST_NAME} from ${TTR_TEST_MISSING:-default}" '$${TTR_TEST_NAME}'
+  env:
+    GREETING: hello-${TTR_TEST_NAME}
+"#;
+    let env = [("TTR_TEST_NAME", "team")];
+    let (output, _) = Headless::new("env-expansion", config, "")
+        .args(&["run", "g"])
+        .env(&env)
+        .run();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("hello-team team from default ${TTR_TEST_NAME}"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn check_env_expansion_defaults() {
+    let config = r#"
+tasks:
+- name: show
+  key: s
+  cmd: echo "[$FALLBACK] $(basename "$PWD")"
+  working_dir: ${TTR_TEST_MISSING_DIR:-.}/backend
+  env:
+    FALLBACK: ${TTR_TEST_MISSING:-fallback}
+"#;
+    let (output, _) = Headless::new("env-expansion-defaults", config, "")
+        .files(&[("backend/.keep", "")])
+        .args(&["run", "s"])
+        .run();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[fallback] backend"), "{}", stdout);
+}
+
+#[test]
+fn check_group_settings() {
+    let config = r#"
+groups:
+- name: backend
+  key: b
+  clear_env: true
+  env:
+    PROFILE: dev
+    ORIGIN: group
+  tasks:
+  - name: show
+    key: s
+    cmd: echo "$PROFILE $ORIGIN [$TTR_TEST_SECRET]"
+    env:
+      ORIGIN: task
+"#;
+    let env = [("TTR_TEST_SECRET", "leaked")];
+    let (output, _) = Headless::new("group-settings", config, "")
+        .args(&["run", "bs"])
+        .env(&env)
+        .run();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("dev task []"), "{}", stdout);
+}
+
+#[test]
+fn check_exec() {
+    let args = [
+        "exec",
+        "--name",
+        "quick",
+        "--env",
+        "GREETING=hello",
+        "--",
+        "echo $GREETING",
+    ];
+    let (output, _) = Headless::new("exec", "tasks: []", "").args(&args).run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Task quick"), "{}", stdout);
+    assert!(stdout.contains("hello"), "{}", stdout);
+
+    let args = ["exec", "--", "sh", "-c", "echo 'two words'; exit 3"];
+    let (output, _) = Headless::new("exec-fail", "tasks: []", "")
+        .args(&args)
+        .run();
+    assert_eq!(Some(3), output.status.code());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("two words"), "{}", stdout);
+}
+
+#[test]
+fn check_fzf_selector() {
+    let config = "
+groups:
+- name: cargo
+  key: c
+  tasks:
+  - {name: build, key: b, cmd: echo building, description: Build the project}
+  - {name: test, key: t, cmd: echo testing}
+";
+    // Fake finder choosing the build task by the whole line it is given
+    let fzf = "#!/bin/sh\ngrep \"$(printf '^cb\\tcargo → build\\tBuild the project$')\"\n";
+    let path = format!("bin:{}", std::env::var("PATH").unwrap());
+    let env = [("PATH", path.as_str())];
+    let args = ["--selector", "fzf"];
+    let files = [("bin/fzf", fzf)];
+    let (output, _) = Headless::new("fzf-selector", config, "")
+        .args(&args)
+        .files(&files)
+        .env(&env)
+        .run();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("building"), "{}", stdout);
+    assert!(!stdout.contains("testing"), "{}", stdout);
+}
+
+#[test]
+fn check_env_file() {
+    let config = r#"
+groups:
+- name: deploy
+  key: d
+  env_file: .env
+  tasks:
+  - name: production
+    key: p
+    cmd: echo "$TARGET $REGION $TOKEN"
+    env_file: .env.production
+    env:
+      TOKEN: from-config
+"#;
+    let files = [
+        (".env", "TARGET=staging\nREGION=eu\nTOKEN=from-file\n"),
+        (
+            ".env.production",
+            "# production\nexport TARGET=\"production\"\n",
+        ),
+    ];
+    let (output, _) = Headless::new("env-file", config, "")
+        .args(&["run", "dp"])
+        .files(&files)
+        .run();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("production eu from-config"), "{}", stdout);
+}
+
+#[test]
+fn check_log_files() {
+    let config = "
+tasks:
+- name: build
+  key: b
+  cmd: echo building
+  log: logs/build-%Y%m%d.log
+- name: lint step
+  key: l
+  cmd: echo linting >&2
+- name: report
+  key: r
+  cmd: cat logs/build-*.log out/lint-step-*.log
+  depends_on: [b, l]
+";
+    let args = ["--log-dir", "out", "run", "r"];
+    let (output, _) = Headless::new("log-files", config, "").args(&args).run();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert_eq!(2, stdout.matches("building").count(), "{}", stdout);
+    assert!(stdout.contains("linting"), "{}", stdout);
+}
+
+#[test]
+fn check_status_file() {
+    let config = "
+tasks:
+- {name: release, key: r, serial: [build, show, publish]}
+- {name: build, key: b, cmd: 'true'}
+- {name: show, key: s, cmd: cat state/status.json}
+- {name: publish, key: p, cmd: 'false'}
+";
+    let args = ["--status-file", "state/status.json", "run", "r"];
+    let (output, _) = Headless::new("status-file", config, "").args(&args).run();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(r#""last":{"name":"build","path":"b","outcome":"success""#));
+    assert!(stdout.contains(r#""queued":1"#), "{}", stdout);
+}
+
+#[test]
+fn check_scripts_are_removed() {
+    let config = "
+tasks:
+- {name: script, key: s, script: echo scripted}
+- {name: list, key: l, cmd: ls tmp/ttr-session-*}
+";
+    let (output, _) = Headless::new("scripts-removed", config, "s l q")
+        .args(&["--loop"])
+        .files(&[("tmp/.keep", "")])
+        .env(&[("TMPDIR", "tmp")])
+        .run();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("scripted\n"), "{}", stdout);
+    assert!(!stdout.contains("ttr-script"), "{}", stdout);
+}
+
+#[test]
+fn check_open_result() {
+    let config = "
+tasks:
+- {name: docs, key: d, cmd: 'true', open: 'docs/{{page}}.html', args: [{name: page, default: index}]}
+- {name: report, key: r, cmd: 'true', open: report.html, confirm_open: true}
+- {name: opened, key: o, cmd: cat opened}
+";
+    let opener = "#!/bin/sh\necho \"$1\" >> opened\n";
+    let path = format!("bin:{}", std::env::var("PATH").unwrap());
+    let env = [("PATH", path.as_str())];
+    let files = [("bin/xdg-open", opener)];
+    let args = ["--loop"];
+    let keys = "d <Enter> r <Enter> o q";
+    let (output, frames) = Headless::new("open", config, keys)
+        .args(&args)
+        .files(&files)
+        .env(&env)
+        .run();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("/docs/index.html\n"), "{}", stdout);
+    assert!(!stdout.contains("report.html"), "{}", stdout);
+    assert!(frames.contains("Press O to open report.html"), "{}", frames);
+
+    // Task succeeded even though its result can't be opened
+    let files = [("bin/xdg-open", "#!/bin/sh\nexit 1\n")];
+    let args = ["run", "d"];
+    let (output, _) = Headless::new("open-failed", config, "")
+        .args(&args)
+        .files(&files)
+        .env(&env)
+        .run();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("xdg-open failed"), "{}", stderr);
+}
+
+#[test]
+fn check_presets() {
+    let cargo = "#!/bin/sh\necho \"cargo $*\"\n";
+    let path = format!("bin:{}", std::env::var("PATH").unwrap());
+    let env = [("PATH", path.as_str())];
+    let files = [("Cargo.toml", ""), ("bin/cargo", cargo)];
+    let args = ["run", "c", "t"];
+    let (output, _) = Headless::new("presets", "", "")
+        .args(&args)
+        .files(&files)
+        .env(&env)
+        .untrusted()
+        .run();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("cargo test"), "{}", stdout);
+
+    let args = ["--no-presets", "run", "c", "t"];
+    let (output, _) = Headless::new("no-presets", "", "")
+        .args(&args)
+        .files(&files)
+        .env(&env)
+        .untrusted()
+        .run();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn check_show_context() {
+    let config = "
+show_context: true
+groups:
+- name: web
+  key: w
+  working_dir: web
+  env: {MODE: dev}
+  tasks:
+  - {name: serve, key: s, cmd: pwd, confirm_before: true}
+";
+    let files = [("web/index.html", "")];
+    let (output, frames) = Headless::new("context", config, "w s y")
+        .files(&files)
+        .run();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("env: MODE=dev"), "{}", stdout);
+    let dir = stdout.lines().last().unwrap();
+    assert!(
+        stdout.contains(&format!("working dir: {}", dir)),
+        "{}",
+        stdout
+    );
+    assert!(frames.contains("working dir: "), "{}", frames);
+}
+
+#[test]
+fn check_arrow_navigation() {
+    let keys = "<Down> <Enter> <Down> j k <Enter> <Enter>";
+    let (output, frames) = Headless::new("arrows", CONFIG, keys).run();
+    assert!(output.status.success());
+    assert_eq!("hello\n", String::from_utf8_lossy(&output.stdout));
+    assert!(frames.contains("SELECT A TASK → greetings"));
+    assert!(frames.contains("browse"));
+}
+
+#[test]
+fn check_describe() {
+    let config = "
+groups:
+- name: database
+  key: d
+  description: Local database management
+  tasks:
+  - {name: migrate, key: m, cmd: echo migrating, description: Apply pending migrations}
+";
+    let keys = "? d <Esc> ? d <Enter> ? m <Enter>";
+    let (output, frames) = Headless::new("describe", config, keys).run();
+    assert!(output.status.success());
+    assert_eq!("migrating\n", String::from_utf8_lossy(&output.stdout));
+    assert!(frames.contains("Press the key of the task or group to describe"));
+    assert!(frames.contains("Local database management"));
+    assert!(
+        frames.contains("m → migrate  Apply pending migrations"),
+        "{}",
+        frames
+    );
+    assert!(frames.contains("working dir: "));
+}
+
+#[test]
+fn check_layout_fallback() {
+    let config = format!("layout_fallback: true\n{}", CONFIG);
+    // `пр` is typed by the `g` and `h` keys in the Russian layout
+    let (output, frames) = Headless::new("layout", &config, "пр <Enter>").run();
+    assert!(output.status.success());
+    assert_eq!("hello\n", String::from_utf8_lossy(&output.stdout));
+    assert!(frames.contains("SELECT A TASK → greetings"));
+
+    let (_, frames) = Headless::new("no-layout", CONFIG, "п q").run();
+    assert!(frames.contains("No task for key: п"));
+}
+
+#[test]
+fn check_loop_dashboard() {
+    let config = "
+tasks:
+- {name: build, key: b, cmd: 'true'}
+- {name: test, key: t, cmd: 'false'}
+- {name: lint, key: l, cmd: 'true'}
+";
+    let keys = "b <Enter> t <Enter> q";
+    let (output, frames) = Headless::new("dashboard", config, keys)
+        .args(&["--loop"])
+        .run();
+    assert!(output.status.success());
+    let last = frames.rsplit("SELECT A TASK").next().unwrap();
+    assert!(last.contains('✓'), "{}", last);
+    assert!(last.contains('✗'), "{}", last);
+    assert_eq!(2, last.matches("0.0s").count(), "{}", last);
+}
+
+#[test]
+fn check_training_mode() {
+    let keys = "g h <Enter> g h <Enter> q";
+    let (output, frames) = Headless::new("training", CONFIG, keys)
+        .args(&["--loop", "--train"])
+        .run();
+    assert!(output.status.success());
+    assert!(frames.contains("TRAINING"));
+    let last = frames.rsplit("SELECT A TASK").next().unwrap();
+    assert!(last.contains("SLOWEST"), "{}", last);
+}
+
+#[test]
+fn check_present_mode() {
+    let config = "
+groups:
+- name: dotfiles
+  key: d
+  tags: [personal]
+  tasks:
+  - {name: sync dotfiles, key: s, cmd: echo syncing}
+tasks:
+- {name: demo, key: b, cmd: echo demo}
+- {name: my notes, key: n, cmd: echo notes, tags: [personal]}
+- {name: deploy, key: p, cmd: echo deploying, dangerous: true}
+";
+    let (output, frames) = Headless::new("present", config, "p q")
+        .args(&["--present"])
+        .run();
+    assert!(output.status.success());
+    assert!(frames.contains("PRESENT"));
+    assert!(frames.contains("demo"));
+    assert!(!frames.contains("dotfiles"));
+    assert!(!frames.contains("my notes"));
+    assert!(frames.contains("Dangerous task deploy"));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("deploying"));
+}
+
+#[test]
+fn check_theme() {
+    let config = "
+theme: {key: yellow, group_key: '#ff8000'}
+tasks:
+- {name: ok, key: o, cmd: 'true'}
+";
+    let args = ["run", "o"];
+    let (output, _) = Headless::new("theme", config, "").args(&args).run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\x1b[38;5;10m"), "{:?}", stdout);
+
+    let env = [("NO_COLOR", "1")];
+    let (output, _) = Headless::new("no-color", config, "")
+        .args(&args)
+        .env(&env)
+        .run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("completed"), "{:?}", stdout);
+    assert!(!stdout.contains("\x1b[38;"), "{:?}", stdout);
+
+    let env = [("TTR_THEME", "key=purple")];
+    let (output, _) = Headless::new("bad-theme", config, "")
+        .args(&args)
+        .env(&env)
+        .untrusted()
+        .run();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unknown color: purple"), "{}", stderr);
+}
+
+#[test]
+fn check_local_overrides() {
+    let config = "
+tasks:
+- {name: greet, key: g, cmd: 'echo $GREETING', env: {GREETING: hello}}
+";
+    let local = "
+overrides:
+  g: {env: {GREETING: hi}, append: there}
+";
+    let files = [(".ttr.local.yaml", local)];
+    let (output, _) = Headless::new("local-overrides", config, "g")
+        .files(&files)
+        .run();
+    assert!(output.status.success());
+    assert_eq!("hi there\n", String::from_utf8_lossy(&output.stdout));
+
+    let changed = [(".ttr.local.yaml", "overrides: {g: {cmd: echo pwned}}")];
+    let (output, frames) = Headless::new("local-overrides-changed", config, "n q")
+        .files(&files)
+        .changed(&changed)
+        .run();
+    assert!(output.status.success());
+    assert!(frames.contains(".ttr.yaml is not trusted"), "{}", frames);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("pwned"));
+}
+
+#[test]
+fn check_deprecated() {
+    let config = "
+tasks:
+- {name: old, key: o, cmd: echo old, deprecated: use new instead}
+- {name: new, key: n, cmd: echo new}
+";
+    let (output, frames) = Headless::new("deprecated", config, "o").run();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Task old is deprecated"), "{}", stdout);
+    assert!(stdout.ends_with(": use new instead\nold\n"), "{}", stdout);
+    assert!(frames.contains("o → old         †"), "{}", frames);
+    assert!(frames.contains("n → new          "), "{}", frames);
+}
+
+#[test]
+fn check_resize() {
+    let config = "
+tasks:
+- {name: alpha, key: a, cmd: 'true'}
+- {name: bravo, key: b, cmd: 'true'}
+- {name: charlie, key: c, cmd: 'true'}
+- {name: delta, key: d, cmd: 'true'}
+";
+    let (output, frames) = Headless::new("resize", config, "<Resize:30x24> q").run();
+    assert!(output.status.success());
+    let mut frames = frames.split("--- frame").skip(1);
+    let wide = frames.next().unwrap();
+    assert!(wide
+        .lines()
+        .any(|l| l.contains("alpha") && l.contains("charlie")));
+    let narrow = frames.next().unwrap();
+    assert!(
+        narrow.lines().any(|l| l.trim() == "a → alpha"),
+        "{}",
+        narrow
+    );
+    assert!(!narrow
+        .lines()
+        .any(|l| l.contains("alpha") && l.contains("charlie")));
+}
+
+#[test]
+fn check_tmux_notification() {
+    let config = "
+tasks:
+- {name: build, key: b, cmd: 'true'}
+";
+    // Fake tmux reporting the pane of ttr as inactive and logging the messages shown
+    let tmux = "#!/bin/sh\n[ \"$2\" = -p ] && echo '0 1 1' && exit\necho \"tmux $*\" >&2\n";
+    let path = format!("bin:{}", std::env::var("PATH").unwrap());
+    let env = [
+        ("PATH", path.as_str()),
+        ("TMUX", "/tmp/tmux-1000/default,1,0"),
+    ];
+    let files = [("bin/tmux", tmux)];
+    let (output, _) = Headless::new("notify", config, "b")
+        .files(&files)
+        .env(&env)
+        .run();
+    assert!(output.status.success());
+    assert_eq!(
+        "tmux display-message -- ttr: Task build completed\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let config = "
+notify_backend: none
+tasks:
+- {name: build, key: b, cmd: 'true'}
+";
+    let (output, _) = Headless::new("no-notify", config, "b")
+        .files(&files)
+        .env(&env)
+        .run();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn check_ssh() {
+    let config = "
+tasks:
+- name: deploy
+  key: d
+  cmd: echo $GREETING from $(basename $PWD)
+  ssh: deploy@staging
+  remote_dir: app
+  env: {GREETING: hello}
+";
+    // Fake ssh running the remote command locally
+    let ssh = "#!/bin/sh\n[ \"$1\" = -- ] && shift\necho \"ssh $1\" >&2\nsh -c \"$2\"\n";
+    let path = format!("bin:{}", std::env::var("PATH").unwrap());
+    let env = [("PATH", path.as_str())];
+    let files = [("bin/ssh", ssh), ("app/.keep", "")];
+    let (output, _) = Headless::new("ssh", config, "d")
+        .files(&files)
+        .env(&env)
+        .run();
+    assert!(output.status.success());
+    assert_eq!(
+        "ssh deploy@staging\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("hello from app\n"));
+
+    let config = "
+tasks:
+- {name: deploy, key: d, cmd: make deploy, host: staging, image: alpine}
+";
+    let (output, _) = Headless::new("ssh-image", config, "d")
+        .files(&files)
+        .env(&env)
+        .run();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("only have one of"));
+}
+
+#[test]
+fn check_running_container() {
+    let config = "
+tasks:
+- {name: test, key: t, cmd: echo tested, container: dev, env: {GREETING: hello}}
+- {name: migrate, key: m, cmd: echo migrated, compose_service: web, remote_dir: /app}
+";
+    // Fake engine logging its arguments and running the command locally
+    let docker = "#!/bin/sh\necho \"docker $*\" >&2\nfor a; do :; done\nsh -c \"$a\"\n";
+    let files = [("docker", docker)];
+    let env = [("TTR_CONTAINER_ENGINE", "./docker")];
+    let (output, _) = Headless::new("exec", config, "t")
+        .files(&files)
+        .env(&env)
+        .run();
+    assert!(output.status.success());
+    assert_eq!(
+        "docker exec -i -e GREETING=hello dev sh -c exec echo tested\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("tested\n"));
+
+    let (output, _) = Headless::new("compose", config, "m")
+        .files(&files)
+        .env(&env)
+        .run();
+    assert!(output.status.success());
+    assert_eq!(
+        "docker compose exec -T -w /app web sh -c exec echo migrated\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("migrated\n"));
+}
+
+#[test]
+fn check_secrets() {
+    let config = "
+tasks:
+- {name: deploy, key: d, cmd: echo token=$API_TOKEN, secrets: [API_TOKEN]}
+";
+    // Secret is asked once per session
+    let keys = "d h u n t e r 2 <Enter> d q";
+    let (output, frames) = Headless::new("secrets", config, keys)
+        .args(&["--loop"])
+        .run();
+    assert!(output.status.success());
+    assert_eq!(
+        "token=hunter2\ntoken=hunter2\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert!(frames.contains("API_TOKEN: *******"));
+    assert!(!frames.contains("hunter2"));
+
+    // Secrets set in the environment are not asked for
+    let env = [("API_TOKEN", "from-env")];
+    let (output, _) = Headless::new("env-secrets", config, "d").env(&env).run();
+    assert!(output.status.success());
+    assert_eq!("token=from-env\n", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn check_unreadable_inputs() {
+    let config = "
+tasks:
+- {name: build, key: b, cmd: echo built, inputs: ['[']}
+";
+    let (output, _) = Headless::new("unreadable-inputs", config, "")
+        .args(&["run", "b"])
+        .run();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("built"));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unable to check if task build is up to date"));
+}
+
+#[test]
+fn check_unwritable_audit_log() {
+    let config = "
+tasks:
+- {name: build, key: b, cmd: echo built}
+";
+    let args = ["--audit-log", ".ttr.yaml/audit.jsonl", "run", "b"];
+    let (output, _) = Headless::new("unwritable-audit-log", config, "")
+        .args(&args)
+        .run();
+    assert!(!output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("built"));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unable to write audit log"));
+}
+
+#[test]
+fn check_background_run_keeps_terminal() {
+    use std::{
+        ffi::CStr,
+        os::{
+            fd::{AsRawFd, FromRawFd},
+            unix::process::CommandExt,
+        },
+    };
+
+    let dir = std::env::temp_dir().join(format!("ttr-background-{}", std::process::id()));
+    let home = dir.join("home");
+    fs::create_dir_all(&home).unwrap();
+    let config = "
+tasks:
+- {name: show, key: s, cmd: 'ps -o tpgid= -p $$ > task'}
+";
+    fs::write(dir.join(".ttr.yaml"), config).unwrap();
+    // Job control puts ttr into a background group, the terminal stays with the shell. Checks
+    // are run in the background too, as foreground jobs get the terminal
+    let script = r#"
+set -m
+"$TTR" trust
+"$TTR" run s &
+wait
+echo "$$ $(ps -o tpgid= -p $$)" > shell &
+wait
+"#;
+
+    // SAFETY: the file descriptor of the pseudo-terminal is owned by the file
+    let master = unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        assert!(fd >= 0 && libc::grantpt(fd) == 0 && libc::unlockpt(fd) == 0);
+        fs::File::from_raw_fd(fd)
+    };
+    // SAFETY: ptsname returns a string owned by libc which is copied right away
+    let name = unsafe { CStr::from_ptr(libc::ptsname(master.as_raw_fd())) };
+    let name = name.to_str().unwrap().to_string();
+    let terminal = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(name)
+        .unwrap();
+    let mut shell = Command::new("sh");
+    shell
+        .args(["-c", script])
+        .current_dir(&dir)
+        .env("TTR", env!("CARGO_BIN_EXE_ttr"))
+        .env("HOME", &home)
+        .env("XDG_STATE_HOME", &dir)
+        .env("XDG_CONFIG_HOME", &dir)
+        .env_remove("TMUX")
+        .stdin(terminal.try_clone().unwrap())
+        .stdout(terminal.try_clone().unwrap())
+        .stderr(terminal);
+    // SAFETY: only async-signal-safe functions are called between fork and exec
+    unsafe {
+        shell.pre_exec(|| {
+            // The terminal becomes the controlling one of a new session led by the shell
+            if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let status = shell.status().unwrap();
+    drop(master);
+    let task = fs::read_to_string(dir.join("task")).unwrap_or_default();
+    let shell = fs::read_to_string(dir.join("shell")).unwrap_or_default();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(status.success());
+    let shell: Vec<&str> = shell.split_whitespace().collect();
+    assert_eq!(2, shell.len());
+    assert_eq!(shell[0], task.trim(), "task took the terminal");
+    assert_eq!(
+        shell[0], shell[1],
+        "terminal wasn't given back to the shell"
+    );
+}